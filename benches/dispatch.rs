@@ -0,0 +1,45 @@
+//! Measures raw instruction throughput, i.e. how fast instructions are decoded and dispatched.
+//! Run with `cargo bench`.
+
+use iron_chip::{Chip8Config, Chip8Emulator};
+use std::hint::black_box;
+use std::time::Instant;
+
+const FRAMES: u32 = 50_000;
+const INSTRUCTIONS_PER_FRAME: u16 = 1000;
+
+fn main() {
+    // A loop touching most instruction classes, without drawing so the display doesn't dominate
+    let program = vec![
+        0x60, 0x12, // V0 = 0x12
+        0x71, 0x01, // V1 += 1
+        0x82, 0x14, // V2 += V1
+        0x83, 0x26, // V3 = V2 >> 1
+        0x84, 0x32, // V4 &= V3
+        0x30, 0x12, // Skip if V0 == 0x12
+        0x00, 0x00,
+        0xA3, 0x00, // I = 0x300
+        0xF4, 0x1E, // I += V4
+        0xF3, 0x33, // BCD of V3
+        0xF2, 0x65, // Load V0 - V2
+        0xC5, 0x0F, // V5 = random & 0x0F
+        0xE5, 0x9E, // Skip if key V5 is pressed
+        0x12, 0x00, // Jump to 0x200
+    ];
+
+    let config =
+        Chip8Config { instructions_per_frame: INSTRUCTIONS_PER_FRAME, seed: Some(0), ..Default::default() };
+    let mut emulator = Chip8Emulator::new(program, config);
+
+    let start = Instant::now();
+    for _ in 0..FRAMES {
+        black_box(emulator.run_60hz_frame([false; 16]));
+    }
+    let elapsed = start.elapsed();
+
+    let instructions = FRAMES as f64 * INSTRUCTIONS_PER_FRAME as f64;
+    println!(
+        "{instructions} instructions in {elapsed:?}, {:.1} million instructions per second",
+        instructions / elapsed.as_secs_f64() / 1_000_000.0
+    );
+}
@@ -0,0 +1,146 @@
+use std::io;
+use std::io::Write;
+
+#[cfg(feature = "audio")]
+use rodio::source::SquareWave;
+#[cfg(feature = "audio")]
+use rodio::{DeviceSinkBuilder, MixerDeviceSink, Player, Source};
+
+pub const DEFAULT_BEEP_FREQUENCY: f32 = 440.0;
+
+/// Keep the square wave quiet, it's unpleasant at full volume
+#[cfg(feature = "audio")]
+const BEEP_VOLUME: f32 = 0.2;
+
+/// Plays a tone while the sound timer is active. Falls back to the terminal bell when built without
+/// the `audio` feature, or when no audio device could be opened.
+pub struct Chip8Audio {
+    #[cfg(feature = "audio")]
+    output: Option<(MixerDeviceSink, Player)>,
+
+    playing: bool,
+
+    /// Muting silences the output without stopping the tone, so unmuting mid-beep picks it back up
+    muted: bool,
+}
+
+impl Chip8Audio {
+    pub fn new(frequency: f32) -> Self {
+        let audio = Self {
+            #[cfg(feature = "audio")]
+            output: Self::open_output(frequency),
+            playing: false,
+            muted: false,
+        };
+
+        if audio.has_output() {
+            info!("Playing {frequency}Hz tone for sound");
+        } else {
+            info!("Using terminal bell for sound");
+        }
+
+        audio
+    }
+
+    #[cfg(feature = "audio")]
+    fn open_output(frequency: f32) -> Option<(MixerDeviceSink, Player)> {
+        let mut sink = match DeviceSinkBuilder::open_default_sink() {
+            Ok(sink) => sink,
+            Err(err) => {
+                warn!("Couldn't open audio device, falling back to terminal bell: {err}");
+                return None;
+            }
+        };
+        sink.log_on_drop(false);
+
+        // The tone runs forever, and is paused / resumed rather than re-created for every beep
+        let player = Player::connect_new(sink.mixer());
+        player.pause();
+        player.append(SquareWave::new(frequency).amplify(BEEP_VOLUME));
+
+        Some((sink, player))
+    }
+
+    #[cfg(feature = "audio")]
+    fn has_output(&self) -> bool {
+        self.output.is_some()
+    }
+
+    #[cfg(not(feature = "audio"))]
+    fn has_output(&self) -> bool {
+        false
+    }
+
+    /// Starts or stops the tone. Should be called every frame with the emulator's sound state.
+    pub fn set_playing(&mut self, playing: bool) {
+        if playing == self.playing {
+            return;
+        }
+        self.playing = playing;
+
+        #[cfg(feature = "audio")]
+        if let Some((_, player)) = &self.output {
+            if playing {
+                player.play();
+            } else {
+                player.pause();
+            }
+            return;
+        }
+
+        if playing && !self.muted {
+            print!("\x07");
+            io::stdout().flush().unwrap();
+        }
+    }
+
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+
+        #[cfg(feature = "audio")]
+        if let Some((_, player)) = &self.output {
+            player.set_volume(if muted { 0.0 } else { 1.0 });
+        }
+    }
+
+    pub fn is_muted(&self) -> bool {
+        self.muted
+    }
+
+    /// Whether sound is actually coming out, i.e. the tone is playing and not muted
+    pub fn is_sounding(&self) -> bool {
+        self.playing && !self.muted
+    }
+}
+
+#[cfg(feature = "audio")]
+impl Drop for Chip8Audio {
+    fn drop(&mut self) {
+        // Stop the tone before the device goes away so it doesn't get cut off mid-sample
+        if let Some((_, player)) = &self.output {
+            player.stop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mute() {
+        let mut audio = Chip8Audio::new(DEFAULT_BEEP_FREQUENCY);
+        audio.set_muted(true);
+        assert!(!audio.is_sounding());
+
+        // The tone keeps going while muted, so unmuting brings it straight back
+        audio.set_playing(true);
+        assert!(!audio.is_sounding());
+        audio.set_muted(false);
+        assert!(audio.is_sounding());
+
+        audio.set_playing(false);
+        assert!(!audio.is_sounding());
+        assert!(!audio.is_muted());
+    }
+}
@@ -0,0 +1,104 @@
+use crate::emulator::Chip8Emulator;
+use crate::headless;
+use crate::opcode::Opcode;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// How long one kind of instruction took in total over a benchmark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpcodeTiming {
+    /// The instruction's pattern, e.g. `8XY4`
+    pub pattern: &'static str,
+    pub count: u64,
+    pub total: Duration,
+}
+
+impl OpcodeTiming {
+    pub fn average(&self) -> Duration {
+        self.total.div_f64(self.count.max(1) as f64)
+    }
+}
+
+/// The results of [`run_benchmark`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BenchReport {
+    pub instructions: u64,
+    pub elapsed: Duration,
+
+    /// Slowest kind of instruction first, by total time
+    pub opcodes: Vec<OpcodeTiming>,
+}
+
+impl BenchReport {
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions as f64 / self.elapsed.as_secs_f64()
+    }
+}
+
+/// Runs the emulator flat out for at least `instructions` instructions and times it, with no keys
+/// pressed.
+///
+/// The run happens twice from a fresh reset. The first run goes a frame at a time like normal and
+/// gives the overall speed. The second steps one instruction at a time to time each kind of
+/// instruction, which is slower because of the timing itself, and doesn't tick the timers, so
+/// only the relative times are meaningful.
+pub fn run_benchmark(emulator: &mut Chip8Emulator, instructions: u64) -> BenchReport {
+    emulator.reset();
+    let start_instructions = emulator.instructions_run();
+    let start = Instant::now();
+    headless::run_for_instructions(emulator, instructions, &[]);
+    let elapsed = start.elapsed();
+    let instructions_run = emulator.instructions_run() - start_instructions;
+
+    emulator.reset();
+    let mut timings: HashMap<&'static str, (u64, Duration)> = HashMap::new();
+    for _ in 0..instructions {
+        let pattern = Opcode::parse(emulator.next_instruction()).pattern();
+
+        let start = Instant::now();
+        emulator.step();
+        let elapsed = start.elapsed();
+
+        let (count, total) = timings.entry(pattern).or_default();
+        *count += 1;
+        *total += elapsed;
+    }
+
+    let mut opcodes: Vec<OpcodeTiming> = timings
+        .into_iter()
+        .map(|(pattern, (count, total))| OpcodeTiming { pattern, count, total })
+        .collect();
+
+    // Ties are broken by pattern so the order is stable
+    opcodes.sort_by(|a, b| b.total.cmp(&a.total).then(a.pattern.cmp(b.pattern)));
+
+    BenchReport { instructions: instructions_run, elapsed, opcodes }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::Chip8Config;
+
+    #[test]
+    fn test_run_benchmark() {
+        let program = vec![
+            0x60, 0x01, // V0 = 1
+            0x71, 0x01, // V1 += 1
+            0x81, 0x04, // V1 += V0
+            0x12, 0x02, // Jump to 0x202
+        ];
+
+        let config = Chip8Config { instructions_per_frame: 10, ..Default::default() };
+        let mut emulator = Chip8Emulator::new(program, config);
+        let report = run_benchmark(&mut emulator, 1000);
+
+        assert_eq!(report.instructions, 1000);
+        assert!(report.instructions_per_second() > 0.0);
+
+        let mut patterns: Vec<&str> = report.opcodes.iter().map(|timing| timing.pattern).collect();
+        patterns.sort();
+        assert_eq!(patterns, vec!["1NNN", "6XNN", "7XNN", "8XY4"]);
+        assert_eq!(report.opcodes.iter().map(|timing| timing.count).sum::<u64>(), 1000);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::opcode::Opcode;
+
+/// Converts an opcode into a human readable mnemonic, e.g. `LD V3, 0x12`. Opcodes that aren't
+/// valid instructions are shown as raw data.
+pub fn disassemble(opcode: u16) -> String {
+    match Opcode::parse(opcode) {
+        Opcode::ClearScreen => "CLS".to_string(),
+        Opcode::Return => "RET".to_string(),
+        Opcode::ScrollDown(n) => format!("SCD {n}"),
+        Opcode::ScrollRight => "SCR".to_string(),
+        Opcode::ScrollLeft => "SCL".to_string(),
+        Opcode::LowResolution => "LOW".to_string(),
+        Opcode::HighResolution => "HIGH".to_string(),
+        Opcode::MachineCall(nnn) => format!("SYS {nnn:#05X}"),
+        Opcode::Jump(nnn) => format!("JP {nnn:#05X}"),
+        Opcode::Call(nnn) => format!("CALL {nnn:#05X}"),
+        Opcode::SkipIfEqual { x, nn } => format!("SE V{x:X}, {nn:#04X}"),
+        Opcode::SkipIfNotEqual { x, nn } => format!("SNE V{x:X}, {nn:#04X}"),
+        Opcode::SkipIfRegistersEqual { x, y } => format!("SE V{x:X}, V{y:X}"),
+        Opcode::SetReg { x, nn } => format!("LD V{x:X}, {nn:#04X}"),
+        Opcode::AddToReg { x, nn } => format!("ADD V{x:X}, {nn:#04X}"),
+        Opcode::CopyReg { x, y } => format!("LD V{x:X}, V{y:X}"),
+        Opcode::Or { x, y } => format!("OR V{x:X}, V{y:X}"),
+        Opcode::And { x, y } => format!("AND V{x:X}, V{y:X}"),
+        Opcode::Xor { x, y } => format!("XOR V{x:X}, V{y:X}"),
+        Opcode::Add { x, y } => format!("ADD V{x:X}, V{y:X}"),
+        Opcode::Subtract { x, y } => format!("SUB V{x:X}, V{y:X}"),
+        Opcode::ShiftRight { x, y } => format!("SHR V{x:X}, V{y:X}"),
+        Opcode::SubtractReversed { x, y } => format!("SUBN V{x:X}, V{y:X}"),
+        Opcode::ShiftLeft { x, y } => format!("SHL V{x:X}, V{y:X}"),
+        Opcode::SkipIfRegistersNotEqual { x, y } => format!("SNE V{x:X}, V{y:X}"),
+        Opcode::SetIndex(nnn) => format!("LD I, {nnn:#05X}"),
+        Opcode::JumpWithOffset { nnn, .. } => format!("JP V0, {nnn:#05X}"),
+        Opcode::Random { x, nn } => format!("RND V{x:X}, {nn:#04X}"),
+        Opcode::Draw { x, y, n } => format!("DRW V{x:X}, V{y:X}, {n}"),
+        Opcode::SkipIfKeyPressed { x } => format!("SKP V{x:X}"),
+        Opcode::SkipIfKeyNotPressed { x } => format!("SKNP V{x:X}"),
+        Opcode::GetDelayTimer { x } => format!("LD V{x:X}, DT"),
+        Opcode::WaitForKey { x } => format!("LD V{x:X}, K"),
+        Opcode::SetDelayTimer { x } => format!("LD DT, V{x:X}"),
+        Opcode::SetSoundTimer { x } => format!("LD ST, V{x:X}"),
+        Opcode::AddToIndex { x } => format!("ADD I, V{x:X}"),
+        Opcode::SetIndexToFont { x } => format!("LD F, V{x:X}"),
+        Opcode::SetIndexToBigFont { x } => format!("LD HF, V{x:X}"),
+        Opcode::StoreBcd { x } => format!("LD B, V{x:X}"),
+        Opcode::StoreRegisters { x } => format!("LD [I], V{x:X}"),
+        Opcode::LoadRegisters { x } => format!("LD V{x:X}, [I]"),
+        Opcode::SaveRplFlags { x } => format!("LD R, V{x:X}"),
+        Opcode::LoadRplFlags { x } => format!("LD V{x:X}, R"),
+        // The address is in the next two bytes, see disassemble_rom
+        Opcode::SetIndexLong => "LD I, LONG".to_string(),
+        Opcode::SelectPlanes(planes) => format!("PLANE {planes}"),
+        Opcode::Unknown(raw) => format!("DW {raw:#06X}"),
+    }
+}
+
+/// Disassembles a whole ROM loaded at `start_address`, usually
+/// [`PROGRAM_START_ADDRESS`](crate::emulator::PROGRAM_START_ADDRESS), returning each instruction's
+/// address alongside its mnemonic. F000 NNNN is shown as a single 4 byte instruction.
+pub fn disassemble_rom(rom: &[u8], start_address: u16) -> Vec<(u16, String)> {
+    let mut instructions = vec![];
+    let mut offset = 0;
+
+    while offset < rom.len() {
+        let address = start_address.wrapping_add(offset as u16);
+
+        let mnemonic = match rom[offset..] {
+            [0xF0, 0x00, high, low, ..] => {
+                offset += 2;
+                format!("LD I, {:#06X}", u16::from_be_bytes([high, low]))
+            }
+            [high, low, ..] => disassemble(u16::from_be_bytes([high, low])),
+            // ROMs with an odd length have a trailing byte that can't be an instruction
+            [byte] => format!("DB {byte:#04X}"),
+            [] => unreachable!(),
+        };
+        offset += 2;
+
+        instructions.push((address, mnemonic));
+    }
+
+    instructions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::{ETI_660_START_ADDRESS, PROGRAM_START_ADDRESS};
+
+    #[test]
+    fn test_disassemble() {
+        assert_eq!(disassemble(0x00E0), "CLS");
+        assert_eq!(disassemble(0x00EE), "RET");
+        assert_eq!(disassemble(0x00C5), "SCD 5");
+        assert_eq!(disassemble(0x00FB), "SCR");
+        assert_eq!(disassemble(0x00FC), "SCL");
+        assert_eq!(disassemble(0x00FE), "LOW");
+        assert_eq!(disassemble(0x00FF), "HIGH");
+        assert_eq!(disassemble(0x0123), "SYS 0x123");
+        assert_eq!(disassemble(0x1ABC), "JP 0xABC");
+        assert_eq!(disassemble(0x2ABC), "CALL 0xABC");
+        assert_eq!(disassemble(0x3312), "SE V3, 0x12");
+        assert_eq!(disassemble(0x4312), "SNE V3, 0x12");
+        assert_eq!(disassemble(0x5340), "SE V3, V4");
+        assert_eq!(disassemble(0x6312), "LD V3, 0x12");
+        assert_eq!(disassemble(0x7312), "ADD V3, 0x12");
+        assert_eq!(disassemble(0x8AB0), "LD VA, VB");
+        assert_eq!(disassemble(0x8AB4), "ADD VA, VB");
+        assert_eq!(disassemble(0x8ABE), "SHL VA, VB");
+        assert_eq!(disassemble(0x9AB0), "SNE VA, VB");
+        assert_eq!(disassemble(0xA123), "LD I, 0x123");
+        assert_eq!(disassemble(0xB123), "JP V0, 0x123");
+        assert_eq!(disassemble(0xC3FF), "RND V3, 0xFF");
+        assert_eq!(disassemble(0xD016), "DRW V0, V1, 6");
+        assert_eq!(disassemble(0xD010), "DRW V0, V1, 0");
+        assert_eq!(disassemble(0xE59E), "SKP V5");
+        assert_eq!(disassemble(0xE5A1), "SKNP V5");
+        assert_eq!(disassemble(0xF50A), "LD V5, K");
+        assert_eq!(disassemble(0xF555), "LD [I], V5");
+        assert_eq!(disassemble(0xF565), "LD V5, [I]");
+        assert_eq!(disassemble(0xF575), "LD R, V5");
+        assert_eq!(disassemble(0xF585), "LD V5, R");
+        assert_eq!(disassemble(0xF201), "PLANE 2");
+    }
+
+    #[test]
+    fn test_disassemble_invalid() {
+        assert_eq!(disassemble(0x5341), "DW 0x5341");
+        assert_eq!(disassemble(0x8AB8), "DW 0x8AB8");
+        assert_eq!(disassemble(0xE500), "DW 0xE500");
+        assert_eq!(disassemble(0xFFFF), "DW 0xFFFF");
+    }
+
+    #[test]
+    fn test_disassemble_rom() {
+        let rom = [0x63, 0x12, 0xA2, 0x00, 0x12];
+
+        assert_eq!(
+            disassemble_rom(&rom, PROGRAM_START_ADDRESS),
+            vec![
+                (0x200, "LD V3, 0x12".to_string()),
+                (0x202, "LD I, 0x200".to_string()),
+                (0x204, "DB 0x12".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_rom_long_index() {
+        let rom = [0xF0, 0x00, 0x12, 0x34, 0x63, 0x12, 0xF0, 0x00];
+
+        assert_eq!(
+            disassemble_rom(&rom, PROGRAM_START_ADDRESS),
+            vec![
+                (0x200, "LD I, 0x1234".to_string()),
+                (0x204, "LD V3, 0x12".to_string()),
+                // Cut off before the address
+                (0x206, "LD I, LONG".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_disassemble_rom_eti_660() {
+        let rom = [0x63, 0x12, 0x16, 0x00];
+
+        assert_eq!(
+            disassemble_rom(&rom, ETI_660_START_ADDRESS),
+            vec![(0x600, "LD V3, 0x12".to_string()), (0x602, "JP 0x600".to_string())]
+        );
+    }
+}
@@ -1,16 +1,96 @@
+use std::collections::HashSet;
 use std::iter::zip;
-use rand::Rng;
-use crate::window;
-use crate::window::{HEIGHT, WIDTH};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+use std::time::Duration;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use crate::error::Chip8Error;
+use crate::opcode::{Opcode, OPCODE_KINDS, PATTERNS};
+use crate::quirks::Quirks;
 
-const RAM_SIZE: usize = 4096;
+mod builder;
+mod condition;
+mod config;
+mod state;
+
+pub use builder::Chip8EmulatorBuilder;
+pub use condition::{BreakCondition, BreakRegister, CmpOp};
+pub use config::Chip8Config;
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+
+/// Size of the SUPER-CHIP high resolution display
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+pub const DEFAULT_FOREGROUND_COLOR: u32 = 0xFFFFFFFF;
+pub const DEFAULT_BACKGROUND_COLOR: u32 = 0x0;
+
+/// XO-CHIP pixels lit on only the second plane
+pub const DEFAULT_PLANE_2_COLOR: u32 = 0xFF555555;
+
+/// XO-CHIP pixels lit on both planes
+pub const DEFAULT_BOTH_PLANES_COLOR: u32 = 0xFFAAAAAA;
+
+/// Which of the 16 keys, 0x0 - 0xF, are held down
+pub type KeyboardState = [bool; 16];
+
+/// Called with the emulator and an instruction, see [`Chip8Emulator::set_pre_hook`]
+pub type InstructionHook = Box<dyn FnMut(&Chip8Emulator, &DecodedInstruction) + Send>;
+
+/// The original 4KB of memory
+pub const RAM_SIZE: usize = 4096;
+
+/// XO-CHIP extends memory to the full 64KB a 16-bit I can address
+pub const XO_CHIP_RAM_SIZE: usize = 65536;
+
+/// Valid memory sizes: the original 4KB, or XO-CHIP's 64KB
+pub const MEMORY_SIZES: [usize; 2] = [RAM_SIZE, XO_CHIP_RAM_SIZE];
+
+/// The HP48 has 8 user flags, so FX75 / FX85 can only save V0 - V7
+const RPL_FLAG_COUNT: usize = 8;
+
+/// Roughly 700 instructions per second, which suits most ROMs
+pub const DEFAULT_INSTRUCTIONS_PER_FRAME: u16 = 12;
+
+/// How many times a second the delay and sound timers tick
+pub const TIMER_FREQUENCY: u32 = 60;
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// One second of a jump to itself, which is plenty to rule out the program doing anything else
+pub const DEFAULT_IDLE_LOOP_FRAMES: u32 = 60;
+
+/// How many instructions [`Chip8Emulator::step_over`] runs waiting for a subroutine to return
+/// before giving up, since it could be waiting on a timer that never ticks
+pub const STEP_OVER_MAX_INSTRUCTIONS: u32 = 1_000_000;
 
 /// First 0x200 bytes are reserved for the interpreter itself plus fonts
-const PROGRAM_START_ADDRESS: u16 = 0x200;
+pub const PROGRAM_START_ADDRESS: u16 = 0x200;
+
+/// Where programs start on the ETI-660, which reserves more memory for its interpreter
+pub const ETI_660_START_ADDRESS: u16 = 0x600;
+
+/// The largest ROM that fits in the original 4KB of memory
+pub const PROGRAM_MAX_SIZE: usize = RAM_SIZE - PROGRAM_START_ADDRESS as usize;
+
+/// Fonts are conventionally stored at 0x50 - 0x9F
+const FONT_START_ADDRESS: u16 = 0x50;
 
-const PROGRAM_MAX_SIZE: usize = RAM_SIZE - PROGRAM_START_ADDRESS as usize;
+/// Each font character is 5 bytes tall
+pub(crate) const FONT_CHARACTER_SIZE: u16 = 5;
 
-const FONTS: [u8; 80] = [
+/// Big font characters are 10 bytes tall, and follow the 16 small ones
+const BIG_FONT_CHARACTER_SIZE: u16 = 10;
+const BIG_FONT_START_ADDRESS: u16 = FONT_START_ADDRESS + 16 * FONT_CHARACTER_SIZE;
+
+/// Valid sizes for a custom font: the 16 small characters, optionally followed by 16 big 8x10
+/// characters
+pub const FONT_SIZES: [usize; 2] = [80, 240];
+
+pub(crate) const FONTS: [u8; 80] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
     0x20, 0x60, 0x20, 0x20, 0x70, // 1
     0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -31,895 +111,3458 @@ const FONTS: [u8; 80] = [
 
 pub struct Chip8Emulator {
     registers: [u8; 16],
-    ram: [u8; RAM_SIZE],
+
+    /// One of the [`MEMORY_SIZES`]
+    ram: Vec<u8>,
     index_register: u16,
     program_counter: u16,
     stack: [u16; 16],
     stack_pointer: u8,
     delay_timer: u8,
-    pub sound_timer: u8,
+    sound_timer: u8,
 
-    previous_keyboard_state: [bool; 16],
-    keyboard_state: [bool; 16],
+    /// The HP48 user flags saved and restored by FX75 / FX85. Like on the calculator, these
+    /// survive a reset.
+    rpl_flags: [u8; RPL_FLAG_COUNT],
 
-    pub display_buffer: [u32; window::WIDTH * window::HEIGHT],
+    previous_keyboard_state: KeyboardState,
+    keyboard_state: KeyboardState,
 
-    instructions_per_frame: u8,
-}
+    /// Sized to match the current resolution. Composited from the plane buffer, so this is only
+    /// ever written alongside it.
+    display_buffer: Vec<u32>,
 
-#[derive(Debug)]
-struct DecodedInstruction {
-    first_nibble: u8,
-    x_register: u8,          // Second nibble
-    y_register: u8,          // Third nibble
-    n_4_bit_constant: u8,    // Fourth nibble
-    nn_8_bit_constant: u8,   // Second byte
-    nnn_12_bit_address: u16, // Second, third and fourth nibbles
-    raw_instruction: u16,
-}
+    /// Which planes each pixel is lit on, bit 0 for plane 1 and bit 1 for plane 2. Only XO-CHIP
+    /// programs use the second plane, everything else only ever sees bit 0.
+    plane_buffer: Vec<u8>,
 
-impl Chip8Emulator {
-    pub fn new(rom: Vec<u8>, instructions_per_frame: u8) -> Self {
-        assert!(rom.len() <= PROGRAM_MAX_SIZE);
+    /// Which planes 00E0, DXYN and the scroll instructions act on, selected by FN01 (XO-CHIP)
+    plane_mask: u8,
+    resolution: Resolution,
 
-        let mut ram = [0; RAM_SIZE];
+    /// Set whenever the display buffer changes, so the frontend can skip redrawing an unchanged
+    /// screen
+    display_dirty: bool,
+    foreground_color: u32,
+    background_color: u32,
+    plane_2_color: u32,
+    both_planes_color: u32,
 
-        // Place fonts into RAM starting at index 50
-        for (index, font_byte) in FONTS.iter().enumerate() {
-            ram[index + 0x50] = *font_byte;
-        }
+    /// Kept around so the program can be reloaded on reset
+    rom: Vec<u8>,
 
-        // Place program into RAM
-        for (index, program_byte) in rom.iter().enumerate() {
-            ram[index + PROGRAM_START_ADDRESS as usize] = *program_byte;
-        }
+    /// Where the ROM is loaded and execution starts, usually [`PROGRAM_START_ADDRESS`]
+    program_start: u16,
+    font: Vec<u8>,
+    instructions_per_frame: u16,
 
-        Self {
-            registers: Default::default(),
-            ram,
-            index_register: 0,
-            program_counter: PROGRAM_START_ADDRESS,
-            stack: Default::default(),
-            stack_pointer: 0,
-            delay_timer: 0,
-            sound_timer: 0,
-            previous_keyboard_state: [false; 16],
-            keyboard_state: [false; 16],
-            display_buffer: [0; window::WIDTH * window::HEIGHT],
-            instructions_per_frame,
-        }
-    }
+    /// The same generator as `StdRng`, but one whose position can be saved in a save state
+    rng: ChaCha12Rng,
 
-    pub fn run_60hz_frame(&mut self, new_keyboard_state: [bool; 16]) {
-        debug!("Running 60hz frame");
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
-            debug!("Decrementing delay counter: {}", self.delay_timer);
-        }
+    /// Fixed seed for the random number generator, so runs are reproducible. Seeded from
+    /// entropy when unset.
+    seed: Option<u64>,
 
-        if self.sound_timer > 0 {
-            self.sound_timer -= 1;
-            debug!("Decrementing sound timer: {}", self.sound_timer);
-        }
+    quirks: Quirks,
 
-        self.previous_keyboard_state = self.keyboard_state;
-        self.keyboard_state = new_keyboard_state;
+    breakpoints: HashSet<u16>,
 
-        for _ in 0..self.instructions_per_frame {
-            self.run_instruction();
-        }
-    }
+    /// Address ranges that stop execution when written to
+    watchpoints: Vec<RangeInclusive<u16>>,
 
-    fn run_instruction(&mut self) {
-        let instruction = self.fetch();
+    /// Conditions that must all hold for execution to stop, see
+    /// [`Chip8Emulator::add_break_condition`]
+    break_conditions: Vec<BreakCondition>,
 
-        self.program_counter += 2;
+    /// Whether the break conditions held after the last instruction, so they only stop execution
+    /// when they first become true rather than on every instruction after
+    break_conditions_met: bool,
 
-        let decoded_instruction = Chip8Emulator::decode(instruction);
-        let DecodedInstruction {
-            first_nibble,
-            x_register,
-            y_register,
-            n_4_bit_constant,
-            nn_8_bit_constant,
-            nnn_12_bit_address,
-            raw_instruction,
-        } = decoded_instruction;
+    /// The watched address written by the current instruction, so the frame can stop and report it
+    watchpoint_hit: Option<u16>,
 
-        let x_register = x_register as usize;
-        let y_register = y_register as usize;
+    /// How many times each kind of instruction has run, indexed by [`Opcode::kind`]
+    instruction_counts: [u64; OPCODE_KINDS],
+    instructions_run: u64,
 
-        match decoded_instruction {
-            //00E0: Clears the screen
-            DecodedInstruction { raw_instruction: 0x00E0, .. } => {
-                self.display_buffer.fill(0);
-                debug!("0x00E0: Clearing display buffer");
-            }
+    /// Set when a breakpoint stops a frame, so execution can resume past it on the next frame
+    stopped_at_breakpoint: bool,
 
-            // 00EE Returns from a subroutine.
-            DecodedInstruction { raw_instruction: 0x00EE, .. } => {
-                self.stack_pointer -= 1;
-                self.program_counter = self.stack[self.stack_pointer as usize];
+    unknown_opcode_policy: UnknownOpcodePolicy,
+    memory_fill: MemoryFill,
 
-                debug!("{raw_instruction:#X}: Returning from subroutine");
-            }
+    /// Set when an unknown opcode halts execution, so the frame can stop and report it
+    halted: bool,
 
-            // 1NNN: Jump to address NNN
-            DecodedInstruction { first_nibble: 0x1, .. } => {
-                self.program_counter = nnn_12_bit_address;
-                debug!("{raw_instruction:#X}: Jumping to address {nnn_12_bit_address:#3X}");
-            }
+    /// How many frames a jump to itself has to run before it's reported as an idle loop, `None`
+    /// to never check
+    idle_loop_frames: Option<u32>,
 
-            // 2NNN: Calls subroutine at NNN.
-            DecodedInstruction { first_nibble: 0x2, .. } => {
-                self.stack[self.stack_pointer as usize] = self.program_counter;
-                self.stack_pointer += 1;
+    /// How many frames in a row have ended on a jump to itself
+    idle_frames: u32,
 
-                self.program_counter = nnn_12_bit_address;
+    /// How far [`Chip8Emulator::advance`] has got through the current 60Hz frame, in instructions
+    frame_cycle: u32,
 
-                debug!(
-                    "{raw_instruction:#X}: Calling subroutine at address {nnn_12_bit_address:#3X}"
-                );
-            }
+    /// Time passed to `advance` that didn't add up to a whole instruction, in nanoseconds times
+    /// instructions per second, so no time is lost to rounding
+    pending_time: u128,
 
-            // 3XNN: Skips the next instruction if VX equals NN
-            // (usually the next instruction is a jump to skip a code block).
-            DecodedInstruction { first_nibble: 0x3, .. } => {
-                if self.registers[x_register] == nn_8_bit_constant {
-                    self.program_counter += 2;
-                    debug!("{raw_instruction:#X}: Skipping because V{x_register} == {nn_8_bit_constant}");
-                } else {
-                    debug!("{raw_instruction:#X}: Not skipping because V{x_register} != {nn_8_bit_constant}");
-                }
-            }
+    /// Set when the display wait quirk ends a frame early, so `advance` waits out the rest of it
+    waiting_for_vblank: bool,
 
-            // 4XNN: Skips the next instruction if VX does not equal NN
-            // (usually the next instruction is a jump to skip a code block).
-            DecodedInstruction { first_nibble: 0x4, .. } => {
-                if self.registers[x_register] != nn_8_bit_constant {
-                    self.program_counter += 2;
-                    debug!("{raw_instruction:#X}: Skipping because V{x_register} != {nn_8_bit_constant}");
-                } else {
-                    debug!("{raw_instruction:#X}: Not skipping because V{x_register} == {nn_8_bit_constant}");
-                }
-            }
+    pre_hook: Option<InstructionHook>,
+    post_hook: Option<InstructionHook>,
+}
 
-            // 5XY0: Skips the next instruction if VX equals VY
-            // (usually the next instruction is a jump to skip a code block).
-            DecodedInstruction { first_nibble: 0x5, n_4_bit_constant: 0x0, ..} => {
-                if self.registers[x_register] == self.registers[y_register] {
-                    self.program_counter += 2;
-                    debug!("{raw_instruction:#X}: Skipping because V{x_register} == V{y_register}");
-                } else {
-                    debug!("{raw_instruction:#X}: Not skipping because V{x_register} != V{y_register}");
-                }
-            }
+/// The display modes switched between by the SUPER-CHIP 00FE / 00FF instructions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// The original 64x32 CHIP-8 display
+    Low,
 
-            // 6XNN: Sets VX to NN
-            DecodedInstruction { first_nibble: 0x6, .. } => {
-                self.registers[x_register] = nn_8_bit_constant;
-                debug!("{raw_instruction:#X}: Setting register {x_register} to {nn_8_bit_constant:#2X}");
-            }
+    /// The SUPER-CHIP 128x64 display
+    High,
+}
 
-            // 7XNN: Adds NN to VX (carry flag is not changed)
-            DecodedInstruction { first_nibble: 0x7, .. } => {
-                self.registers[x_register] = self.registers[x_register].wrapping_add(nn_8_bit_constant);
-                debug!("{raw_instruction:#X}: Adding {nn_8_bit_constant} to register {x_register}");
-            }
+impl Resolution {
+    pub fn width(self) -> usize {
+        match self {
+            Resolution::Low => WIDTH,
+            Resolution::High => HIRES_WIDTH,
+        }
+    }
 
-            // 8XY0: Sets VX to the value of VY
-            DecodedInstruction { first_nibble: 0x8, n_4_bit_constant: 0x0, .. } => {
-                self.registers[x_register] = self.registers[y_register];
+    pub fn height(self) -> usize {
+        match self {
+            Resolution::Low => HEIGHT,
+            Resolution::High => HIRES_HEIGHT,
+        }
+    }
+}
 
-                debug!("{raw_instruction:#X}: Setting V{x_register} to V{y_register}");
-            }
+/// How a call to run_60hz_frame finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameStatus {
+    Completed,
 
-            // 8XY1: Sets VX to VX or VY. (bitwise OR operation).
-            DecodedInstruction { first_nibble: 0x8, n_4_bit_constant: 0x1, .. } => {
-                self.registers[x_register] |= self.registers[y_register];
+    /// Execution stopped before running the instruction at this address
+    HitBreakpoint(u16),
 
-                debug!("{raw_instruction:#X}: Setting V{x_register} |= V{y_register}");
-            }
+    /// The instruction at `program_counter` wrote to the watched `address`. Execution stops after
+    /// the instruction has finished.
+    HitWatchpoint { address: u16, program_counter: u16 },
 
-            // 8XY2: Sets VX to VX and VY. (bitwise AND operation)
-            DecodedInstruction { first_nibble: 0x8, n_4_bit_constant: 2, ..} => {
-                self.registers[x_register] &= self.registers[y_register];
+    /// The instruction at `program_counter` made the break conditions true. Execution stops after
+    /// the instruction has finished.
+    HitBreakCondition { program_counter: u16 },
 
-                debug!("{raw_instruction:#X}: Setting V{x_register} &= V{y_register}");
-            }
+    /// The program has sat on a 1NNN jump to itself at `address` for the configured number of
+    /// frames, which is how many programs signal they're done. Returned on every frame for as long
+    /// as it stays there, with only the timers running. See
+    /// [`Chip8EmulatorBuilder::idle_loop_frames`].
+    IdleLoop { address: u16 },
 
-            // 8XY3: Sets VX to VX xor VY.
-            DecodedInstruction { first_nibble: 0x8, n_4_bit_constant: 3, ..} => {
-                self.registers[x_register] ^= self.registers[y_register];
+    /// Execution stopped at an unknown opcode, with [`UnknownOpcodePolicy::Halt`]. The program
+    /// counter is left pointing at it, so running another frame halts again.
+    Halted { address: u16, opcode: u16 },
+}
 
-                debug!("{raw_instruction:#X}: Setting V{x_register} ^= V{y_register}");
-            }
+/// What to do when the program runs an opcode that isn't recognized or a 0NNN machine code call,
+/// or when the program counter runs off the end of memory
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UnknownOpcodePolicy {
+    /// Log an error and carry on with the next instruction. A program counter past the end of
+    /// memory wraps around to the start.
+    #[default]
+    Ignore,
 
-            // 8XY4: Adds VY to VX. VF is set to 1 when there's an overflow, and to 0 when there is not.
-            DecodedInstruction { first_nibble: 0x8, n_4_bit_constant: 0x4, .. } => {
-                let x_value = self.registers[x_register];
-                let y_value = self.registers[y_register];
-                let (result_value, overflow) = x_value.overflowing_add(y_value);
+    /// Stop before the opcode and report it, which catches bugs while developing a ROM
+    Halt,
 
-                self.registers[x_register] = result_value;
-                self.registers[0xF] = if overflow { 1 } else { 0 };
+    Panic,
+}
 
-                debug!("{raw_instruction:#X}: V{x_register} += V{y_register} - Overflow: {overflow}");
-            }
+impl FromStr for UnknownOpcodePolicy {
+    type Err = String;
 
-            // 8XY5: VY is subtracted from VX. VF is set to 0 when there's an underflow, and 1 when
-            // there is not. (i.e. VF set to 1 if VX >= VY and 0 if not)
-            DecodedInstruction { first_nibble: 0x8, n_4_bit_constant: 0x5, .. } => {
-                let (result, underflow) = self.registers[x_register].overflowing_sub(self.registers[y_register]);
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "ignore" => Ok(UnknownOpcodePolicy::Ignore),
+            "halt" => Ok(UnknownOpcodePolicy::Halt),
+            "panic" => Ok(UnknownOpcodePolicy::Panic),
+            _ => Err(format!("Unknown opcode policy '{policy}', must be one of ignore, halt or panic")),
+        }
+    }
+}
 
-                self.registers[x_register] = result;
-                self.registers[0xF] = if underflow { 0 } else { 1 };
+/// What RAM holds outside the font and the ROM when the emulator starts or resets. Real hardware
+/// boots with whatever happens to be in memory, so filling it with something other than zeros
+/// catches ROMs that read memory they never wrote.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MemoryFill {
+    #[default]
+    Zero,
 
-                debug!("{raw_instruction:#X}: V{x_register} -= V{y_register} - Underflow: {underflow}");
-            }
+    /// Every byte set to the same value
+    Pattern(u8),
 
-            // 8XY6: Shifts VX to the right by 1, then stores the least significant bit of VX
-            // prior to the shift into VF
-            DecodedInstruction {first_nibble: 0x8, n_4_bit_constant: 0x6, .. } => {
-                let lsb = self.registers[x_register] & 0b1;
+    /// Random bytes from the emulator's seed, so a seeded run fills memory the same way every time
+    Random,
+}
 
-                self.registers[x_register] >>= 1;
-                self.registers[0xF] = lsb;
+impl FromStr for MemoryFill {
+    type Err = String;
 
-                debug!("{raw_instruction:#X}: V{x_register} >>= 1: VF set to {lsb}");
+    /// Parses `zero`, `random`, or a hex byte to fill with, e.g. `0xAA`
+    fn from_str(fill: &str) -> Result<Self, Self::Err> {
+        match fill {
+            "zero" => Ok(MemoryFill::Zero),
+            "random" => Ok(MemoryFill::Random),
+            _ => {
+                let hex = fill.strip_prefix("0x").or_else(|| fill.strip_prefix("0X")).unwrap_or(fill);
+                u8::from_str_radix(hex, 16).map(MemoryFill::Pattern).map_err(|_| {
+                    format!("Unknown memory fill '{fill}', must be zero, random or a hex byte")
+                })
             }
+        }
+    }
+}
 
-            // 8XY7: Sets VX to VY minus VX. VF is set to 0 when there's an underflow, and 1 when
-            // there is not. (i.e. VF set to 1 if VY >= VX).
-            DecodedInstruction { first_nibble: 0x8, n_4_bit_constant: 0x7, .. } => {
-                let (result, underflow) = self.registers[y_register].overflowing_sub(self.registers[x_register]);
+#[derive(Debug, Clone, Copy)]
+pub struct DecodedInstruction {
+    pub first_nibble: u8,
+    pub x_register: u8,          // Second nibble
+    pub y_register: u8,          // Third nibble
+    pub n_4_bit_constant: u8,    // Fourth nibble
+    pub nn_8_bit_constant: u8,   // Second byte
+    pub nnn_12_bit_address: u16, // Second, third and fourth nibbles
+    pub raw_instruction: u16,
+}
 
-                self.registers[x_register] = result;
-                self.registers[0xF] = if underflow { 0 } else { 1 };
+impl Chip8Emulator {
+    /// Creates an emulator running `rom`. Panics if the ROM is too large or the config is invalid,
+    /// use [`Chip8Emulator::try_new`] when either comes from somewhere untrusted.
+    pub fn new(rom: Vec<u8>, config: Chip8Config) -> Self {
+        Self::try_new(rom, config).unwrap()
+    }
 
-                debug!("{raw_instruction:#X}: V{x_register} = V{y_register} - V{x_register} - Underflow: {underflow}");
-            }
+    /// Creates an emulator running `rom`, checking the ROM fits and the config is valid. See
+    /// [`Chip8EmulatorBuilder`] to change a few settings without writing out a whole config.
+    pub fn try_new(rom: Vec<u8>, config: Chip8Config) -> Result<Self, Chip8Error> {
+        Chip8EmulatorBuilder::new().rom(rom).config(config).build()
+    }
 
-            // 8XYE: Shifts VX to the left by 1, then sets VF to 1 if the most significant bit of VX
-            // prior to that shift was set, or to 0 if it was unset.
-            DecodedInstruction {first_nibble: 0x8, n_4_bit_constant: 0xE, .. } => {
-                let msb = self.registers[x_register] >> 7;
+    /// Restarts the ROM from scratch. RAM is reloaded from the original ROM so any self-modifying
+    /// code is undone.
+    pub fn reset(&mut self) {
+        self.fill_memory();
 
-                self.registers[x_register] <<= 1;
-                self.registers[0xF] = msb;
+        // Place fonts into RAM starting at FONT_START_ADDRESS
+        for (index, font_byte) in self.font.iter().enumerate() {
+            self.ram[index + FONT_START_ADDRESS as usize] = *font_byte;
+        }
 
-                debug!("{raw_instruction:#X}: V{x_register} <<= 1: VF set to {msb}");
-            }
+        // Place program into RAM
+        for (index, program_byte) in self.rom.iter().enumerate() {
+            self.ram[index + self.program_start as usize] = *program_byte;
+        }
 
-            // 9XY0: Skips the next instruction if VX does not equal VY.
-            // (Usually the next instruction is a jump to skip a code block).
-            DecodedInstruction {first_nibble: 0x9, n_4_bit_constant: 0x0, ..} => {
-                if self.registers[x_register] != self.registers[y_register] {
-                    self.program_counter += 2;
-                    debug!("{raw_instruction:#X}: Skipping because V{x_register} != V{y_register}");
-                } else {
-                    debug!("{raw_instruction:#X}: Not skipping because V{x_register} == V{y_register}");
-                }
-            }
+        self.registers = Default::default();
+        self.index_register = 0;
+        self.program_counter = self.program_start;
+        self.stack = Default::default();
+        self.stack_pointer = 0;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.previous_keyboard_state = [false; 16];
+        self.keyboard_state = [false; 16];
+        self.set_resolution(Resolution::Low);
+        self.plane_mask = 1;
+        self.seed_rng();
+        self.halted = false;
+        self.idle_frames = 0;
+        self.frame_cycle = 0;
+        self.pending_time = 0;
+        self.waiting_for_vblank = false;
+        self.watchpoint_hit = None;
+        self.break_conditions_met = self.break_conditions_hold();
+    }
 
-            // AXNN: Sets I to the address NNN.
-            DecodedInstruction { first_nibble: 0xA, .. } => {
-                self.index_register = nnn_12_bit_address;
-                debug!("{raw_instruction:#X}: Setting index register to {nnn_12_bit_address:#3X}");
+    fn fill_memory(&mut self) {
+        match self.memory_fill {
+            MemoryFill::Zero => self.ram.fill(0),
+            MemoryFill::Pattern(byte) => self.ram.fill(byte),
+            MemoryFill::Random => {
+                // A generator of its own, so the fill doesn't change which numbers CXNN gets
+                let mut rng = match self.seed {
+                    Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+                    None => ChaCha12Rng::from_os_rng(),
+                };
+                rng.fill(&mut self.ram[..]);
             }
+        }
+    }
 
-            // CXNN: Sets VX to the result of a bitwise and operation on a random number
-            // (Typically: 0 to 255) and NN.
-            DecodedInstruction { first_nibble: 0xC, .. } => {
-                let random: u8 = rand::rng().random();
-                let result = random & nn_8_bit_constant;
-                self.registers[x_register] = result;
+    fn seed_rng(&mut self) {
+        self.rng = match self.seed {
+            Some(seed) => ChaCha12Rng::seed_from_u64(seed),
+            None => ChaCha12Rng::from_os_rng(),
+        };
+    }
 
-                debug!("{raw_instruction:#X}: Random number: V{x_register} = {random} & {nn_8_bit_constant} = {result}");
-            }
+    /// Switches the display mode, clearing the screen
+    fn set_resolution(&mut self, resolution: Resolution) {
+        self.resolution = resolution;
+        self.display_buffer = vec![self.background_color; resolution.width() * resolution.height()];
+        self.plane_buffer = vec![0; resolution.width() * resolution.height()];
+        self.display_dirty = true;
+    }
 
-            // DXYN:
-            // Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N pixels.
-            // Each row of 8 pixels is read as bit-coded starting from memory location I;
-            // I value does not change after the execution of this instruction.
-            // As described above, VF is set to 1 if any screen pixels are flipped from set
-            // to unset when the sprite is drawn, and to 0 if that does not happen
-            DecodedInstruction { first_nibble: 0xD, .. } => {
-                let x = self.registers[x_register] as usize % WIDTH;
-                let y = self.registers[y_register] as usize % HEIGHT;
-                let height = n_4_bit_constant as usize;
+    /// The color shown for a pixel lit on the given planes
+    fn plane_color(&self, planes: u8) -> u32 {
+        match planes {
+            0 => self.background_color,
+            1 => self.foreground_color,
+            2 => self.plane_2_color,
+            _ => self.both_planes_color,
+        }
+    }
 
-                let mut collision_detected = false;
+    fn set_pixel_planes(&mut self, index: usize, planes: u8) {
+        self.plane_buffer[index] = planes;
+        self.display_buffer[index] = self.plane_color(planes);
+    }
 
-                for y_counter in 0..height {
-                    for x_counter in 0..8 {
-                        let is_pixel_on = (self.ram[self.index_register as usize + y_counter]
-                            & (0x80 >> x_counter))
-                            != 0;
+    /// Redraws the whole display buffer from the plane buffer
+    fn composite_display(&mut self) {
+        for index in 0..self.plane_buffer.len() {
+            self.display_buffer[index] = self.plane_color(self.plane_buffer[index]);
+        }
+        self.display_dirty = true;
+    }
 
-                        if x_counter + x >= WIDTH {
-                            continue;
-                        }
+    /// Replaces the ROM and restarts from scratch with it. Fails, leaving the current ROM running,
+    /// if the new one doesn't fit in memory.
+    pub fn load_rom(&mut self, rom: Vec<u8>) -> Result<(), Chip8Error> {
+        let max = self.program_max_size();
+        if rom.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max });
+        }
 
-                        if y_counter + y >= HEIGHT {
-                            continue;
-                        }
+        self.rom = rom;
+        self.reset();
+        Ok(())
+    }
 
-                        let dest_address = (y_counter + y) * WIDTH + (x_counter + x);
-                        let is_already_on = self.display_buffer[dest_address] != 0;
+    /// Ticks the timers and runs a frame's worth of instructions. The keyboard snapshot is held
+    /// for the whole frame, so every instruction in the frame sees the same key state.
+    ///
+    /// The frame stops early if a breakpoint is hit. Running another frame resumes from the
+    /// breakpoint.
+    pub fn run_60hz_frame(&mut self, new_keyboard_state: KeyboardState) -> FrameStatus {
+        self.run_frame(new_keyboard_state, self.instructions_per_frame as u32)
+    }
 
-                        if is_pixel_on {
-                            if is_already_on {
-                                self.display_buffer[dest_address] = 0x0;
-                                collision_detected = true;
-                            } else {
-                                self.display_buffer[dest_address] = 0xFFFFFFFF;
-                            }
-                        }
-                    }
-                }
+    /// Like [`Chip8Emulator::run_60hz_frame`], but runs `multiplier` times as many instructions.
+    /// The timers still tick once, so the program sees a single 60Hz frame go by.
+    pub fn run_turbo_frame(&mut self, new_keyboard_state: KeyboardState, multiplier: u32) -> FrameStatus {
+        self.run_frame(new_keyboard_state, (self.instructions_per_frame as u32).saturating_mul(multiplier))
+    }
 
-                self.registers[0xF] = if collision_detected { 1 } else { 0 };
+    /// Runs however many instructions and timer ticks fit in `dt` of emulated time, at
+    /// [`TIMER_FREQUENCY`] frames a second of the usual instructions per frame. Unlike
+    /// [`Chip8Emulator::run_60hz_frame`], this can be called at any rate: time that doesn't add up
+    /// to a whole instruction is carried over to the next call, and a frame can be split across
+    /// calls.
+    ///
+    /// Stops early for the same reasons as a frame does, dropping the rest of `dt`.
+    pub fn advance(&mut self, dt: Duration, new_keyboard_state: KeyboardState) -> FrameStatus {
+        let instructions_per_frame = self.instructions_per_frame as u32;
+        self.pending_time += dt.as_nanos() * (instructions_per_frame * TIMER_FREQUENCY) as u128;
+        let mut cycles = self.pending_time / NANOS_PER_SECOND;
+        self.pending_time %= NANOS_PER_SECOND;
 
-                debug!("{raw_instruction:#X}: Drawing sprite at address {:#3X} of height {height} to ({x}, {y}). Collision Detected: {collision_detected}",
-                    self.index_register);
+        self.previous_keyboard_state = self.keyboard_state;
+        self.keyboard_state = new_keyboard_state;
+
+        let mut status = FrameStatus::Completed;
+        while cycles > 0 {
+            if self.frame_cycle == 0 {
+                self.tick_timers();
             }
 
-            // EX9E Skips the next instruction if the key stored in VX(only consider the lowest nibble)
-            // is pressed (usually the next instruction is a jump to skip a code block).
-            DecodedInstruction { first_nibble: 0xE, nn_8_bit_constant: 0x9E, .. } => {
-                let key_to_check = self.registers[x_register] & 0x0F;
-                if self.keyboard_state[key_to_check as usize] {
-                    self.program_counter += 2;
-                    debug!("{raw_instruction:#X}: Skipping because Key {key_to_check:#X} is pressed");
-                } else {
-                    debug!("{raw_instruction:#X}: Not skipping because Key {key_to_check:#X} isn't pressed");
+            let frame_cycles = cycles.min((instructions_per_frame - self.frame_cycle) as u128) as u32;
+            cycles -= frame_cycles as u128;
+
+            if self.check_idle_loop() {
+                status = FrameStatus::IdleLoop { address: self.program_counter };
+            } else if !self.waiting_for_vblank {
+                let instructions_run = self.instructions_run;
+                let result = self.run_instructions(frame_cycles);
+                let ran = (self.instructions_run - instructions_run) as u32;
+                if let Err(status) = result {
+                    self.frame_cycle += ran;
+                    return status;
                 }
             }
 
-            // EXA1 Skips the next instruction if the key stored in VX(only consider the lowest nibble)
-            // is not pressed (usually the next instruction is a jump to skip a code block)
-            DecodedInstruction { first_nibble: 0xE, nn_8_bit_constant: 0xA1, .. } => {
-                let key_to_check = self.registers[x_register] & 0x0F;
-                if !self.keyboard_state[key_to_check as usize] {
-                    self.program_counter += 2;
-                    debug!("{raw_instruction:#X}: Skipping because Key {key_to_check:#X} is not pressed");
-                } else {
-                    debug!("{raw_instruction:#X}: Not skipping because Key {key_to_check:#X} is pressed");
+            self.frame_cycle += frame_cycles;
+            if self.frame_cycle == instructions_per_frame {
+                self.frame_cycle = 0;
+                self.waiting_for_vblank = false;
+                if let Some(idle_loop) = self.end_frame() {
+                    status = idle_loop;
                 }
             }
+        }
 
-            // FX07: Sets VX to the value of the delay timer
-            DecodedInstruction { first_nibble: 0xF, nn_8_bit_constant: 0x07, .. } => {
-                self.registers[x_register] = self.delay_timer;
-                debug!("{raw_instruction:#X}: Setting V{x_register} to delay timer");
-            }
+        status
+    }
 
-            // FX0A: A key press is awaited, and then stored in VX (blocking operation, all instruction
-            // halted until next key event, delay and sound timers should continue processing).
-            DecodedInstruction { first_nibble: 0xF, nn_8_bit_constant: 0x0A, .. } => {
-                let first_released =
-                    zip(self.previous_keyboard_state.iter(), self.keyboard_state.iter())
-                    .enumerate()
-                    .filter_map(|(idx, (prev, curr))| {
-                        // Grab keys that were previously pressed, and are now released
-                        (*prev && !*curr).then_some(idx)
-                    })
-                    .next();
+    fn run_frame(&mut self, new_keyboard_state: KeyboardState, instructions: u32) -> FrameStatus {
+        debug!("Running 60hz frame");
+        self.tick_timers();
 
-                if let Some(first_released) = first_released {
-                    self.registers[x_register] = first_released as u8;
-                    debug!("{raw_instruction:#X}: Key {first_released:#X} stored to V{x_register}");
-                } else {
-                    self.program_counter -= 2;
-                    debug!("{raw_instruction:#X}: No keys pressed, blocking");
-                }
-            }
+        self.previous_keyboard_state = self.keyboard_state;
+        self.keyboard_state = new_keyboard_state;
 
-            // FX15: Sets the delay timer to VX
-            DecodedInstruction { first_nibble: 0xF, nn_8_bit_constant: 0x15, .. } => {
-                self.delay_timer = self.registers[x_register];
-                debug!("{raw_instruction:#X}: Setting delay timer to V{x_register}");
-            }
+        if self.check_idle_loop() {
+            return FrameStatus::IdleLoop { address: self.program_counter };
+        }
 
-            // FX18: Sets the sound timer to VX
-            DecodedInstruction { first_nibble: 0xF, nn_8_bit_constant: 0x18, .. } => {
-                self.sound_timer = self.registers[x_register];
-                debug!("{raw_instruction:#X}: Setting sound timer to V{x_register}");
-            }
+        let result = self.run_instructions(instructions);
 
-            // FX1E: Adds VX to I. VF is not affected.
-            DecodedInstruction { first_nibble: 0xF, nn_8_bit_constant: 0x1E, .. } => {
-                self.index_register += self.registers[x_register] as u16;
-                debug!("{raw_instruction:#X}: Adding register {x_register} to index");
-            }
+        // The frame's over, so any display wait is too
+        self.waiting_for_vblank = false;
+        if let Err(status) = result {
+            return status;
+        }
 
-            // FX29: Sets I to the location of the sprite for the character in
-            // VX(only consider the lowest nibble). Characters 0-F (in hexadecimal) are represented by a 4x5 font.
-            DecodedInstruction { first_nibble: 0xF, nn_8_bit_constant: 0x29, .. } => {
-                let lower_nibble = self.registers[x_register] & 0x0F;
-                self.index_register = (0x50 + lower_nibble * 5) as u16;
-                debug!("{raw_instruction:#X}: Setting index register to sprite of character at V{x_register}");
-            }
+        self.end_frame().unwrap_or(FrameStatus::Completed)
+    }
 
-            // FX33: Stores the binary-coded decimal representation of VX, with the hundreds digit in memory
-            // at location in I, the tens digit at location I+1, and the ones digit at location I+2.
-            DecodedInstruction {first_nibble: 0xF, nn_8_bit_constant: 0x33, ..} => {
-                let bcd = u8_bcd(self.registers[x_register]);
-                for i in 0..3 {
-                    self.ram[self.index_register as usize + i] = bcd[i];
-                }
+    /// Decrements the delay and sound timers, unless they've already reached zero. Done at the
+    /// start of every frame, so only needed when running instructions with
+    /// [`Chip8Emulator::tick`].
+    pub fn tick_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+            debug!("Decrementing delay counter: {}", self.delay_timer);
+        }
 
-                debug!("{raw_instruction:#X}: Storing BCD of V{x_register} to index location");
-            }
+        if self.sound_timer > 0 {
+            self.sound_timer -= 1;
+            debug!("Decrementing sound timer: {}", self.sound_timer);
+        }
+    }
 
-            // FX55: Stores from V0 to VX (including VX) in memory, starting at address I.
-            // The offset from I is increased by 1 for each value written, but I itself is left unmodified.
-            DecodedInstruction { first_nibble: 0xF, nn_8_bit_constant: 0x55, .. } => {
-                for i in 0..=x_register {
-                    self.ram[self.index_register as usize + i] = self.registers[i];
-                }
+    /// Whether an idle loop is still going, in which case nothing but the timers can change, so
+    /// there's no point running it. Checked again each frame in case the PC or RAM was changed
+    /// from outside.
+    fn check_idle_loop(&mut self) -> bool {
+        if self.is_idle_looping() {
+            if self.is_jump_to_self() {
+                return true;
+            }
+            self.idle_frames = 0;
+        }
+        false
+    }
 
-                debug!("{raw_instruction:#X}: Filling location {:#X} with V0 - V{x_register}", self.index_register);
+    /// Runs up to `instructions` instructions, or fewer if the display wait quirk ends the frame,
+    /// which sets `waiting_for_vblank`. Stops with an error status if a breakpoint, watchpoint or
+    /// halt stops execution.
+    fn run_instructions(&mut self, instructions: u32) -> Result<(), FrameStatus> {
+        for _ in 0..instructions {
+            if let Some(status) = self.check_breakpoint() {
+                return Err(status);
             }
 
-            // FX65: Fills from V0 to VX (including VX) with values from memory, starting at address I.
-            // The offset from I is increased by 1 for each value read, but I itself is left unmodified
-            DecodedInstruction { first_nibble: 0xF, nn_8_bit_constant: 0x65, .. } => {
-                for i in 0..=x_register {
-                    self.registers[i] = self.ram[self.index_register as usize + i];
-                }
+            let instruction_address = self.program_counter;
+            let instruction = self.run_instruction();
 
-                debug!("{raw_instruction:#X}: Filling V0 - V{x_register} from location {:#X}", self.index_register);
+            if let Some(status) = self.check_stop_after(instruction_address, instruction) {
+                return Err(status);
             }
 
-            _ => {
-                error!(
-                    "Unimplemented or invalid opcode {:#4X}",
-                    decoded_instruction.raw_instruction
-                );
+            // With the display wait quirk, drawing waits for the vertical blank so nothing else
+            // can run for the rest of this frame
+            if self.quirks.display_wait && instruction.first_nibble == 0xD {
+                debug!("Display wait, deferring remaining instructions to the next frame");
+                self.waiting_for_vblank = true;
+                break;
             }
         }
-    }
 
-    fn fetch(&mut self) -> u16 {
-        u16::from_be_bytes([
-            self.ram[self.program_counter as usize],
-            self.ram[self.program_counter as usize + 1],
-        ])
+        Ok(())
     }
 
-    fn decode(instruction: u16) -> DecodedInstruction {
-        DecodedInstruction {
-            first_nibble: (instruction >> 12) as u8,
-            x_register: ((instruction >> 8) as u8) & 0xF,
-            y_register: ((instruction >> 4) as u8) & 0xF,
-            n_4_bit_constant: (instruction & 0xF) as u8,
-            nn_8_bit_constant: instruction as u8,
-            nnn_12_bit_address: instruction & 0x0FFF,
-            raw_instruction: instruction,
+    /// Counts frames spent jumping to itself, returning an idle loop status once there's been
+    /// enough of them
+    fn end_frame(&mut self) -> Option<FrameStatus> {
+        if self.idle_loop_frames.is_some() {
+            self.idle_frames = if self.is_jump_to_self() { self.idle_frames + 1 } else { 0 };
+            if self.is_idle_looping() {
+                info!("Program halted, jumping to itself at {:#05X}", self.program_counter);
+                return Some(FrameStatus::IdleLoop { address: self.program_counter });
+            }
         }
+
+        None
     }
-}
 
-fn u8_bcd(byte: u8) -> [u8; 3] {
-    [
-        (byte / 100) % 10,
-        (byte / 10) % 10,
-        byte % 10
-    ]
-}
+    fn is_idle_looping(&self) -> bool {
+        self.idle_loop_frames.is_some_and(|frames| self.idle_frames >= frames)
+    }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+    /// Whether the next instruction is a 1NNN jump to its own address
+    fn is_jump_to_self(&self) -> bool {
+        Opcode::parse(self.fetch()) == Opcode::Jump(self.program_counter)
+    }
 
-    fn assert_pixel(emulator: &Chip8Emulator, display_buffer_addr: usize, set: bool) {
+    /// Whether a breakpoint should stop execution before the next instruction runs
+    fn check_breakpoint(&mut self) -> Option<FrameStatus> {
+        if !self.stopped_at_breakpoint
+            && self.breakpoints.contains(&self.program_counter)
+            && self.break_conditions_hold()
+        {
+            info!("Hit breakpoint at {:#05X}", self.program_counter);
+            self.stopped_at_breakpoint = true;
+            return Some(FrameStatus::HitBreakpoint(self.program_counter));
+        }
+
+        None
+    }
+
+    /// Whether the instruction that just ran at `instruction_address` should stop execution
+    fn check_stop_after(
+        &mut self,
+        instruction_address: u16,
+        instruction: DecodedInstruction,
+    ) -> Option<FrameStatus> {
+        if let Some(address) = self.watchpoint_hit.take() {
+            info!("Watchpoint at {address:#05X} written by {instruction_address:#05X}");
+            return Some(FrameStatus::HitWatchpoint { address, program_counter: instruction_address });
+        }
+
+        // Conditions combined with address breakpoints are checked by check_breakpoint instead
+        if !self.break_conditions.is_empty() && self.breakpoints.is_empty() {
+            let was_met = self.break_conditions_met;
+            self.break_conditions_met = self.break_conditions_hold();
+            if self.break_conditions_met && !was_met {
+                info!("Break conditions met after {instruction_address:#05X}");
+                return Some(FrameStatus::HitBreakCondition { program_counter: instruction_address });
+            }
+        }
+
+        if self.halted {
+            self.halted = false;
+            return Some(FrameStatus::Halted {
+                address: self.program_counter,
+                opcode: instruction.raw_instruction,
+            });
+        }
+
+        None
+    }
+
+    /// Stops execution before the instruction at `address` runs
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    pub fn has_breakpoint(&self, address: u16) -> bool {
+        self.breakpoints.contains(&address)
+    }
+
+    /// Stops execution after any instruction that writes to `address`
+    pub fn add_watchpoint(&mut self, address: u16) {
+        self.add_watchpoint_range(address..=address);
+    }
+
+    /// Stops execution after any instruction that writes to an address in `addresses`
+    pub fn add_watchpoint_range(&mut self, addresses: RangeInclusive<u16>) {
+        self.watchpoints.push(addresses);
+    }
+
+    pub fn clear_watchpoints(&mut self) {
+        self.watchpoints.clear();
+    }
+
+    /// Adds a condition on the registers that must hold for execution to stop. All conditions
+    /// must hold at once. With address breakpoints set, those only stop execution when the
+    /// conditions hold too. Without any, execution stops after the instruction that first makes
+    /// the conditions true.
+    pub fn add_break_condition(&mut self, condition: BreakCondition) {
+        self.break_conditions.push(condition);
+        self.break_conditions_met = self.break_conditions_hold();
+    }
+
+    pub fn clear_break_conditions(&mut self) {
+        self.break_conditions.clear();
+        self.break_conditions_met = false;
+    }
+
+    fn break_conditions_hold(&self) -> bool {
+        self.break_conditions.iter().all(|condition| condition.is_met(self))
+    }
+
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    /// Reads VX. Only the low nibble of `x` is used, so any index is safe.
+    pub fn register(&self, x: u8) -> u8 {
+        self.registers[(x & 0xF) as usize]
+    }
+
+    /// Overwrites VX, for tools that modify the emulator from outside. Only the low nibble of `x`
+    /// is used, so any index is safe.
+    pub fn set_register(&mut self, x: u8, value: u8) {
+        self.registers[(x & 0xF) as usize] = value;
+    }
+
+    /// The return addresses of the subroutines currently being run, outermost first
+    pub fn call_stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer as usize]
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        self.resolution
+    }
+
+    /// Whether the pixel is lit on either plane
+    pub fn is_pixel_on(&self, x: usize, y: usize) -> bool {
+        self.plane_buffer[y * self.resolution.width() + x] != 0
+    }
+
+    /// The screen as ARGB pixels, row by row. The dimensions depend on the current
+    /// [`Resolution`]. With XO-CHIP's two planes there are up to four colors, one for each
+    /// combination of planes a pixel is lit on.
+    pub fn display_buffer(&self) -> &[u32] {
+        &self.display_buffer
+    }
+
+    /// Whether the display has changed since the last call
+    pub fn take_display_dirty(&mut self) -> bool {
+        std::mem::take(&mut self.display_dirty)
+    }
+
+    /// Forces the next [`Chip8Emulator::take_display_dirty`] to report a change, e.g. when the
+    /// frontend's own drawing needs clearing away
+    pub fn mark_display_dirty(&mut self) {
+        self.display_dirty = true;
+    }
+
+    /// Returns `len` bytes of RAM starting at `start`. The range is clamped to the end of RAM, so
+    /// the returned slice may be shorter than requested.
+    pub fn dump_memory(&self, start: u16, len: usize) -> &[u8] {
+        let start = (start as usize).min(self.ram.len());
+        let end = start.saturating_add(len).min(self.ram.len());
+
+        &self.ram[start..end]
+    }
+
+    /// Reads a byte of RAM, for tools that inspect the emulator from outside
+    pub fn read_byte(&self, address: u16) -> Result<u8, Chip8Error> {
+        self.ram.get(address as usize).copied().ok_or(Chip8Error::AddressOutOfBounds {
+            address,
+            memory_size: self.ram.len(),
+        })
+    }
+
+    /// Overwrites a byte of RAM, for tools that modify the emulator from outside, e.g. cheats.
+    /// Unlike writes made by the program, this doesn't trigger watchpoints.
+    pub fn write_byte(&mut self, address: u16, value: u8) -> Result<(), Chip8Error> {
+        let memory_size = self.ram.len();
+        let byte = self
+            .ram
+            .get_mut(address as usize)
+            .ok_or(Chip8Error::AddressOutOfBounds { address, memory_size })?;
+        *byte = value;
+        Ok(())
+    }
+
+    /// The opcode that will run next
+    pub fn next_instruction(&self) -> u16 {
+        self.fetch()
+    }
+
+    /// Runs exactly one instruction without ticking the timers, for single-step debugging.
+    /// Returns what ran, along with whether it hit a watchpoint, a break condition or halted, so
+    /// the stop is reported here rather than by the next frame.
+    pub fn step(&mut self) -> (DecodedInstruction, FrameStatus) {
+        let instruction_address = self.program_counter;
+        let instruction = self.run_instruction();
+        let status = self.check_stop_after(instruction_address, instruction);
+        (instruction, status.unwrap_or(FrameStatus::Completed))
+    }
+
+    /// Like [`Chip8Emulator::step`], except a 2NNN call runs the whole subroutine and stops on the
+    /// instruction after the call. Recursive calls are run through too. Breakpoints, watchpoints
+    /// and halts inside the subroutine still stop it early, as does running more than
+    /// [`STEP_OVER_MAX_INSTRUCTIONS`] without returning. Timers don't tick, same as `step`.
+    pub fn step_over(&mut self) -> FrameStatus {
+        let instruction_address = self.program_counter;
+        let call_depth = self.stack_pointer;
+        let is_call = matches!(Opcode::parse(self.fetch()), Opcode::Call(_));
+        let instruction = self.run_instruction();
+        if let Some(status) = self.check_stop_after(instruction_address, instruction) {
+            return status;
+        }
+
+        if !is_call {
+            return FrameStatus::Completed;
+        }
+
+        // Checking the depth too means recursive calls returning to the same address don't count
+        let return_address = self.wrap_address(instruction_address as usize + 2) as u16;
+
+        for _ in 0..STEP_OVER_MAX_INSTRUCTIONS {
+            if self.program_counter == return_address && self.stack_pointer == call_depth {
+                return FrameStatus::Completed;
+            }
+
+            if let Some(status) = self.check_breakpoint() {
+                return status;
+            }
+
+            let instruction_address = self.program_counter;
+            let instruction = self.run_instruction();
+            if let Some(status) = self.check_stop_after(instruction_address, instruction) {
+                return status;
+            }
+        }
+
+        warn!("Subroutine called from {instruction_address:#05X} didn't return, stopping step over");
+        FrameStatus::Completed
+    }
+
+    /// Formats the program counter, index register and general purpose registers for debugging
+    pub fn register_dump(&self) -> String {
+        let registers: Vec<String> = self
+            .registers
+            .iter()
+            .enumerate()
+            .map(|(i, value)| format!("V{i:X}={value:02X}"))
+            .collect();
+
+        format!(
+            "PC={:04X} I={:04X} {}",
+            self.program_counter,
+            self.index_register,
+            registers.join(" ")
+        )
+    }
+
+    /// Size of RAM, one of the [`MEMORY_SIZES`]
+    pub fn memory_size(&self) -> usize {
+        self.ram.len()
+    }
+
+    /// Where the ROM is loaded and execution starts
+    pub fn program_start(&self) -> u16 {
+        self.program_start
+    }
+
+    /// The largest ROM that fits in this emulator's memory from the program start address
+    pub fn program_max_size(&self) -> usize {
+        self.ram.len().saturating_sub(self.program_start as usize)
+    }
+
+    /// Total number of instructions run since the emulator was created
+    pub fn instructions_run(&self) -> u64 {
+        self.instructions_run
+    }
+
+    /// How many times each kind of instruction has run since the emulator was created, most
+    /// frequent first. Instructions are identified by their pattern, e.g. `8XY4`, with invalid
+    /// instructions counted under `????`.
+    pub fn instruction_stats(&self) -> Vec<(&'static str, u64)> {
+        let mut stats: Vec<(&'static str, u64)> =
+            zip(PATTERNS, self.instruction_counts).filter(|(_, count)| *count > 0).collect();
+
+        // Ties are broken by pattern so the order is stable
+        stats.sort_by(|(a_pattern, a_count), (b_pattern, b_count)| {
+            b_count.cmp(a_count).then(a_pattern.cmp(b_pattern))
+        });
+        stats
+    }
+
+    /// Whether the sound timer is active, i.e. whether a tone should currently be playing
+    pub fn is_beeping(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Calls `hook` before every instruction runs, with the program counter still pointing at it.
+    /// Replaces any previous pre-hook.
+    pub fn set_pre_hook(&mut self, hook: impl FnMut(&Chip8Emulator, &DecodedInstruction) + Send + 'static) {
+        self.pre_hook = Some(Box::new(hook));
+    }
+
+    /// Calls `hook` after every instruction has run, including ones that halt. Replaces any
+    /// previous post-hook.
+    pub fn set_post_hook(&mut self, hook: impl FnMut(&Chip8Emulator, &DecodedInstruction) + Send + 'static) {
+        self.post_hook = Some(Box::new(hook));
+    }
+
+    /// Removes both the pre- and post-hook
+    pub fn clear_hooks(&mut self) {
+        self.pre_hook = None;
+        self.post_hook = None;
+    }
+
+    /// Runs exactly one instruction, between the hooks, and returns what ran. This is for callers
+    /// that want to control exactly how many instructions run.
+    ///
+    /// Nothing else happens: the timers don't tick, and breakpoints, watchpoints, halts and the
+    /// display wait quirk are ignored. A watchpoint hit or halt isn't carried over into the next
+    /// frame either. Callers running a program this way are responsible for calling
+    /// [`Chip8Emulator::tick_timers`] 60 times a second of emulated time.
+    pub fn tick(&mut self) -> DecodedInstruction {
+        let instruction = self.run_instruction();
+        self.watchpoint_hit = None;
+        self.halted = false;
+        instruction
+    }
+
+    /// Runs one instruction between the hooks, leaving any watchpoint hit or halt for
+    /// [`Chip8Emulator::check_stop_after`] to report
+    fn run_instruction(&mut self) -> DecodedInstruction {
+        // The hooks are taken out while they run so they can see the whole emulator
+        if let Some(mut hook) = self.pre_hook.take() {
+            hook(self, &Chip8Emulator::decode(self.fetch()));
+            self.pre_hook = Some(hook);
+        }
+
+        let instruction = self.execute_instruction();
+
+        if let Some(mut hook) = self.post_hook.take() {
+            hook(self, &instruction);
+            self.post_hook = Some(hook);
+        }
+
+        instruction
+    }
+
+    fn execute_instruction(&mut self) -> DecodedInstruction {
+        self.stopped_at_breakpoint = false;
+
+        let instruction_address = self.program_counter;
+        let raw_instruction = self.fetch();
+
+        if instruction_address as usize >= self.ram.len() - 1 {
+            let err = Chip8Error::ProgramCounterOutOfBounds { address: instruction_address };
+
+            match self.unknown_opcode_policy {
+                UnknownOpcodePolicy::Ignore => warn!("{err}, wrapping around to the start of RAM"),
+                UnknownOpcodePolicy::Halt => {
+                    error!("{err}, halting");
+                    self.halted = true;
+                    return Chip8Emulator::decode(raw_instruction);
+                }
+                UnknownOpcodePolicy::Panic => panic!("{err}"),
+            }
+        }
+
+        self.program_counter = self.wrap_address(instruction_address as usize + 2) as u16;
+
+        let decoded_instruction = Chip8Emulator::decode(raw_instruction);
+        let opcode = Opcode::parse(raw_instruction);
+
+        self.instruction_counts[opcode.kind()] += 1;
+        self.instructions_run += 1;
+
+        match opcode {
+            //00E0: Clears the screen, or with XO-CHIP just the selected planes
+            Opcode::ClearScreen => {
+                let plane_mask = self.plane_mask;
+                self.plane_buffer.iter_mut().for_each(|planes| *planes &= !plane_mask);
+                self.composite_display();
+                debug!("0x00E0: Clearing display buffer");
+            }
+
+            // 00EE Returns from a subroutine.
+            Opcode::Return => {
+                if self.stack_pointer == 0 {
+                    let err = Chip8Error::StackUnderflow { address: instruction_address };
+                    error!("{raw_instruction:#X}: {err}, ignoring return with empty stack");
+                    return decoded_instruction;
+                }
+
+                self.stack_pointer -= 1;
+                self.program_counter = self.stack[self.stack_pointer as usize];
+
+                debug!("{raw_instruction:#X}: Returning from subroutine");
+            }
+
+            // 00CN: Scrolls the display down by N lines (SUPER-CHIP)
+            Opcode::ScrollDown(n) => {
+                let lines = self.scroll_distance(n as usize);
+                self.scroll_display(0, lines as isize);
+                debug!("{raw_instruction:#X}: Scrolling display down {lines} lines");
+            }
+
+            // 00FB: Scrolls the display right by 4 pixels (SUPER-CHIP)
+            Opcode::ScrollRight => {
+                let pixels = self.scroll_distance(4);
+                self.scroll_display(pixels as isize, 0);
+                debug!("{raw_instruction:#X}: Scrolling display right {pixels} pixels");
+            }
+
+            // 00FC: Scrolls the display left by 4 pixels (SUPER-CHIP)
+            Opcode::ScrollLeft => {
+                let pixels = self.scroll_distance(4);
+                self.scroll_display(-(pixels as isize), 0);
+                debug!("{raw_instruction:#X}: Scrolling display left {pixels} pixels");
+            }
+
+            // 00FE: Switches to the 64x32 low resolution display (SUPER-CHIP)
+            Opcode::LowResolution => {
+                self.set_resolution(Resolution::Low);
+                debug!("{raw_instruction:#X}: Switching to low resolution");
+            }
+
+            // 00FF: Switches to the 128x64 high resolution display (SUPER-CHIP)
+            Opcode::HighResolution => {
+                self.set_resolution(Resolution::High);
+                debug!("{raw_instruction:#X}: Switching to high resolution");
+            }
+
+            // 1NNN: Jump to address NNN
+            Opcode::Jump(nnn) => {
+                self.program_counter = nnn;
+                debug!("{raw_instruction:#X}: Jumping to address {nnn:#3X}");
+            }
+
+            // 2NNN: Calls subroutine at NNN.
+            Opcode::Call(nnn) => {
+                if self.stack_pointer as usize >= self.stack.len() {
+                    let err = Chip8Error::StackOverflow { address: instruction_address };
+                    error!("{raw_instruction:#X}: {err}, ignoring call to {nnn:#3X}");
+                    return decoded_instruction;
+                }
+
+                self.stack[self.stack_pointer as usize] = self.program_counter;
+                self.stack_pointer += 1;
+
+                self.program_counter = nnn;
+
+                debug!(
+                    "{raw_instruction:#X}: Calling subroutine at address {nnn:#3X}"
+                );
+            }
+
+            // 3XNN: Skips the next instruction if VX equals NN
+            // (usually the next instruction is a jump to skip a code block).
+            Opcode::SkipIfEqual { x, nn } => {
+                if self.registers[x as usize] == nn {
+                    self.skip_next_instruction();
+                    debug!("{raw_instruction:#X}: Skipping because V{x} == {nn}");
+                } else {
+                    debug!("{raw_instruction:#X}: Not skipping because V{x} != {nn}");
+                }
+            }
+
+            // 4XNN: Skips the next instruction if VX does not equal NN
+            // (usually the next instruction is a jump to skip a code block).
+            Opcode::SkipIfNotEqual { x, nn } => {
+                if self.registers[x as usize] != nn {
+                    self.skip_next_instruction();
+                    debug!("{raw_instruction:#X}: Skipping because V{x} != {nn}");
+                } else {
+                    debug!("{raw_instruction:#X}: Not skipping because V{x} == {nn}");
+                }
+            }
+
+            // 5XY0: Skips the next instruction if VX equals VY
+            // (usually the next instruction is a jump to skip a code block).
+            Opcode::SkipIfRegistersEqual { x, y } => {
+                if self.registers[x as usize] == self.registers[y as usize] {
+                    self.skip_next_instruction();
+                    debug!("{raw_instruction:#X}: Skipping because V{x} == V{y}");
+                } else {
+                    debug!("{raw_instruction:#X}: Not skipping because V{x} != V{y}");
+                }
+            }
+
+            // 6XNN: Sets VX to NN
+            Opcode::SetReg { x, nn } => {
+                self.registers[x as usize] = nn;
+                debug!("{raw_instruction:#X}: Setting register {x} to {nn:#2X}");
+            }
+
+            // 7XNN: Adds NN to VX (carry flag is not changed)
+            Opcode::AddToReg { x, nn } => {
+                self.registers[x as usize] = self.registers[x as usize].wrapping_add(nn);
+                debug!("{raw_instruction:#X}: Adding {nn} to register {x}");
+            }
+
+            // 8XY0: Sets VX to the value of VY
+            Opcode::CopyReg { x, y } => {
+                self.registers[x as usize] = self.registers[y as usize];
+
+                debug!("{raw_instruction:#X}: Setting V{x} to V{y}");
+            }
+
+            // 8XY1: Sets VX to VX or VY. (bitwise OR operation).
+            Opcode::Or { x, y } => {
+                self.registers[x as usize] |= self.registers[y as usize];
+
+                if self.quirks.vf_reset_on_logic {
+                    self.registers[0xF] = 0;
+                }
+
+                debug!("{raw_instruction:#X}: Setting V{x} |= V{y}");
+            }
+
+            // 8XY2: Sets VX to VX and VY. (bitwise AND operation)
+            Opcode::And { x, y } => {
+                self.registers[x as usize] &= self.registers[y as usize];
+
+                if self.quirks.vf_reset_on_logic {
+                    self.registers[0xF] = 0;
+                }
+
+                debug!("{raw_instruction:#X}: Setting V{x} &= V{y}");
+            }
+
+            // 8XY3: Sets VX to VX xor VY.
+            Opcode::Xor { x, y } => {
+                self.registers[x as usize] ^= self.registers[y as usize];
+
+                if self.quirks.vf_reset_on_logic {
+                    self.registers[0xF] = 0;
+                }
+
+                debug!("{raw_instruction:#X}: Setting V{x} ^= V{y}");
+            }
+
+            // 8XY4: Adds VY to VX. VF is set to 1 when there's an overflow, and to 0 when there is not.
+            Opcode::Add { x, y } => {
+                let x_value = self.registers[x as usize];
+                let y_value = self.registers[y as usize];
+                let (result_value, overflow) = x_value.overflowing_add(y_value);
+
+                self.registers[x as usize] = result_value;
+                self.registers[0xF] = if overflow { 1 } else { 0 };
+
+                debug!("{raw_instruction:#X}: V{x} += V{y} - Overflow: {overflow}");
+            }
+
+            // 8XY5: VY is subtracted from VX. VF is set to 0 when there's an underflow, and 1 when
+            // there is not. (i.e. VF set to 1 if VX >= VY and 0 if not)
+            Opcode::Subtract { x, y } => {
+                let (result, underflow) = self.registers[x as usize].overflowing_sub(self.registers[y as usize]);
+
+                self.registers[x as usize] = result;
+                self.registers[0xF] = if underflow { 0 } else { 1 };
+
+                debug!("{raw_instruction:#X}: V{x} -= V{y} - Underflow: {underflow}");
+            }
+
+            // 8XY6: Shifts VX to the right by 1, then stores the least significant bit of VX
+            // prior to the shift into VF. With the shift quirk, VY is shifted into VX instead.
+            Opcode::ShiftRight { x, y } => {
+                let source_register = if self.quirks.shift_uses_vy { y } else { x };
+                let value = self.registers[source_register as usize];
+                let lsb = value & 0b1;
+
+                self.registers[x as usize] = value >> 1;
+                self.registers[0xF] = lsb;
+
+                debug!("{raw_instruction:#X}: V{x} = V{source_register} >> 1: VF set to {lsb}");
+            }
+
+            // 8XY7: Sets VX to VY minus VX. VF is set to 0 when there's an underflow, and 1 when
+            // there is not. (i.e. VF set to 1 if VY >= VX).
+            Opcode::SubtractReversed { x, y } => {
+                let (result, underflow) = self.registers[y as usize].overflowing_sub(self.registers[x as usize]);
+
+                self.registers[x as usize] = result;
+                self.registers[0xF] = if underflow { 0 } else { 1 };
+
+                debug!("{raw_instruction:#X}: V{x} = V{y} - V{x} - Underflow: {underflow}");
+            }
+
+            // 8XYE: Shifts VX to the left by 1, then sets VF to 1 if the most significant bit of VX
+            // prior to that shift was set, or to 0 if it was unset. With the shift quirk, VY is
+            // shifted into VX instead.
+            Opcode::ShiftLeft { x, y } => {
+                let source_register = if self.quirks.shift_uses_vy { y } else { x };
+                let value = self.registers[source_register as usize];
+                let msb = value >> 7;
+
+                self.registers[x as usize] = value << 1;
+                self.registers[0xF] = msb;
+
+                debug!("{raw_instruction:#X}: V{x} = V{source_register} << 1: VF set to {msb}");
+            }
+
+            // 9XY0: Skips the next instruction if VX does not equal VY.
+            // (Usually the next instruction is a jump to skip a code block).
+            Opcode::SkipIfRegistersNotEqual { x, y } => {
+                if self.registers[x as usize] != self.registers[y as usize] {
+                    self.skip_next_instruction();
+                    debug!("{raw_instruction:#X}: Skipping because V{x} != V{y}");
+                } else {
+                    debug!("{raw_instruction:#X}: Not skipping because V{x} == V{y}");
+                }
+            }
+
+            // AXNN: Sets I to the address NNN.
+            Opcode::SetIndex(nnn) => {
+                self.index_register = nnn;
+                debug!("{raw_instruction:#X}: Setting index register to {nnn:#3X}");
+            }
+
+            // BNNN: Jumps to the address NNN plus V0. With the jump quirk, this is BXNN instead and
+            // jumps to XNN plus VX.
+            Opcode::JumpWithOffset { x, nnn } => {
+                let offset_register = if self.quirks.jump_uses_vx { x } else { 0 };
+                self.program_counter = nnn + self.registers[offset_register as usize] as u16;
+
+                debug!("{raw_instruction:#X}: Jumping to {nnn:#3X} + V{offset_register:X}");
+            }
+
+            // CXNN: Sets VX to the result of a bitwise and operation on a random number
+            // (Typically: 0 to 255) and NN.
+            Opcode::Random { x, nn } => {
+                let random: u8 = self.rng.random();
+                let result = random & nn;
+                self.registers[x as usize] = result;
+
+                debug!("{raw_instruction:#X}: Random number: V{x} = {random} & {nn} = {result}");
+            }
+
+            // DXYN:
+            // Draws a sprite at coordinate (VX, VY) that has a width of 8 pixels and a height of N pixels.
+            // Each row of 8 pixels is read as bit-coded starting from memory location I;
+            // I value does not change after the execution of this instruction.
+            // As described above, VF is set to 1 if any screen pixels are flipped from set
+            // to unset when the sprite is drawn, and to 0 if that does not happen
+            // DXY0 (SUPER-CHIP) draws a 16x16 sprite instead, with each row stored as 2 bytes.
+            // Without the large sprites quirk it's an 8 pixel wide sprite with no rows, so nothing
+            // is drawn, as on the COSMAC VIP.
+            // XO-CHIP draws to each selected plane in turn, with the sprite for plane 2 straight
+            // after the one for plane 1 when both are selected.
+            Opcode::Draw { x, y, n } => {
+                let width = self.resolution.width();
+                let height = self.resolution.height();
+                let x = self.registers[x as usize] as usize % width;
+                let y = self.registers[y as usize] as usize % height;
+                let (sprite_width, rows) = match n {
+                    0 if self.quirks.large_sprites => (16, 16),
+                    n => (8, n as usize),
+                };
+                let bytes_per_row = sprite_width / 8;
+                let sprite_size = rows * bytes_per_row;
+
+                // VF is only ever set on collision or clipping below, so clear out any previous
+                // result first
+                self.registers[0xF] = 0;
+                self.display_dirty = true;
+                let mut collision_detected = false;
+                let mut clipped = false;
+
+                let plane_mask = self.plane_mask;
+                let selected_planes = [1, 2].into_iter().filter(|plane| plane_mask & plane != 0);
+                for (sprite_index, plane) in selected_planes.enumerate() {
+                    let sprite_address = self.index_register as usize + sprite_index * sprite_size;
+
+                    for y_counter in 0..rows {
+                        // Rows that fall off the bottom of the screen are clipped, or wrapped to
+                        // the top with the wrap quirk
+                        if y_counter + y >= height && !self.quirks.wrap_sprites {
+                            clipped = true;
+                            break;
+                        }
+                        let dest_y = (y_counter + y) % height;
+
+                        // Rows are left aligned in a u16 so 8 and 16 pixel wide sprites are read
+                        // the same way
+                        let row_address = sprite_address + y_counter * bytes_per_row;
+                        let high_byte = self.ram[self.wrap_address(row_address)];
+                        let low_byte =
+                            if bytes_per_row == 2 { self.ram[self.wrap_address(row_address + 1)] } else { 0 };
+                        let sprite_row = u16::from_be_bytes([high_byte, low_byte]);
+
+                        for x_counter in 0..sprite_width {
+                            // Columns that fall off the right of the screen are clipped, or
+                            // wrapped to the left with the wrap quirk
+                            if x_counter + x >= width && !self.quirks.wrap_sprites {
+                                break;
+                            }
+                            let dest_x = (x_counter + x) % width;
+
+                            let is_pixel_on = (sprite_row & (0x8000 >> x_counter)) != 0;
+                            let dest_address = dest_y * width + dest_x;
+                            let planes = self.plane_buffer[dest_address];
+
+                            if is_pixel_on {
+                                if planes & plane != 0 {
+                                    self.registers[0xF] = 1;
+                                    collision_detected = true;
+                                }
+                                self.set_pixel_planes(dest_address, planes ^ plane);
+                            }
+                        }
+                    }
+                }
+
+                if clipped && self.quirks.clipping_sets_vf {
+                    self.registers[0xF] = 1;
+                }
+
+                debug!("{raw_instruction:#X}: Drawing sprite at address {:#3X} of size {sprite_width}x{rows} to ({x}, {y}). Collision Detected: {collision_detected}",
+                    self.index_register);
+            }
+
+            // EX9E Skips the next instruction if the key stored in VX(only consider the lowest nibble)
+            // is pressed (usually the next instruction is a jump to skip a code block).
+            Opcode::SkipIfKeyPressed { x } => {
+                let key_to_check = self.registers[x as usize] & 0x0F;
+                if self.keyboard_state[key_to_check as usize] {
+                    self.skip_next_instruction();
+                    debug!("{raw_instruction:#X}: Skipping because Key {key_to_check:#X} is pressed");
+                } else {
+                    debug!("{raw_instruction:#X}: Not skipping because Key {key_to_check:#X} isn't pressed");
+                }
+            }
+
+            // EXA1 Skips the next instruction if the key stored in VX(only consider the lowest nibble)
+            // is not pressed (usually the next instruction is a jump to skip a code block)
+            Opcode::SkipIfKeyNotPressed { x } => {
+                let key_to_check = self.registers[x as usize] & 0x0F;
+                if !self.keyboard_state[key_to_check as usize] {
+                    self.skip_next_instruction();
+                    debug!("{raw_instruction:#X}: Skipping because Key {key_to_check:#X} is not pressed");
+                } else {
+                    debug!("{raw_instruction:#X}: Not skipping because Key {key_to_check:#X} is pressed");
+                }
+            }
+
+            // FX07: Sets VX to the value of the delay timer
+            Opcode::GetDelayTimer { x } => {
+                self.registers[x as usize] = self.delay_timer;
+                debug!("{raw_instruction:#X}: Setting V{x} to delay timer");
+            }
+
+            // FX0A: A key press is awaited, and then stored in VX (blocking operation, all instruction
+            // halted until next key event, delay and sound timers should continue processing).
+            Opcode::WaitForKey { x } => {
+                let first_key =
+                    zip(self.previous_keyboard_state.iter(), self.keyboard_state.iter())
+                    .enumerate()
+                    .filter_map(|(idx, (prev, curr))| {
+                        if self.quirks.wait_for_key_release {
+                            // Grab keys that were previously pressed, and are now released
+                            (*prev && !*curr).then_some(idx)
+                        } else {
+                            curr.then_some(idx)
+                        }
+                    })
+                    .next();
+
+                if let Some(first_key) = first_key {
+                    self.registers[x as usize] = first_key as u8;
+                    debug!("{raw_instruction:#X}: Key {first_key:#X} stored to V{x}");
+                } else {
+                    // Back to this instruction, rather than PC - 2, which underflows when the PC
+                    // wrapped around from the end of RAM
+                    self.program_counter = instruction_address;
+                    debug!("{raw_instruction:#X}: No keys pressed, blocking");
+                }
+            }
+
+            // FX15: Sets the delay timer to VX
+            Opcode::SetDelayTimer { x } => {
+                self.delay_timer = self.registers[x as usize];
+                debug!("{raw_instruction:#X}: Setting delay timer to V{x}");
+            }
+
+            // FX18: Sets the sound timer to VX
+            Opcode::SetSoundTimer { x } => {
+                self.sound_timer = self.registers[x as usize];
+                debug!("{raw_instruction:#X}: Setting sound timer to V{x}");
+            }
+
+            // FX1E: Adds VX to I. VF is not affected, unless the index overflow quirk is enabled in
+            // which case VF is set to 1 if I goes past 0x0FFF.
+            Opcode::AddToIndex { x } => {
+                self.index_register = self.index_register.wrapping_add(self.registers[x as usize] as u16);
+
+                if self.quirks.index_overflow_sets_vf {
+                    self.registers[0xF] = if self.index_register > 0x0FFF { 1 } else { 0 };
+                }
+
+                debug!("{raw_instruction:#X}: Adding register {x} to index");
+            }
+
+            // FX29: Sets I to the location of the sprite for the character in
+            // VX(only consider the lowest nibble). Characters 0-F (in hexadecimal) are represented by a 4x5 font.
+            Opcode::SetIndexToFont { x } => {
+                let lower_nibble = self.registers[x as usize] & 0x0F;
+                self.index_register = FONT_START_ADDRESS + lower_nibble as u16 * FONT_CHARACTER_SIZE;
+                debug!("{raw_instruction:#X}: Setting index register to sprite of character at V{x}");
+            }
+
+            // FX30 (SUPER-CHIP): Sets I to the location of the big 8x10 sprite for the character
+            // in VX, again only considering the lowest nibble. The built in font has no big
+            // characters, so this needs a 240 byte custom font.
+            Opcode::SetIndexToBigFont { x } => {
+                let lower_nibble = self.registers[x as usize] & 0x0F;
+                self.index_register = BIG_FONT_START_ADDRESS + lower_nibble as u16 * BIG_FONT_CHARACTER_SIZE;
+                debug!("{raw_instruction:#X}: Setting index register to big sprite of character at V{x}");
+            }
+
+            // FX33: Stores the binary-coded decimal representation of VX, with the hundreds digit in memory
+            // at location in I, the tens digit at location I+1, and the ones digit at location I+2.
+            Opcode::StoreBcd { x } => {
+                let bcd = u8_bcd(self.registers[x as usize]);
+                for (i, digit) in bcd.iter().enumerate() {
+                    self.write_ram(self.index_register as usize + i, *digit);
+                }
+
+                debug!("{raw_instruction:#X}: Storing BCD of V{x} to index location");
+            }
+
+            // FX55: Stores from V0 to VX (including VX) in memory, starting at address I.
+            // The offset from I is increased by 1 for each value written, but I itself is left unmodified
+            // unless the load/store quirk is enabled.
+            Opcode::StoreRegisters { x } => {
+                for i in 0..=x as usize {
+                    self.write_ram(self.index_register as usize + i, self.registers[i]);
+                }
+
+                if self.quirks.load_store_increments_i {
+                    self.index_register = self.index_register.wrapping_add(x as u16 + 1);
+                }
+
+                debug!("{raw_instruction:#X}: Filling location {:#X} with V0 - V{x}", self.index_register);
+            }
+
+            // FX65: Fills from V0 to VX (including VX) with values from memory, starting at address I.
+            // The offset from I is increased by 1 for each value read, but I itself is left unmodified
+            // unless the load/store quirk is enabled.
+            Opcode::LoadRegisters { x } => {
+                for i in 0..=x as usize {
+                    self.registers[i] = self.ram[self.wrap_address(self.index_register as usize + i)];
+                }
+
+                if self.quirks.load_store_increments_i {
+                    self.index_register = self.index_register.wrapping_add(x as u16 + 1);
+                }
+
+                debug!("{raw_instruction:#X}: Filling V0 - V{x} from location {:#X}", self.index_register);
+            }
+
+            // FX75: Stores V0 to VX (including VX) in the HP48 user flags, X < 8 (SUPER-CHIP)
+            Opcode::SaveRplFlags { x } => {
+                let last_register = self.last_rpl_register(x as usize);
+                self.rpl_flags[..=last_register].copy_from_slice(&self.registers[..=last_register]);
+
+                debug!("{raw_instruction:#X}: Saving V0 - V{last_register} to the RPL flags");
+            }
+
+            // FX85: Fills V0 to VX (including VX) from the HP48 user flags, X < 8 (SUPER-CHIP)
+            Opcode::LoadRplFlags { x } => {
+                let last_register = self.last_rpl_register(x as usize);
+                self.registers[..=last_register].copy_from_slice(&self.rpl_flags[..=last_register]);
+
+                debug!("{raw_instruction:#X}: Loading V0 - V{last_register} from the RPL flags");
+            }
+
+            // FN01: Selects which planes the drawing instructions act on, as a bitmask with plane 1
+            // in bit 0 and plane 2 in bit 1 (XO-CHIP)
+            Opcode::SelectPlanes(planes) => {
+                self.plane_mask = planes & 0b11;
+                debug!("{raw_instruction:#X}: Selecting planes {:#04b}", self.plane_mask);
+            }
+
+            // F000 NNNN: Sets I to the 16-bit address NNNN in the two bytes following the
+            // instruction, which are then skipped over (XO-CHIP)
+            Opcode::SetIndexLong => {
+                self.index_register = self.fetch();
+                self.program_counter = self.wrap_address(self.program_counter as usize + 2) as u16;
+
+                debug!("{raw_instruction:#X}: Setting index register to {:#06X}", self.index_register);
+            }
+
+            Opcode::MachineCall(_) | Opcode::Unknown(_) => {
+                let err = Chip8Error::InvalidOpcode { address: instruction_address, opcode: raw_instruction };
+
+                match self.unknown_opcode_policy {
+                    UnknownOpcodePolicy::Ignore => error!("{err}"),
+                    UnknownOpcodePolicy::Halt => {
+                        error!("{err}, halting");
+                        self.program_counter = instruction_address;
+                        self.halted = true;
+                    }
+                    UnknownOpcodePolicy::Panic => panic!("{err}"),
+                }
+            }
+        }
+
+        decoded_instruction
+    }
+
+    /// Limits the X of FX75 / FX85 to the registers that have a matching RPL flag
+    fn last_rpl_register(&self, x_register: usize) -> usize {
+        if x_register >= RPL_FLAG_COUNT {
+            warn!("Only V0 - V{:X} can be saved to the RPL flags, ignoring the rest", RPL_FLAG_COUNT - 1);
+        }
+
+        x_register.min(RPL_FLAG_COUNT - 1)
+    }
+
+    /// How far a scroll instruction moves the display in the current resolution, in display pixels
+    fn scroll_distance(&self, distance: usize) -> usize {
+        if self.resolution == Resolution::Low && self.quirks.half_scroll_in_lores {
+            distance / 2
+        } else {
+            distance
+        }
+    }
+
+    /// Shifts the selected planes by the given offsets. Pixels shifted off the edge are lost, and
+    /// the vacated area is cleared.
+    fn scroll_display(&mut self, right: isize, down: isize) {
+        let width = self.resolution.width() as isize;
+        let height = self.resolution.height() as isize;
+        let plane_mask = self.plane_mask;
+
+        // Planes that aren't selected stay where they are
+        let mut scrolled: Vec<u8> = self.plane_buffer.iter().map(|planes| planes & !plane_mask).collect();
+        for y in 0..height {
+            for x in 0..width {
+                let (source_x, source_y) = (x - right, y - down);
+                if (0..width).contains(&source_x) && (0..height).contains(&source_y) {
+                    scrolled[(y * width + x) as usize] |=
+                        self.plane_buffer[(source_y * width + source_x) as usize] & plane_mask;
+                }
+            }
+        }
+
+        self.plane_buffer = scrolled;
+        self.composite_display();
+    }
+
+    /// Reads the instruction at the program counter. Addresses wrap around the end of RAM, so a
+    /// program counter that's run off the end can't read out of bounds.
+    fn fetch(&self) -> u16 {
+        let address = self.program_counter as usize;
+
+        u16::from_be_bytes([self.ram[self.wrap_address(address)], self.ram[self.wrap_address(address + 1)]])
+    }
+
+    /// Wraps an address around the end of RAM, so reads and writes past the end can't go out of
+    /// bounds
+    fn wrap_address(&self, address: usize) -> usize {
+        address % self.ram.len()
+    }
+
+    /// Writes a byte to RAM on behalf of the running program, wrapping the address. Every program
+    /// store goes through here so watchpoints see it.
+    fn write_ram(&mut self, address: usize, value: u8) {
+        let address = self.wrap_address(address);
+        self.ram[address] = value;
+
+        let address = address as u16;
+        if self.watchpoints.iter().any(|watched| watched.contains(&address)) {
+            self.watchpoint_hit.get_or_insert(address);
+        }
+    }
+
+    /// Skips over the next instruction, which is 4 bytes long if it's F000 NNNN
+    fn skip_next_instruction(&mut self) {
+        let length = if self.fetch() == 0xF000 { 4 } else { 2 };
+        self.program_counter = self.wrap_address(self.program_counter as usize + length) as u16;
+    }
+
+    pub fn decode(instruction: u16) -> DecodedInstruction {
+        DecodedInstruction {
+            first_nibble: (instruction >> 12) as u8,
+            x_register: ((instruction >> 8) as u8) & 0xF,
+            y_register: ((instruction >> 4) as u8) & 0xF,
+            n_4_bit_constant: (instruction & 0xF) as u8,
+            nn_8_bit_constant: instruction as u8,
+            nnn_12_bit_address: instruction & 0x0FFF,
+            raw_instruction: instruction,
+        }
+    }
+}
+
+fn u8_bcd(byte: u8) -> [u8; 3] {
+    [
+        (byte / 100) % 10,
+        (byte / 10) % 10,
+        byte % 10
+    ]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+    use std::sync::{Arc, Mutex};
+
+    fn assert_pixel(emulator: &Chip8Emulator, display_buffer_addr: usize, set: bool) {
         if set {
-            assert_ne!(emulator.display_buffer[display_buffer_addr], 0);
+            assert_eq!(emulator.display_buffer[display_buffer_addr], emulator.foreground_color);
         } else {
-            assert_eq!(emulator.display_buffer[display_buffer_addr], 0);
+            assert_eq!(emulator.display_buffer[display_buffer_addr], emulator.background_color);
+        }
+    }
+
+    #[test]
+    fn test_initial_ram() {
+        let mut rom = vec![0; PROGRAM_MAX_SIZE];
+
+        // Set some marker values at the beginning and end
+        rom[0] = 42;
+        rom[PROGRAM_MAX_SIZE - 1] = 69;
+
+        let emulator = Chip8Emulator::new(rom, Chip8Config::default());
+
+        // Check some font values
+        assert_eq!(emulator.ram[0x50], 0xF0);
+        assert_eq!(emulator.ram[0x9F], 0x80);
+
+        // Check the markers
+        assert_eq!(emulator.ram[PROGRAM_START_ADDRESS as usize], 42);
+        assert_eq!(emulator.ram[PROGRAM_START_ADDRESS as usize + PROGRAM_MAX_SIZE - 1], 69);
+    }
+
+    #[test]
+    fn test_custom_font() {
+        let font: Vec<u8> = (0..80).collect();
+
+        let emulator = Chip8EmulatorBuilder::new().font(&font).build().unwrap();
+
+        assert_eq!(emulator.ram[0x50..0xA0], font[..]);
+        assert_eq!(emulator.ram[0xA0], 0);
+
+        // A big font follows straight after the small one
+        let font: Vec<u8> = (0..240).map(|byte| byte as u8).collect();
+        let mut emulator = Chip8EmulatorBuilder::new().font(&font).build().unwrap();
+
+        assert_eq!(emulator.ram[0x50..0x140], font[..]);
+
+        // The custom font survives a reset
+        emulator.reset();
+        assert_eq!(emulator.ram[0x50..0x140], font[..]);
+
+        let emulator = Chip8Emulator::new(vec![], Chip8Config::default());
+        assert_eq!(emulator.ram[0x50..0xA0], FONTS);
+    }
+
+    #[test]
+    fn test_custom_font_wrong_size() {
+        let result = Chip8EmulatorBuilder::new().font(&[0; 100]).build();
+        assert!(matches!(result.err(), Some(Chip8Error::InvalidFontSize(100))));
+    }
+
+    #[test]
+    #[should_panic(expected = "RomTooLarge")]
+    fn test_emulator_too_large_rom_fails() {
+        Chip8Emulator::new(vec![0; PROGRAM_MAX_SIZE + 1], Chip8Config::default());
+    }
+
+    #[test]
+    fn test_try_new_too_large_rom() {
+        let result = Chip8Emulator::try_new(vec![0; PROGRAM_MAX_SIZE + 1], Chip8Config::default());
+        assert!(matches!(
+            result.err(),
+            Some(Chip8Error::RomTooLarge { size, max: PROGRAM_MAX_SIZE }) if size == PROGRAM_MAX_SIZE + 1
+        ));
+
+        assert!(Chip8Emulator::try_new(vec![0; PROGRAM_MAX_SIZE], Chip8Config::default()).is_ok());
+    }
+
+    #[test]
+    fn test_new_with_config() {
+        let config = Chip8Config {
+            instructions_per_frame: 20,
+            quirks: Quirks::super_chip(),
+            seed: Some(7),
+            program_start: ETI_660_START_ADDRESS,
+            ..Default::default()
+        };
+
+        let emulator = Chip8Emulator::new(vec![0x60, 0x12], config);
+
+        assert_eq!(emulator.instructions_per_frame, 20);
+        assert_eq!(emulator.quirks, Quirks::super_chip());
+        assert_eq!(emulator.seed, Some(7));
+        assert_eq!(emulator.program_counter, 0x600);
+        assert_eq!(emulator.ram[0x600..0x602], [0x60, 0x12]);
+
+        // The defaults match an unconfigured builder. Both are seeded, as the generator's state is
+        // part of the snapshot.
+        let defaults = Chip8Emulator::new(vec![], Chip8Config { seed: Some(0), ..Default::default() });
+        let built = Chip8EmulatorBuilder::new().seed(Some(0)).build().unwrap();
+        assert_eq!(defaults.instructions_per_frame, DEFAULT_INSTRUCTIONS_PER_FRAME);
+        assert_eq!(defaults.snapshot(), built.snapshot());
+
+        let invalid = Chip8Config { foreground_color: 0, background_color: 0, ..Default::default() };
+        assert!(matches!(Chip8Emulator::try_new(vec![], invalid), Err(Chip8Error::IdenticalColors)));
+    }
+
+    /// Sets V1, runs an illegal 5XY0 with a nonzero last nibble, then sets V2
+    fn unknown_opcode_emulator(policy: UnknownOpcodePolicy) -> Chip8Emulator {
+        let program = vec![
+            0x61, 0x11, // V1 = 0x11
+            0x50, 0x01, // Illegal
+            0x62, 0x22, // V2 = 0x22
+            0x12, 0x06, // Jump to 0x206, i.e. infinite loop
+        ];
+
+        Chip8EmulatorBuilder::new().rom(program).ipf(10).unknown_opcode_policy(policy).build().unwrap()
+    }
+
+    #[test]
+    fn test_unknown_opcode_ignore() {
+        let mut emulator = unknown_opcode_emulator(UnknownOpcodePolicy::Ignore);
+
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+        assert_eq!(emulator.registers[1], 0x11);
+        assert_eq!(emulator.registers[2], 0x22);
+    }
+
+    #[test]
+    fn test_unknown_opcode_halt() {
+        let mut emulator = unknown_opcode_emulator(UnknownOpcodePolicy::Halt);
+
+        let halted = FrameStatus::Halted { address: 0x202, opcode: 0x5001 };
+        assert_eq!(emulator.run_60hz_frame([false; 16]), halted);
+        assert_eq!(emulator.registers[1], 0x11);
+        assert_eq!(emulator.registers[2], 0);
+        assert_eq!(emulator.program_counter, 0x202);
+
+        // Stays stuck on the opcode rather than skipping past it
+        assert_eq!(emulator.run_60hz_frame([false; 16]), halted);
+        assert_eq!(emulator.registers[2], 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid opcode 5001 at 0x202")]
+    fn test_unknown_opcode_panic() {
+        let mut emulator = unknown_opcode_emulator(UnknownOpcodePolicy::Panic);
+        emulator.run_60hz_frame([false; 16]);
+    }
+
+    #[test]
+    fn test_fetch_past_end_of_ram() {
+        let mut emulator = Chip8Emulator::new(vec![], Chip8Config::default());
+        emulator.ram[RAM_SIZE - 1] = 0x61;
+        emulator.ram[0] = 0x23;
+        emulator.program_counter = (RAM_SIZE - 1) as u16;
+
+        // The instruction straddling the end of RAM wraps around to read its second byte
+        emulator.tick();
+        assert_eq!(emulator.registers[1], 0x23);
+        assert_eq!(emulator.program_counter, 1);
+
+        // Even past the end of RAM entirely, e.g. after BNNN with a large V0
+        emulator.program_counter = 0x10FE;
+        emulator.tick();
+        assert_eq!(emulator.program_counter, 0x100);
+    }
+
+    #[test]
+    fn test_fetch_past_end_of_ram_halt() {
+        let mut emulator = unknown_opcode_emulator(UnknownOpcodePolicy::Halt);
+        emulator.program_counter = (RAM_SIZE - 1) as u16;
+
+        assert_eq!(
+            emulator.run_60hz_frame([false; 16]),
+            FrameStatus::Halted { address: (RAM_SIZE - 1) as u16, opcode: 0x0000 }
+        );
+        assert_eq!(emulator.program_counter, (RAM_SIZE - 1) as u16);
+    }
+
+    #[test]
+    fn test_parse_unknown_opcode_policy() {
+        assert_eq!("ignore".parse(), Ok(UnknownOpcodePolicy::Ignore));
+        assert_eq!("halt".parse(), Ok(UnknownOpcodePolicy::Halt));
+        assert_eq!("panic".parse(), Ok(UnknownOpcodePolicy::Panic));
+        assert!("stop".parse::<UnknownOpcodePolicy>().is_err());
+    }
+
+    #[test]
+    fn test_step() {
+        let program = vec![
+            0x60, 0x12, // V0 = 0x12
+            0x61, 0x34, // V1 = 0x34
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.delay_timer = 5;
+
+        let (instruction, status) = emulator.step();
+
+        assert_eq!(instruction.raw_instruction, 0x6012);
+        assert_eq!(status, FrameStatus::Completed);
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 2);
+        assert_eq!(emulator.registers[0], 0x12);
+        assert_eq!(emulator.registers[1], 0);
+        assert_eq!(emulator.delay_timer, 5); // Timers aren't ticked
+    }
+
+    #[test]
+    fn test_tick() {
+        let program = vec![
+            0x60, 0x05, // V0 = 5
+            0x30, 0x05, // Skip the next instruction, V0 is 5
+            0x00, 0xE0, // Clear the screen, skipped
+            0x12, 0x0A, // Jump to 0x20A
+            0x00, 0xE0, // Clear the screen, jumped over
+            0x70, 0x01, // V0 += 1
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.delay_timer = 5;
+
+        let program_counters: Vec<u16> = (0..4)
+            .map(|_| {
+                emulator.tick();
+                emulator.program_counter
+            })
+            .collect();
+
+        assert_eq!(program_counters, [0x202, 0x206, 0x20A, 0x20C]);
+        assert_eq!(emulator.instructions_run(), 4);
+        assert_eq!(emulator.registers[0], 6);
+
+        // The timers are left to the caller
+        assert_eq!(emulator.delay_timer, 5);
+        emulator.tick_timers();
+        assert_eq!(emulator.delay_timer, 4);
+    }
+
+    #[test]
+    fn test_tick_ignores_watchpoints() {
+        let program = vec![
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x55, // Store V0 at 0x300
+            0x12, 0x04, // Jump to 0x204, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.add_watchpoint(0x300);
+        emulator.tick();
+        emulator.tick();
+
+        // The write isn't reported later by a frame either
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+    }
+
+    #[test]
+    fn test_set_register() {
+        let mut emulator = Chip8Emulator::new(vec![0x80, 0xA4], Chip8Config::default());
+        emulator.set_register(0xA, 0x42);
+        emulator.set_register(0x10, 0x01); // Masked to V0
+
+        assert_eq!(emulator.register(0xA), 0x42);
+        assert_eq!(emulator.register(0x1A), 0x42);
+        assert_eq!(emulator.register(0), 0x01);
+
+        // V0 += VA
+        emulator.tick();
+        assert_eq!(emulator.register(0), 0x43);
+        assert_eq!(emulator.registers()[0], 0x43);
+        assert_eq!(emulator.program_counter(), 0x202);
+        assert_eq!(emulator.index_register(), 0);
+    }
+
+    #[test]
+    fn test_register_dump() {
+        let mut emulator = Chip8Emulator::new(vec![], Chip8Config::default());
+        emulator.registers[0xA] = 0xBC;
+        emulator.index_register = 0x123;
+
+        assert_eq!(
+            emulator.register_dump(),
+            "PC=0200 I=0123 V0=00 V1=00 V2=00 V3=00 V4=00 V5=00 V6=00 V7=00 \
+             V8=00 V9=00 VA=BC VB=00 VC=00 VD=00 VE=00 VF=00"
+        );
+    }
+
+    #[test]
+    fn test_instruction_stats() {
+        let program = vec![
+            0x60, 0x03, // V0 = 3
+            0x70, 0xFF, // V0 -= 1
+            0x30, 0x00, // Skip the jump once V0 == 0
+            0x12, 0x02, // Jump to 0x202
+            0xFF, 0xFF, // Invalid
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        assert!(emulator.instruction_stats().is_empty());
+
+        for _ in 0..9 {
+            emulator.tick();
+        }
+
+        assert_eq!(emulator.instructions_run(), 9);
+        assert_eq!(
+            emulator.instruction_stats(),
+            vec![("3XNN", 3), ("7XNN", 3), ("1NNN", 2), ("6XNN", 1)]
+        );
+
+        emulator.tick();
+
+        assert_eq!(emulator.instruction_stats().last(), Some(&("????", 1)));
+    }
+
+    #[test]
+    fn test_reset() {
+        let program = vec![
+            0x60, 0x12, // V0 = 0x12
+            0xA2, 0x00, // I = 0x200
+            0xF0, 0x55, // Overwrite the first instruction with V0
+            0xD0, 0x01, // Draw at (V0, V0), height 1
+            0x22, 0x00, // Call 0x200
+        ];
+
+        let mut emulator = Chip8Emulator::new(program.clone(), Chip8Config::default());
+        for _ in 0..5 {
+            emulator.tick();
+        }
+        emulator.delay_timer = 10;
+        emulator.sound_timer = 10;
+
+        assert_eq!(emulator.ram[0x200], 0x12);
+
+        emulator.reset();
+
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS);
+        assert_eq!(emulator.index_register, 0);
+        assert_eq!(emulator.registers, [0; 16]);
+        assert_eq!(emulator.stack_pointer, 0);
+        assert_eq!(emulator.delay_timer, 0);
+        assert_eq!(emulator.sound_timer, 0);
+        assert!(emulator.display_buffer.iter().all(|pixel| *pixel == emulator.background_color));
+
+        // Program and fonts are still in RAM, with the self-modification undone
+        assert_eq!(emulator.ram[0x200..0x20A], program[..]);
+        assert_eq!(emulator.ram[0x50], 0xF0);
+    }
+
+    #[test]
+    fn test_turbo_frame() {
+        let program = vec![
+            0x70, 0x01, // V0 += 1
+            0x12, 0x00, // Jump to 0x200
+        ];
+
+        let config = Chip8Config { instructions_per_frame: 10, ..Default::default() };
+        let mut normal = Chip8Emulator::new(program.clone(), config.clone());
+        let mut turbo = Chip8Emulator::new(program, config);
+        normal.delay_timer = 50;
+        turbo.delay_timer = 50;
+
+        normal.run_60hz_frame([false; 16]);
+        turbo.run_turbo_frame([false; 16], 4);
+
+        assert_eq!(normal.instructions_run(), 10);
+        assert_eq!(turbo.instructions_run(), 40);
+        assert_eq!(turbo.registers[0], 20);
+
+        // Both only ticked the timers once
+        assert_eq!(normal.delay_timer, 49);
+        assert_eq!(turbo.delay_timer, 49);
+
+        // A huge multiplier doesn't overflow, the breakpoint stops it long before running out
+        turbo.add_breakpoint(0x202);
+        assert_eq!(turbo.run_turbo_frame([false; 16], u32::MAX), FrameStatus::HitBreakpoint(0x202));
+    }
+
+    #[test]
+    fn test_advance() {
+        let program = vec![
+            0x60, 0xFF, // V0 = 255
+            0xF0, 0x15, // Delay timer = V0
+            0x12, 0x04, // Jump to 0x204, i.e. infinite loop
+        ];
+        let config = Chip8Config { instructions_per_frame: 10, ..Default::default() };
+
+        // A second is 60 frames, so the timer set during the first frame ticks on the other 59
+        let mut emulator = Chip8Emulator::new(program.clone(), config.clone());
+        assert_eq!(emulator.advance(Duration::from_secs(1), [false; 16]), FrameStatus::Completed);
+        assert_eq!(emulator.delay_timer, 255 - 59);
+        assert_eq!(emulator.instructions_run(), 600);
+
+        // The same however the second is split up, even into steps shorter than an instruction or
+        // that don't line up with frames
+        let mut emulator = Chip8Emulator::new(program.clone(), config.clone());
+        for _ in 0..1000 {
+            emulator.advance(Duration::from_millis(1), [false; 16]);
+        }
+        assert_eq!(emulator.delay_timer, 255 - 59);
+        assert_eq!(emulator.instructions_run(), 600);
+
+        let mut emulator = Chip8Emulator::new(program, config);
+        for _ in 0..142 {
+            emulator.advance(Duration::from_millis(7), [false; 16]);
+        }
+        assert_eq!(emulator.instructions_run(), 596);
+        emulator.advance(Duration::from_millis(6), [false; 16]);
+        assert_eq!(emulator.delay_timer, 255 - 59);
+        assert_eq!(emulator.instructions_run(), 600);
+    }
+
+    #[test]
+    fn test_advance_display_wait() {
+        let program = vec![
+            0xD0, 0x01, // Draw, ending the frame
+            0x12, 0x00, // Jump to 0x200
+        ];
+        let quirks = Quirks { display_wait: true, ..Quirks::default() };
+        let config = Chip8Config { instructions_per_frame: 10, quirks, ..Default::default() };
+        let mut emulator = Chip8Emulator::new(program, config);
+
+        // Only the draw runs, however the frame is split up, then the next frame starts over
+        for _ in 0..5 {
+            emulator.advance(Duration::from_micros(3334), [false; 16]);
+        }
+        assert_eq!(emulator.instructions_run(), 1);
+        emulator.advance(Duration::from_micros(3334), [false; 16]);
+        assert_eq!(emulator.instructions_run(), 3);
+    }
+
+    #[test]
+    fn test_many_instructions_per_frame() {
+        let program = vec![
+            0x70, 0x01, // V0 += 1
+            0x12, 0x00, // Jump to 0x200
+        ];
+
+        let mut emulator = Chip8Emulator::new(
+            program,
+            Chip8Config { instructions_per_frame: 3000, ..Default::default() },
+        );
+        emulator.run_60hz_frame([false; 16]);
+
+        assert_eq!(emulator.instructions_run(), 3000);
+        assert_eq!(emulator.registers[0], (1500 % 256) as u8);
+    }
+
+    #[test]
+    fn test_load_rom() {
+        let mut emulator = Chip8Emulator::new(vec![0x60, 0x12, 0x61, 0x34], Chip8Config::default());
+        emulator.tick();
+        emulator.add_breakpoint(0x200);
+
+        emulator.load_rom(vec![0x62, 0x56]).unwrap();
+
+        // The old program is gone entirely, not just overwritten at the start
+        assert_eq!(emulator.ram[0x200..0x204], [0x62, 0x56, 0x00, 0x00]);
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS);
+        assert_eq!(emulator.registers[0], 0);
+        assert!(emulator.has_breakpoint(0x200));
+
+        emulator.tick();
+        assert_eq!(emulator.registers[2], 0x56);
+
+        // The new ROM is what gets reloaded on reset
+        emulator.reset();
+        assert_eq!(emulator.ram[0x200..0x202], [0x62, 0x56]);
+
+        // A ROM that doesn't fit is refused, and the current one keeps running
+        let result = emulator.load_rom(vec![0; PROGRAM_MAX_SIZE + 1]);
+        assert!(matches!(result, Err(Chip8Error::RomTooLarge { max: PROGRAM_MAX_SIZE, .. })));
+        assert_eq!(emulator.ram[0x200..0x202], [0x62, 0x56]);
+    }
+
+    #[test]
+    fn test_breakpoint() {
+        let program = vec![
+            0x60, 0x01, // V0 = 1
+            0x61, 0x01, // V1 = 1
+            0x62, 0x01, // V2 = 1
+            0x12, 0x06, // Jump to 0x206, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.add_breakpoint(0x204);
+
+        let status = emulator.run_60hz_frame([false; 16]);
+
+        assert_eq!(status, FrameStatus::HitBreakpoint(0x204));
+        assert_eq!(emulator.program_counter, 0x204);
+        assert_eq!(emulator.registers[1], 1);
+        assert_eq!(emulator.registers[2], 0); // Halted before the breakpoint instruction ran
+        assert_eq!(emulator.next_instruction(), 0x6201);
+
+        // Resumes past the breakpoint
+        let status = emulator.run_60hz_frame([false; 16]);
+
+        assert_eq!(status, FrameStatus::Completed);
+        assert_eq!(emulator.registers[2], 1);
+
+        assert!(emulator.has_breakpoint(0x204));
+        emulator.remove_breakpoint(0x204);
+        assert!(!emulator.has_breakpoint(0x204));
+
+        emulator.reset();
+
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+        assert_eq!(emulator.registers[2], 1);
+    }
+
+    #[test]
+    fn test_step_over() {
+        let program = vec![
+            0x22, 0x08, // Call 0x208
+            0x60, 0x01, // V0 = 1
+            0x22, 0x0E, // Call 0x20E
+            0x00, 0x00,
+            0x61, 0x01, // 0x208: V1 = 1
+            0x62, 0x01, // V2 = 1
+            0x00, 0xEE, // Return
+            0x12, 0x0E, // 0x20E: Jump to 0x20E, i.e. never returns
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+
+        // Runs the whole subroutine and lands after the call
+        assert_eq!(emulator.step_over(), FrameStatus::Completed);
+        assert_eq!(emulator.program_counter, 0x202);
+        assert_eq!(emulator.stack_pointer, 0);
+        assert_eq!(emulator.registers[1..3], [1, 1]);
+        assert_eq!(emulator.instructions_run(), 4);
+
+        // Anything other than a call is a single step
+        assert_eq!(emulator.step_over(), FrameStatus::Completed);
+        assert_eq!(emulator.program_counter, 0x204);
+        assert_eq!(emulator.registers[0], 1);
+
+        // Gives up on a subroutine that never returns
+        assert_eq!(emulator.step_over(), FrameStatus::Completed);
+        assert_eq!(emulator.program_counter, 0x20E);
+        assert_eq!(emulator.instructions_run(), 6 + STEP_OVER_MAX_INSTRUCTIONS as u64);
+
+        // Breakpoints inside the subroutine still stop it
+        emulator.reset();
+        emulator.add_breakpoint(0x20A);
+        assert_eq!(emulator.step_over(), FrameStatus::HitBreakpoint(0x20A));
+        assert_eq!(emulator.registers[1..3], [1, 0]);
+    }
+
+    #[test]
+    fn test_step_over_recursion() {
+        let program = vec![
+            0x22, 0x04, // Call 0x204
+            0x00, 0x00,
+            0x70, 0x01, // 0x204: V0 += 1
+            0x30, 0x03, // Skip the next instruction if V0 == 3
+            0x22, 0x04, // Call 0x204
+            0x00, 0xEE, // Return
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.step_over();
+
+        // Doesn't stop when an inner call returns, only the outermost one
+        assert_eq!(emulator.program_counter, 0x202);
+        assert_eq!(emulator.stack_pointer, 0);
+        assert_eq!(emulator.registers[0], 3);
+    }
+
+    #[test]
+    fn test_idle_loop() {
+        let build = |frames| {
+            Chip8EmulatorBuilder::new().rom(vec![0x12, 0x00]).idle_loop_frames(frames).build().unwrap()
+        };
+
+        let mut emulator = build(Some(3));
+        emulator.delay_timer = 10;
+
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::IdleLoop { address: 0x200 });
+
+        // Once detected the loop isn't run anymore, but the timers keep going
+        let instructions_run = emulator.instructions_run();
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::IdleLoop { address: 0x200 });
+        assert_eq!(emulator.instructions_run(), instructions_run);
+        assert_eq!(emulator.delay_timer, 6);
+
+        // Breaking out of the loop from outside starts the count again
+        emulator.ram[0x201] = 0x02;
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+        assert!(emulator.instructions_run() > instructions_run);
+
+        let mut emulator = build(None);
+        for _ in 0..100 {
+            assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+        }
+    }
+
+    #[test]
+    fn test_hooks() {
+        let program = vec![
+            0x60, 0x05, // V0 = 5
+            0x22, 0x08, // Call 0x208
+            0x12, 0x04, // Jump to 0x204, i.e. infinite loop
+            0x00, 0x00,
+            0x70, 0x01, // 0x208: V0 += 1
+            0x00, 0xEE, // Return
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+
+        let before = Arc::new(Mutex::new(vec![]));
+        let after = Arc::new(Mutex::new(vec![]));
+        let (pre_log, post_log) = (before.clone(), after.clone());
+        emulator.set_pre_hook(move |emulator, instruction| {
+            pre_log.lock().unwrap().push((emulator.program_counter(), instruction.raw_instruction));
+        });
+        emulator.set_post_hook(move |emulator, _| {
+            post_log.lock().unwrap().push(emulator.register(0));
+        });
+
+        for _ in 0..6 {
+            emulator.step();
+        }
+
+        // The pre-hook sees each instruction before it runs, the post-hook sees its effects
+        assert_eq!(
+            *before.lock().unwrap(),
+            vec![
+                (0x200, 0x6005),
+                (0x202, 0x2208),
+                (0x208, 0x7001),
+                (0x20A, 0x00EE),
+                (0x204, 0x1204),
+                (0x204, 0x1204),
+            ]
+        );
+        assert_eq!(*after.lock().unwrap(), vec![5, 5, 6, 6, 6, 6]);
+
+        emulator.clear_hooks();
+        emulator.run_60hz_frame([false; 16]);
+        assert_eq!(before.lock().unwrap().len(), 6);
+    }
+
+    #[test]
+    fn test_watchpoint() {
+        let program = vec![
+            0x60, 0x7B, // V0 = 123
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x33, // Store BCD of V0 at 0x300 - 0x302
+            0x61, 0x01, // V1 = 1
+            0x12, 0x08, // Jump to 0x208, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.add_watchpoint(0x301);
+
+        let status = emulator.run_60hz_frame([false; 16]);
+
+        assert_eq!(status, FrameStatus::HitWatchpoint { address: 0x301, program_counter: 0x204 });
+        assert_eq!(emulator.ram[0x300..0x303], [1, 2, 3]); // The write itself still happens
+        assert_eq!(emulator.program_counter, 0x206);
+        assert_eq!(emulator.registers[1], 0);
+
+        // Resumes after the writing instruction
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+        assert_eq!(emulator.registers[1], 1);
+
+        // Ranges catch a write anywhere inside them, and writes elsewhere are ignored
+        emulator.clear_watchpoints();
+        emulator.add_watchpoint_range(0x303..=0x310);
+        emulator.reset();
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+
+        emulator.add_watchpoint_range(0x2F0..=0x300);
+        emulator.reset();
+        let status = emulator.run_60hz_frame([false; 16]);
+        assert_eq!(status, FrameStatus::HitWatchpoint { address: 0x300, program_counter: 0x204 });
+    }
+
+    #[test]
+    fn test_step_watchpoint() {
+        let program = vec![
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x55, // Store V0 at 0x300
+            0x61, 0x01, // V1 = 1
+            0x12, 0x06, // Jump to 0x206, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.add_watchpoint(0x300);
+
+        assert_eq!(emulator.step().1, FrameStatus::Completed);
+        assert_eq!(emulator.step().1, FrameStatus::HitWatchpoint { address: 0x300, program_counter: 0x202 });
+
+        // The step has already reported the hit, so the next frame doesn't stop for it again
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+        assert_eq!(emulator.registers[1], 1);
+    }
+
+    #[test]
+    fn test_step_halt() {
+        let mut emulator = unknown_opcode_emulator(UnknownOpcodePolicy::Halt);
+
+        emulator.step();
+        assert_eq!(emulator.step().1, FrameStatus::Halted { address: 0x202, opcode: 0x5001 });
+        assert!(!emulator.halted);
+    }
+
+    #[test]
+    fn test_break_condition() {
+        let program = vec![
+            0x75, 0x55, // V5 += 0x55
+            0x12, 0x00, // Jump to 0x200
+        ];
+
+        let mut emulator = Chip8Emulator::new(
+            program,
+            Chip8Config { instructions_per_frame: 100, ..Default::default() },
+        );
+        emulator.add_break_condition("V5=0xFF".parse().unwrap());
+
+        // Stops right after the third add, the first time V5 reaches 0xFF
+        let status = emulator.run_60hz_frame([false; 16]);
+
+        assert_eq!(status, FrameStatus::HitBreakCondition { program_counter: 0x200 });
+        assert_eq!(emulator.registers[5], 0xFF);
+        assert_eq!(emulator.program_counter, 0x202);
+        assert_eq!(emulator.instructions_run(), 5);
+
+        // V5 moves on and doesn't hit 0xFF again for a long time
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+
+        // A condition that's already true doesn't stop execution until it becomes true again
+        emulator.clear_break_conditions();
+        emulator.add_break_condition("I=0".parse().unwrap());
+        assert_eq!(emulator.run_60hz_frame([false; 16]), FrameStatus::Completed);
+    }
+
+    #[test]
+    fn test_break_condition_with_breakpoint() {
+        let program = vec![
+            0x75, 0x01, // V5 += 1
+            0x12, 0x00, // Jump to 0x200
+        ];
+
+        let mut emulator = Chip8Emulator::new(
+            program,
+            Chip8Config { instructions_per_frame: 100, ..Default::default() },
+        );
+        emulator.add_breakpoint(0x202);
+        emulator.add_break_condition("V5>=3".parse().unwrap());
+
+        // The breakpoint is skipped until the condition holds too
+        let status = emulator.run_60hz_frame([false; 16]);
+
+        assert_eq!(status, FrameStatus::HitBreakpoint(0x202));
+        assert_eq!(emulator.registers[5], 3);
+
+        let status = emulator.run_60hz_frame([false; 16]);
+
+        assert_eq!(status, FrameStatus::HitBreakpoint(0x202));
+        assert_eq!(emulator.registers[5], 4);
+    }
+
+    #[test]
+    fn test_read_byte() {
+        let mut emulator = Chip8Emulator::new(vec![0x60, 0x12], Chip8Config::default());
+
+        assert_eq!(emulator.read_byte(0x200).unwrap(), 0x60);
+        assert_eq!(emulator.read_byte(0x201).unwrap(), 0x12);
+        assert_eq!(emulator.read_byte(FONT_START_ADDRESS).unwrap(), FONTS[0]);
+        assert!(matches!(
+            emulator.read_byte(0x1000),
+            Err(Chip8Error::AddressOutOfBounds { address: 0x1000, memory_size: RAM_SIZE })
+        ));
+
+        emulator.write_byte(0x201, 0x34).unwrap();
+        emulator.write_byte(0xFFF, 0xAB).unwrap();
+        assert_eq!(emulator.read_byte(0x201).unwrap(), 0x34);
+        assert_eq!(emulator.read_byte(0xFFF).unwrap(), 0xAB);
+        assert!(matches!(emulator.write_byte(0x1000, 0), Err(Chip8Error::AddressOutOfBounds { .. })));
+
+        // The program sees the written byte
+        emulator.tick();
+        assert_eq!(emulator.registers[0], 0x34);
+    }
+
+    #[test]
+    fn test_dump_memory() {
+        let program = vec![0x60, 0x12, 0x61, 0x34, 0xA2, 0x00];
+
+        let emulator = Chip8Emulator::new(program.clone(), Chip8Config::default());
+
+        assert_eq!(emulator.dump_memory(PROGRAM_START_ADDRESS, program.len()), &program[..]);
+        assert_eq!(emulator.dump_memory(0x50, 5), &[0xF0, 0x90, 0x90, 0x90, 0xF0]);
+
+        // Out of range requests are clamped
+        assert_eq!(emulator.dump_memory(0xFFE, 10).len(), 2);
+        assert_eq!(emulator.dump_memory(0xFFFF, 10).len(), 0);
+        assert_eq!(emulator.dump_memory(0x200, usize::MAX).len(), RAM_SIZE - 0x200);
+    }
+
+    #[test]
+    fn test_00e0() {
+        let mut emulator = Chip8Emulator::new(vec![0x00, 0xE0], Chip8Config::default());
+
+        emulator.display_buffer.fill(69);
+        emulator.tick();
+
+        assert!(emulator.display_buffer.iter().all(|i| *i == 0));
+    }
+
+    #[test]
+    fn test_display_dirty() {
+        let program = vec![
+            0x00, 0xE0, // Clear the screen
+            0x60, 0x05, // V0 = 5
+            0xD0, 0x01, // Draw at (V0, V0), height 1
+            0x12, 0x06, // Jump to 0x206, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(
+            program,
+            Chip8Config { instructions_per_frame: 1, ..Default::default() },
+        );
+
+        // Starts dirty so the first frame is drawn
+        assert!(emulator.take_display_dirty());
+        assert!(!emulator.take_display_dirty());
+
+        emulator.run_60hz_frame([false; 16]);
+        assert!(emulator.take_display_dirty());
+
+        emulator.run_60hz_frame([false; 16]);
+        assert!(!emulator.take_display_dirty());
+
+        emulator.run_60hz_frame([false; 16]);
+        assert!(emulator.take_display_dirty());
+
+        // Idle frames don't touch the display
+        for _ in 0..3 {
+            emulator.run_60hz_frame([false; 16]);
+            assert!(!emulator.take_display_dirty());
+        }
+    }
+
+    #[test]
+    fn test_00ee() {
+        let mut emulator = Chip8Emulator::new(vec![0x00, 0xEE], Chip8Config::default());
+
+        emulator.stack[0] = 0x1234;
+        emulator.stack_pointer += 1;
+
+        emulator.tick();
+
+        assert_eq!(emulator.program_counter, 0x1234);
+        assert_eq!(emulator.stack_pointer, 0);
+    }
+
+    #[test]
+    fn test_00ee_stack_underflow() {
+        let mut emulator = Chip8Emulator::new(vec![0x00, 0xEE], Chip8Config::default());
+
+        emulator.tick();
+
+        // The return is ignored, execution carries on to the next instruction
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 2);
+        assert_eq!(emulator.stack_pointer, 0);
+    }
+
+    #[test]
+    fn test_1nnn() {
+        let mut emulator = Chip8Emulator::new(vec![0x12, 0x34], Chip8Config::default());
+        emulator.tick();
+        assert_eq!(emulator.program_counter, 0x234);
+    }
+
+    #[test]
+    fn test_2nnn() {
+        let mut emulator = Chip8Emulator::new(vec![0x21, 0x23], Chip8Config::default());
+        emulator.tick();
+
+        assert_eq!(emulator.program_counter, 0x123);
+        assert_eq!(emulator.stack_pointer, 1);
+        assert_eq!(emulator.stack[0], 0x202);
+    }
+
+    #[test]
+    fn test_2nnn_stack_overflow() {
+        let program = vec![
+            0x22, 0x00, // Call 0x200, i.e. infinite recursion
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        for _ in 0..16 {
+            emulator.tick();
+        }
+
+        assert_eq!(emulator.stack_pointer, 16);
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS);
+
+        // The 17th call is ignored, execution carries on to the next instruction
+        emulator.tick();
+
+        assert_eq!(emulator.stack_pointer, 16);
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 2);
+    }
+
+    #[test]
+    fn test_3xnn() {
+        let program = vec![
+            0x61, 0x12, // Set register 1 to 0x12
+            0x31, 0x00, // If register 1 == 0, skip next instruction
+            0x31, 0x12, // If register 1 == 0x12, skip next instruction
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.tick();
+        emulator.tick(); // Should not skip
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 4);
+
+        emulator.tick(); // Should skip
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 8);
+    }
+
+    #[test]
+    fn test_4xnn() {
+        let program = vec![
+            0x40, 0x00, // V0 == 0, so don't skip
+            0x40, 0x01, // V= != 1, so skip
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+
+        emulator.tick(); // Should not skip
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 2);
+
+        emulator.tick(); // Should skip
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 6);
+    }
+
+    #[test]
+    fn test_5xy0() {
+        let program = vec![
+            0x61, 0x12, // Set V1 to 0x12
+            0x50, 0x10, // Skip if V0 == V1, so don't skip
+            0x50, 0x20, // Skip if V0 == V2, so skip
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+
+        emulator.tick();
+        emulator.tick(); // Should not skip
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 4);
+
+        emulator.tick(); // Should skip
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 8);
+    }
+
+    #[test]
+    fn test_6xnn() {
+        let mut emulator = Chip8Emulator::new(vec![0x60, 0x12, 0x6e, 0x34], Chip8Config::default());
+
+        emulator.tick();
+        assert_eq!(emulator.registers[0], 0x12);
+
+        emulator.tick();
+        assert_eq!(emulator.registers[0xe], 0x34);
+    }
+
+    #[test]
+    fn test_7xnn() {
+        let program = vec![
+            0x71, 0x01, // Add 1 to V1
+            0x71, 0x02, // Add 2 to V1
+            0x71, 0xFF, // Add 255 to V1. Should overflow back to 2
+        ];
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+
+        emulator.tick();
+        assert_eq!(emulator.registers[1], 0x1);
+
+        emulator.tick();
+        assert_eq!(emulator.registers[1], 0x3);
+
+        emulator.tick();
+        assert_eq!(emulator.registers[1], 0x2);
+    }
+
+    #[test]
+    fn test_8xy0() {
+        let program = vec![
+            0x6A, 0x20, // Set register A to 0x20
+            0x6B, 0x30, // Set register B to 0x30
+            0x8A, 0xB0, // Set VA to VB
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        for _ in 0..3 {
+            emulator.tick();
         }
+
+        assert_eq!(emulator.registers[0xA], 0x30);
     }
 
     #[test]
-    fn test_initial_ram() {
-        let mut rom = vec![0; PROGRAM_MAX_SIZE];
+    fn test_8xy1() {
+        let program = vec![0x84, 0x51];
 
-        // Set some marker values at the beginning and end
-        rom[0] = 42;
-        rom[PROGRAM_MAX_SIZE - 1] = 69;
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[4] = 0b11110000;
+        emulator.registers[5] = 0b10101010;
 
-        let emulator = Chip8Emulator::new(rom, 10);
+        emulator.tick();
 
-        // Check some font values
-        assert_eq!(emulator.ram[0x50], 0xF0);
-        assert_eq!(emulator.ram[0x9F], 0x80);
+        assert_eq!(emulator.registers[4], 0b11111010); // V4 |= V5
+        assert_eq!(emulator.registers[5], 0b10101010); // V5 should remain unchanged
+    }
 
-        // Check the markers
-        assert_eq!(emulator.ram[PROGRAM_START_ADDRESS as usize], 42);
-        assert_eq!(emulator.ram[PROGRAM_START_ADDRESS as usize + PROGRAM_MAX_SIZE - 1], 69);
+    #[test]
+    fn test_8xy2() {
+        let program = vec![0x84, 0x52];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[4] = 0b11110000;
+        emulator.registers[5] = 0b10101111;
+
+        emulator.tick();
+
+        assert_eq!(emulator.registers[4], 0b10100000); // V4 &= V5
+        assert_eq!(emulator.registers[5], 0b10101111); // V5 should remain unchanged
     }
 
     #[test]
-    #[should_panic(expected = "PROGRAM_MAX_SIZE")]
-    fn test_emulator_too_large_rom_fails() {
-        Chip8Emulator::new(vec![0; PROGRAM_MAX_SIZE + 1], 10);
+    fn test_8xy3() {
+        let program = vec![
+            0x60, 0b10101010, // Set V0
+            0x61, 0b11110000, // Set V1
+            0x80, 0x13,       // V0 ^= V1
+            0x80, 0x13,       // V0 ^= V1, should restore the original V0
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[0xF] = 0x42;
+
+        for _ in 0..3 {
+            emulator.tick();
+        }
+
+        assert_eq!(emulator.registers[0], 0b01011010);
+        assert_eq!(emulator.registers[1], 0b11110000); // V1 should remain unchanged
+        assert_eq!(emulator.registers[0xF], 0x42); // VF should not be touched
+
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 0b10101010);
+        assert_eq!(emulator.registers[0xF], 0x42);
     }
 
     #[test]
-    fn test_00e0() {
-        let mut emulator = Chip8Emulator::new(vec![0x00, 0xE0], 10);
+    fn test_vf_reset_on_logic() {
+        let program = vec![
+            0x80, 0x11, // V0 |= V1
+            0x80, 0x12, // V0 &= V1
+            0x80, 0x13, // V0 ^= V1
+        ];
+
+        let mut unchanged = Chip8Emulator::new(program.clone(), Chip8Config::default());
+        let mut resets = Chip8Emulator::new(
+            program,
+            Chip8Config {
+                quirks: Quirks { vf_reset_on_logic: true, ..Default::default() },
+                ..Default::default()
+            },
+        );
+
+        for _ in 0..3 {
+            unchanged.registers[0xF] = 0x69;
+            resets.registers[0xF] = 0x69;
+
+            unchanged.tick();
+            resets.tick();
+
+            assert_eq!(unchanged.registers[0xF], 0x69);
+            assert_eq!(resets.registers[0xF], 0);
+        }
+    }
+
+    #[test]
+    fn test_8xy4() {
+        let program = vec![
+            0x66, 254,  // Set register 6 to 254
+            0x67, 1,    // Set register 7 to 1
+            0x86, 0x74, // V6 += V7, should be 255 and not overflow
+            0x86, 0x74, // V6 += V7, should be 0 and overflow
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.tick();
+        emulator.tick();
+        emulator.tick();
+
+        assert_eq!(emulator.registers[6], 255);
+        assert_eq!(emulator.registers[0xF], 0); // Overflow not set
+
+        emulator.tick();
+
+        assert_eq!(emulator.registers[6], 0);
+        assert_eq!(emulator.registers[0xF], 1); // Overflow set
+    }
+
+    #[test]
+    fn test_8xy5() {
+        let program = vec![
+            0x60, 0x02, // Set V0 to 2
+            0x61, 0x03, // Set V1 to 3
+            0x81, 0x05, // V1 -= V0. Expect V1 == 1 and VF == 1
+            0x81, 0x05, // V1 -= V0. Expect V1 == 255 and Vf == 0
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+
+        emulator.tick();
+        emulator.tick();
+        emulator.tick();
+
+        assert_eq!(emulator.registers[1], 1);
+        assert_eq!(emulator.registers[0xF], 1);
+
+        emulator.tick();
+
+        assert_eq!(emulator.registers[1], 255);
+        assert_eq!(emulator.registers[0xF], 0);
+    }
+
+    #[test]
+    fn test_8xy6() {
+        let program = vec![
+            0x60, 0b10, // Set V0 to 0b10
+            0x80, 0x06, // V0 >>= 1, Expect V0 == 1 and VF == 0
+            0x80, 0x06, // V0 >>= 1, Expect V0 == 0 and VF == 1
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.tick();
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 1);
+        assert_eq!(emulator.registers[0xF], 0);
+
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 0);
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_8xy6_shift_uses_vy() {
+        let program = vec![
+            0x80, 0x16, // V0 = V1 >> 1
+        ];
+
+        let mut emulator = Chip8Emulator::new(program.clone(), Chip8Config::default());
+        emulator.registers[0] = 0b1000;
+        emulator.registers[1] = 0b0011;
+        emulator.tick();
+
+        // In-place: V1 is ignored
+        assert_eq!(emulator.registers[0], 0b0100);
+        assert_eq!(emulator.registers[0xF], 0);
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.quirks.shift_uses_vy = true;
+        emulator.registers[0] = 0b1000;
+        emulator.registers[1] = 0b0011;
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 0b0001);
+        assert_eq!(emulator.registers[1], 0b0011); // V1 should remain unchanged
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_shift_uses_vy_setting() {
+        let program = vec![
+            0x60, 0b0110, // V0 = 0b0110
+            0x61, 0b0001, // V1 = 0b0001
+            0x80, 0x16,   // V0 = V? >> 1
+            0x82, 0x1E,   // V2 = V? << 1
+        ];
+
+        let in_place_quirks = Quirks { shift_uses_vy: false, ..Default::default() };
+        let uses_vy_quirks = Quirks { shift_uses_vy: true, ..Default::default() };
+
+        let mut in_place = Chip8Emulator::new(
+            program.clone(),
+            Chip8Config { quirks: in_place_quirks, ..Default::default() },
+        );
+        let mut uses_vy = Chip8Emulator::new(
+            program,
+            Chip8Config { quirks: uses_vy_quirks, ..Default::default() },
+        );
+
+        for _ in 0..4 {
+            in_place.tick();
+            uses_vy.tick();
+        }
+
+        assert_eq!(in_place.registers[0..3], [0b0011, 0b0001, 0b0000]);
+        assert_eq!(uses_vy.registers[0..3], [0b0000, 0b0001, 0b0010]);
+    }
+
+    #[test]
+    fn test_quirk_presets() {
+        let program = vec![
+            0x80, 0x16, // V0 = V? >> 1
+            0xB3, 0x00, // Jump to 0x300 + V?
+        ];
+
+        let mut vip = Chip8Emulator::new(
+            program.clone(),
+            Chip8Config { quirks: Quirks::cosmac_vip(), ..Default::default() },
+        );
+        let mut super_chip = Chip8Emulator::new(
+            program,
+            Chip8Config { quirks: Quirks::super_chip(), ..Default::default() },
+        );
+
+        for emulator in [&mut vip, &mut super_chip] {
+            emulator.registers[0] = 0x10;
+            emulator.registers[1] = 0x04;
+            emulator.registers[3] = 0x20;
+            emulator.tick();
+            emulator.tick();
+        }
+
+        assert_eq!(vip.registers[0], 0x02);
+        assert_eq!(vip.program_counter, 0x302);
+
+        assert_eq!(super_chip.registers[0], 0x08);
+        assert_eq!(super_chip.program_counter, 0x320);
+    }
+
+    #[test]
+    fn test_8xy7() {
+        let program = vec![
+            0x60, 0x02, // Set V0 to 2
+            0x61, 0x03, // Set V1 to 3
+            0x80, 0x17, // Set V0 = V1 - V0. Expect V0 == 1 and VF == 1
+            0x81, 0x07, // Set V1 = V0 - V1. Expect V1 == 254 and VF == 0
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+
+        emulator.tick();
+        emulator.tick();
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 1);
+        assert_eq!(emulator.registers[0xF], 1);
+
+        emulator.tick();
+
+        assert_eq!(emulator.registers[1], 254);
+        assert_eq!(emulator.registers[0xF], 0);
+    }
+
+    #[test]
+    fn test_8xye() {
+        let program = vec![
+            0x60, 0b0100_0000, // Set V0
+            0x80, 0x0e, // V0 <<= 1, Expect V0 == 0b1000_0000 and VF == 0
+            0x80, 0x0e, // V0 <<= 1, Expect V0 == 0 and VF == 1
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.tick();
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 0b1000_0000);
+        assert_eq!(emulator.registers[0xF], 0);
+
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 0);
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_8xye_shift_uses_vy() {
+        let program = vec![
+            0x80, 0x1E, // V0 = V1 << 1
+        ];
+
+        let mut emulator = Chip8Emulator::new(program.clone(), Chip8Config::default());
+        emulator.registers[0] = 0b0000_0001;
+        emulator.registers[1] = 0b1000_0001;
+        emulator.tick();
+
+        // In-place: V1 is ignored
+        assert_eq!(emulator.registers[0], 0b0000_0010);
+        assert_eq!(emulator.registers[0xF], 0);
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.quirks.shift_uses_vy = true;
+        emulator.registers[0] = 0b0000_0001;
+        emulator.registers[1] = 0b1000_0001;
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 0b0000_0010);
+        assert_eq!(emulator.registers[1], 0b1000_0001); // V1 should remain unchanged
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_8xye_carry() {
+        let program = vec![
+            0x80, 0x0E, // V0 <<= 1
+            0x8F, 0x0E, // VF <<= 1, VF ends up holding the carry rather than the shifted value
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[0] = 0x80;
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0], 0);
+        assert_eq!(emulator.registers[0xF], 1);
+
+        emulator.registers[0xF] = 0x81;
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_9xy0() {
+        let program = vec![
+            0x60, 0x01, // Set V0 to 1
+            0x91, 0x20, // Skip if V1 != V2, so don't skip
+            0x90, 0x10, // Skip if V0 != V1, so skip
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
 
-        emulator.display_buffer.fill(69);
-        emulator.run_instruction();
+        emulator.tick();
+        emulator.tick(); // Should not skip
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 0x4);
 
-        assert!(emulator.display_buffer.iter().all(|i| *i == 0));
+        emulator.tick(); // Should skip
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 0x8);
     }
 
     #[test]
-    fn test_00ee() {
-        let mut emulator = Chip8Emulator::new(vec![0x00, 0xEE], 10);
+    fn test_bnnn() {
+        let program = vec![
+            0xB3, 0x00, // Jump to 0x300 + V0
+        ];
 
-        emulator.stack[0] = 0x1234;
-        emulator.stack_pointer += 1;
+        let mut emulator = Chip8Emulator::new(program.clone(), Chip8Config::default());
+        emulator.registers[0] = 0x12;
+        emulator.registers[3] = 0x34;
+        emulator.tick();
 
-        emulator.run_instruction();
+        assert_eq!(emulator.program_counter, 0x312);
 
-        assert_eq!(emulator.program_counter, 0x1234);
-        assert_eq!(emulator.stack_pointer, 0);
-    }
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.quirks.jump_uses_vx = true;
+        emulator.registers[0] = 0x12;
+        emulator.registers[3] = 0x34;
+        emulator.tick();
 
-    #[test]
-    fn test_1nnn() {
-        let mut emulator = Chip8Emulator::new(vec![0x12, 0x34], 10);
-        emulator.run_instruction();
-        assert_eq!(emulator.program_counter, 0x234);
+        assert_eq!(emulator.program_counter, 0x334);
     }
 
     #[test]
-    fn test_2nnn() {
-        let mut emulator = Chip8Emulator::new(vec![0x21, 0x23], 10);
-        emulator.run_instruction();
+    fn test_cxnn() {
+        let program = vec![
+            0xC0, 0xFF, // V0 = random & 0xFF
+            0xC1, 0x0F, // V1 = random & 0x0F
+            0xC2, 0x00, // V2 = random & 0x00
+        ];
 
-        assert_eq!(emulator.program_counter, 0x123);
-        assert_eq!(emulator.stack_pointer, 1);
-        assert_eq!(emulator.stack[0], 0x202);
+        let mut emulator = Chip8EmulatorBuilder::new().rom(program).seed(Some(42)).build().unwrap();
+        emulator.registers[2] = 0x69;
+
+        for _ in 0..3 {
+            emulator.tick();
+        }
+
+        assert_eq!(emulator.registers[0], 162);
+        assert_eq!(emulator.registers[1], 3);
+        assert_eq!(emulator.registers[2], 0); // Masked off entirely
     }
 
     #[test]
-    fn test_3xnn() {
+    fn test_cxnn_seeded() {
         let program = vec![
-            0x61, 0x12, // Set register 1 to 0x12
-            0x31, 0x00, // If register 1 == 0, skip next instruction
-            0x31, 0x12, // If register 1 == 0x12, skip next instruction
+            0xC0, 0xFF, // V0 = random & 0xFF
+            0x12, 0x00, // Jump to 0x200
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
-        emulator.run_instruction();
-        emulator.run_instruction(); // Should not skip
-        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 4);
+        let builder = Chip8EmulatorBuilder::new().rom(program).seed(Some(7));
+        let mut first = builder.clone().build().unwrap();
+        let mut second = builder.build().unwrap();
 
-        emulator.run_instruction(); // Should skip
-        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 8);
+        let mut results = vec![];
+        for _ in 0..10 {
+            for emulator in [&mut first, &mut second] {
+                emulator.tick();
+                emulator.tick();
+            }
+            assert_eq!(first.registers[0], second.registers[0]);
+            results.push(first.registers[0]);
+        }
+
+        // Resetting replays the same sequence
+        first.reset();
+        for result in results {
+            first.tick();
+            first.tick();
+            assert_eq!(first.registers[0], result);
+        }
     }
 
     #[test]
-    fn test_4xnn() {
-        let program = vec![
-            0x40, 0x00, // V0 == 0, so don't skip
-            0x40, 0x01, // V= != 1, so skip
+    fn test_dxyn() {
+        let program: Vec<u8> = vec![
+            0x60, 1, // Set register 0 to 1
+            0x61, 2, // Set register 1 to 2
+            0x62, 3, // Set register 2 to 3
+            0xA2, 0x0C, // Set index register to 0x20C
+            0xD0, 0x12, // Display to location (1, 2), height 2
+            0xD0, 0x22,       // Display to location (1, 3), height 2
+            0xFF,       // Bitmask row 1
+            0b10101010, // Bitmask row 2
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        for _ in 0..5 {
+            emulator.tick();
+        }
 
-        emulator.run_instruction(); // Should not skip
-        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 2);
+        assert_eq!(emulator.registers[0xF], 0); // no collision
 
-        emulator.run_instruction(); // Should skip
-        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 6);
+        // First row, everything should be set
+        assert_pixel(&emulator, 2 * WIDTH, false);
+        for i in 1..=8 {
+            assert_pixel(&emulator, 2 * WIDTH + i, true);
+        }
+        assert_pixel(&emulator, 2 * WIDTH + 9, false);
+
+        // Second row, alternating
+        assert_pixel(&emulator, 3 * WIDTH, false);
+        for i in 1..=8 {
+            assert_pixel(&emulator, 3 * WIDTH + i, i % 2 == 1);
+        }
+        assert_pixel(&emulator, 3 * WIDTH + 9, false);
+
+        // Should overwrite row 3, leave row 2
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0xF], 1); // Collision
+
+        // First row, everything should be set
+        assert_pixel(&emulator, 2 * WIDTH, false);
+        for i in 1..=8 {
+            assert_pixel(&emulator, 2 * WIDTH + i, true);
+        }
+        assert_pixel(&emulator, 2 * WIDTH + 9, false);
+
+        // Second row, alternating the other way around now because all the 1s got flipped to 0
+        assert_pixel(&emulator, 3 * WIDTH, false);
+        for i in 1..=8 {
+            assert_pixel(&emulator, 3 * WIDTH + i, i % 2 == 0);
+        }
+        assert_pixel(&emulator, 3 * WIDTH + 9, false);
+
+        // New third row, alternating
+        assert_pixel(&emulator, 4 * WIDTH, false);
+        for i in 1..=8 {
+            assert_pixel(&emulator, 4 * WIDTH + i, i % 2 == 1);
+        }
+        assert_pixel(&emulator, 4 * WIDTH + 9, false);
     }
 
     #[test]
-    fn test_5xy0() {
+    fn test_colors() {
         let program = vec![
-            0x61, 0x12, // Set V1 to 0x12
-            0x50, 0x10, // Skip if V0 == V1, so don't skip
-            0x50, 0x20, // Skip if V0 == V2, so skip
+            0xD0, 0x01, // Draw at (0, 0), height 1
+            0xD0, 0x01, // Draw at (0, 0) again, collides
+            0x00, 0xE0, // Clear the screen
+            0xC0,       // Bitmask row 1
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator =
+            Chip8EmulatorBuilder::new().rom(program).colors(0xFF33FF66, 0xFF101010).build().unwrap();
+        emulator.index_register = 0x206;
+        assert!(emulator.display_buffer.iter().all(|pixel| *pixel == 0xFF101010));
 
-        emulator.run_instruction();
-        emulator.run_instruction(); // Should not skip
-        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 4);
+        emulator.tick();
+        assert_eq!(emulator.display_buffer[0..3], [0xFF33FF66, 0xFF33FF66, 0xFF101010]);
 
-        emulator.run_instruction(); // Should skip
-        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 8);
+        emulator.tick();
+        assert_eq!(emulator.registers[0xF], 1);
+        assert_eq!(emulator.display_buffer[0..3], [0xFF101010, 0xFF101010, 0xFF101010]);
+
+        emulator.set_pixel_planes(5, 1);
+        emulator.tick();
+        assert!(emulator.display_buffer.iter().all(|pixel| *pixel == 0xFF101010));
     }
 
     #[test]
-    fn test_6xnn() {
-        let mut emulator = Chip8Emulator::new(vec![0x60, 0x12, 0x6e, 0x34], 10);
-
-        emulator.run_instruction();
-        assert_eq!(emulator.registers[0], 0x12);
+    fn test_dxyn_display_wait() {
+        let program = vec![
+            0x60, 0x01, // V0 = 1
+            0xD0, 0x01, // Draw at (V0, V0), height 1
+            0x61, 0x01, // V1 = 1
+            0x62, 0x01, // V2 = 1
+        ];
 
-        emulator.run_instruction();
-        assert_eq!(emulator.registers[0xe], 0x34);
+        let mut no_wait = Chip8Emulator::new(
+            program.clone(),
+            Chip8Config { instructions_per_frame: 4, ..Default::default() },
+        );
+        let mut wait = Chip8Emulator::new(
+            program,
+            Chip8Config {
+                instructions_per_frame: 4,
+                quirks: Quirks { display_wait: true, ..Default::default() },
+                ..Default::default()
+            },
+        );
+
+        no_wait.run_60hz_frame([false; 16]);
+        wait.run_60hz_frame([false; 16]);
+
+        assert_eq!(no_wait.program_counter, PROGRAM_START_ADDRESS + 8);
+        assert_eq!(no_wait.registers[1], 1);
+
+        // The draw ends the frame early
+        assert_eq!(wait.program_counter, PROGRAM_START_ADDRESS + 4);
+        assert_eq!(wait.registers[1], 0);
+
+        // And the remaining instructions run on the next frame
+        wait.run_60hz_frame([false; 16]);
+        assert_eq!(wait.registers[1], 1);
+        assert_eq!(wait.registers[2], 1);
     }
 
     #[test]
-    fn test_7xnn() {
+    fn test_dxyn_resets_vf() {
         let program = vec![
-            0x71, 0x01, // Add 1 to V1
-            0x71, 0x02, // Add 2 to V1
-            0x71, 0xFF, // Add 255 to V1. Should overflow back to 2
+            0xD0, 0x01, // Draw at (0, 0), height 1
+            0xD0, 0x01, // Draw at (0, 0) again, collides
+            0xD1, 0x11, // Draw at (8, 8), no collision
+            0x80,       // Bitmask row 1
         ];
-        let mut emulator = Chip8Emulator::new(program, 10);
 
-        emulator.run_instruction();
-        assert_eq!(emulator.registers[1], 0x1);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[1] = 8;
+        emulator.index_register = 0x206;
 
-        emulator.run_instruction();
-        assert_eq!(emulator.registers[1], 0x3);
+        emulator.tick();
+        assert_eq!(emulator.registers[0xF], 0);
 
-        emulator.run_instruction();
-        assert_eq!(emulator.registers[1], 0x2);
+        emulator.tick();
+        assert_eq!(emulator.registers[0xF], 1);
+
+        emulator.tick();
+        assert_eq!(emulator.registers[0xF], 0);
     }
 
     #[test]
-    fn test_8xy0() {
+    fn test_dxyn_clips_at_edges() {
         let program = vec![
-            0x6A, 0x20, // Set register A to 0x20
-            0x6B, 0x30, // Set register B to 0x30
-            0x8A, 0xB0, // Set VA to VB
+            0xD0, 0x12, // Draw at (V0, V1), height 2
+            0xFF,       // Bitmask row 1
+            0xFF,       // Bitmask row 2
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
-        for _ in 0..3 {
-            emulator.run_instruction();
-        }
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[0] = 63;
+        emulator.registers[1] = 31;
+        emulator.index_register = 0x202;
+        emulator.tick();
 
-        assert_eq!(emulator.registers[0xA], 0x30);
+        // Only the bottom right pixel should be drawn, everything else is clipped
+        assert_pixel(&emulator, 31 * WIDTH + 63, true);
+        assert_eq!(emulator.display_buffer.iter().filter(|pixel| **pixel != 0).count(), 1);
     }
 
     #[test]
-    fn test_8xy1() {
+    fn test_dxyn_clipping_sets_vf() {
         let program = vec![
-            0x60, 0b10101010, // Set V0
-            0x61, 0b11110000, // Set V1
-            0x80, 0x11,       // V0 |= V1
+            0xD0, 0x12, // Draw at (V0, V1), height 2
+            0xFF,       // Bitmask row 1
+            0xFF,       // Bitmask row 2
         ];
-
-        let mut emulator = Chip8Emulator::new(program, 10);
-        for _ in 0..3 {
-            emulator.run_instruction();
-        }
-
-        assert_eq!(emulator.registers[0], 0b11111010);
+        let draw = |quirks: Quirks, x: u8, y: u8| {
+            let mut emulator = Chip8Emulator::new(program.clone(), Chip8Config { quirks, ..Default::default() });
+            emulator.registers[0] = x;
+            emulator.registers[1] = y;
+            emulator.index_register = 0x202;
+            emulator.tick();
+            emulator.registers[0xF]
+        };
+        let clipping_sets_vf = Quirks { clipping_sets_vf: true, ..Default::default() };
+
+        // The second row falls off the bottom. Nothing collides, so VF is only set with the quirk.
+        assert_eq!(draw(Quirks::default(), 10, 31), 0);
+        assert_eq!(draw(clipping_sets_vf, 10, 31), 1);
+
+        // Fits on the screen, or is only clipped at the right edge
+        assert_eq!(draw(clipping_sets_vf, 10, 30), 0);
+        assert_eq!(draw(clipping_sets_vf, 60, 5), 0);
+
+        // Nothing is clipped when wrapping
+        assert_eq!(draw(Quirks { wrap_sprites: true, ..clipping_sets_vf }, 10, 31), 0);
     }
 
     #[test]
-    fn test_8xy2() {
-        let program = vec![0x84, 0x52];
-
-        let mut emulator = Chip8Emulator::new(program, 10);
-        emulator.registers[4] = 0b11110000;
-        emulator.registers[5] = 0b10101111;
+    fn test_dxyn_wrap_sprites() {
+        let program = vec![
+            0xD0, 0x11, // Draw at (V0, V1), height 1
+            0xFF,       // Bitmask row 1
+        ];
 
-        emulator.run_instruction();
+        let mut clipped = Chip8Emulator::new(program.clone(), Chip8Config::default());
+        let mut wrapped = Chip8Emulator::new(
+            program,
+            Chip8Config {
+                quirks: Quirks { wrap_sprites: true, ..Default::default() },
+                ..Default::default()
+            },
+        );
+
+        for emulator in [&mut clipped, &mut wrapped] {
+            emulator.registers[0] = 60;
+            emulator.registers[1] = 5;
+            emulator.index_register = 0x202;
+            emulator.tick();
+
+            for i in 60..64 {
+                assert_pixel(emulator, 5 * WIDTH + i, true);
+            }
+        }
 
-        assert_eq!(emulator.registers[4], 0b10100000); // V4 &= V5
-        assert_eq!(emulator.registers[5], 0b10101111); // V5 should remain unchanged
+        // The 4 trailing pixels are dropped when clipping, and show up on the left when wrapping
+        for i in 0..4 {
+            assert_pixel(&clipped, 5 * WIDTH + i, false);
+            assert_pixel(&wrapped, 5 * WIDTH + i, true);
+        }
+        assert_pixel(&wrapped, 5 * WIDTH + 4, false);
     }
 
     #[test]
-    fn test_8xy3() {
+    fn test_dxyn_wraps_origin() {
         let program = vec![
-            0x60, 0b10101010, // Set V0
-            0x61, 0b11110000, // Set V1
-            0x80, 0x13,       // V0 ^= V1
+            0xD0, 0x11, // Draw at (V0, V1), height 1
+            0x80,       // Bitmask row 1
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
-        for _ in 0..3 {
-            emulator.run_instruction();
-        }
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[0] = 64 + 5;
+        emulator.registers[1] = 32 + 3;
+        emulator.index_register = 0x202;
+        emulator.tick();
 
-        assert_eq!(emulator.registers[0], 0b01011010);
+        assert_pixel(&emulator, 3 * WIDTH + 5, true);
+        assert_eq!(emulator.display_buffer.iter().filter(|pixel| **pixel != 0).count(), 1);
     }
 
     #[test]
-    fn test_8xy4() {
+    fn test_dxyn_hires() {
         let program = vec![
-            0x66, 254,  // Set register 6 to 254
-            0x67, 1,    // Set register 7 to 1
-            0x86, 0x74, // V6 += V7, should be 255 and not overflow
-            0x86, 0x74, // V6 += V7, should be 0 and overflow
+            0xD0, 0x12, // Draw at (V0, V1), height 2
+            0x00, 0xFF, // Switch to high resolution
+            0xD0, 0x12, // Draw at (V0, V1), height 2
+            0xF0,       // Bitmask row 1
+            0x81,       // Bitmask row 2
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
-        emulator.run_instruction();
-        emulator.run_instruction();
-        emulator.run_instruction();
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[0] = 100;
+        emulator.registers[1] = 40;
+        emulator.index_register = 0x206;
 
-        assert_eq!(emulator.registers[6], 255);
-        assert_eq!(emulator.registers[0xF], 0); // Overflow not set
+        // In low resolution the coordinates wrap around to (36, 8)
+        emulator.tick();
 
-        emulator.run_instruction();
+        assert_eq!(emulator.display_buffer.len(), WIDTH * HEIGHT);
+        assert_pixel(&emulator, 8 * WIDTH + 36, true);
+        assert_pixel(&emulator, 9 * WIDTH + 43, true);
+        assert_eq!(emulator.display_buffer.iter().filter(|pixel| **pixel != 0).count(), 6);
 
-        assert_eq!(emulator.registers[6], 0);
-        assert_eq!(emulator.registers[0xF], 1); // Overflow set
+        // In high resolution they fit on screen as they are
+        emulator.tick();
+        emulator.tick();
+
+        assert_eq!(emulator.display_buffer.len(), HIRES_WIDTH * HIRES_HEIGHT);
+        for i in 0..4 {
+            assert_pixel(&emulator, 40 * HIRES_WIDTH + 100 + i, true);
+        }
+        assert_pixel(&emulator, 41 * HIRES_WIDTH + 100, true);
+        assert_pixel(&emulator, 41 * HIRES_WIDTH + 107, true);
+        assert_eq!(emulator.display_buffer.iter().filter(|pixel| **pixel != 0).count(), 6);
+        assert!(emulator.is_pixel_on(107, 41));
     }
 
     #[test]
-    fn test_8xy5() {
-        let program = vec![
-            0x60, 0x02, // Set V0 to 2
-            0x61, 0x03, // Set V1 to 3
-            0x81, 0x05, // V1 -= V0. Expect V1 == 1 and VF == 1
-            0x81, 0x05, // V1 -= V0. Expect V1 == 255 and Vf == 0
+    fn test_dxy0() {
+        let mut program = vec![
+            0x00, 0xFF, // Switch to high resolution
+            0xD0, 0x10, // Draw a 16x16 sprite at (V0, V1)
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        // Each row has the left half fully set, and the right half alternating
+        for _ in 0..16 {
+            program.extend_from_slice(&[0xFF, 0xAA]);
+        }
+
+        let quirks = Quirks::super_chip();
+        let mut emulator = Chip8Emulator::new(program, Chip8Config { quirks, ..Default::default() });
+        emulator.registers[0] = 20;
+        emulator.registers[1] = 10;
+        emulator.index_register = 0x204;
+        emulator.tick();
+        emulator.tick();
+
+        for row in 0..16 {
+            for column in 0..8 {
+                assert!(emulator.is_pixel_on(20 + column, 10 + row));
+            }
+            for column in 8..16 {
+                assert_eq!(emulator.is_pixel_on(20 + column, 10 + row), column % 2 == 0);
+            }
+        }
+        assert_eq!(lit_pixels(&emulator).len(), 16 * 12);
+        assert_eq!(emulator.registers[0xF], 0);
 
-        emulator.run_instruction();
-        emulator.run_instruction();
-        emulator.run_instruction();
+        // Drawing it again erases it and reports the collision
+        emulator.program_counter = 0x202;
+        emulator.tick();
 
-        assert_eq!(emulator.registers[1], 1);
+        assert!(lit_pixels(&emulator).is_empty());
         assert_eq!(emulator.registers[0xF], 1);
+    }
 
-        emulator.run_instruction();
+    #[test]
+    fn test_dxy0_without_large_sprites() {
+        let mut program = vec![
+            0xD0, 0x10, // Draw a 16x16 sprite at (V0, V1)
+        ];
+        program.extend_from_slice(&[0xFF; 32]);
 
-        assert_eq!(emulator.registers[1], 255);
+        let quirks = Quirks::cosmac_vip();
+        let mut emulator = Chip8Emulator::new(program, Chip8Config { quirks, ..Default::default() });
+        emulator.index_register = 0x202;
+        emulator.registers[0xF] = 1;
+        emulator.tick();
+
+        // Draws nothing, so there's nothing to collide with either
+        assert!(lit_pixels(&emulator).is_empty());
         assert_eq!(emulator.registers[0xF], 0);
+        assert_eq!(emulator.program_counter, 0x202);
     }
 
     #[test]
-    fn test_8xy6() {
+    fn test_dxy0_clips_at_edges() {
+        let mut program = vec![
+            0xD0, 0x10, // Draw a 16x16 sprite at (V0, V1)
+        ];
+        program.extend_from_slice(&[0xFF; 32]);
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[0] = 60;
+        emulator.registers[1] = 30;
+        emulator.index_register = 0x202;
+        emulator.tick();
+
+        // Only the 4x2 corner that fits on the low resolution screen is drawn
+        assert_eq!(lit_pixels(&emulator).len(), 4 * 2);
+        assert!(emulator.is_pixel_on(63, 31));
+    }
+
+    #[test]
+    fn test_dxyn_hires_clips_at_edges() {
         let program = vec![
-            0x60, 0b10, // Set V0 to 0b10
-            0x80, 0x06, // V0 >>= 1, Expect V0 == 1 and VF == 0
-            0x80, 0x06, // V0 >>= 1, Expect V0 == 0 and VF == 1
+            0x00, 0xFF, // Switch to high resolution
+            0xD0, 0x12, // Draw at (V0, V1), height 2
+            0xFF,       // Bitmask row 1
+            0xFF,       // Bitmask row 2
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
-        emulator.run_instruction();
-        emulator.run_instruction();
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[0] = 127;
+        emulator.registers[1] = 63;
+        emulator.index_register = 0x204;
+        emulator.tick();
+        emulator.tick();
 
-        assert_eq!(emulator.registers[0], 1);
-        assert_eq!(emulator.registers[0xF], 0);
+        assert_pixel(&emulator, 63 * HIRES_WIDTH + 127, true);
+        assert_eq!(emulator.display_buffer.iter().filter(|pixel| **pixel != 0).count(), 1);
+    }
 
-        emulator.run_instruction();
+    /// Lights a 2x2 block with its top left corner at (x, y)
+    fn draw_block(emulator: &mut Chip8Emulator, x: usize, y: usize) {
+        let width = emulator.resolution.width();
+        for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            emulator.set_pixel_planes((y + dy) * width + x + dx, 1);
+        }
+    }
 
-        assert_eq!(emulator.registers[0], 0);
-        assert_eq!(emulator.registers[0xF], 1);
+    fn lit_pixels(emulator: &Chip8Emulator) -> Vec<(usize, usize)> {
+        let resolution = emulator.resolution;
+        (0..resolution.height())
+            .flat_map(|y| (0..resolution.width()).map(move |x| (x, y)))
+            .filter(|(x, y)| emulator.is_pixel_on(*x, *y))
+            .collect()
     }
 
     #[test]
-    fn test_8xy7() {
+    fn test_00cn() {
         let program = vec![
-            0x60, 0x02, // Set V0 to 2
-            0x61, 0x03, // Set V1 to 3
-            0x80, 0x17, // Set V0 = V1 - V0. Expect V0 == 1 and VF == 1
-            0x81, 0x07, // Set V1 = V0 - V1. Expect V1 == 254 and VF == 0
+            0x00, 0xC3, // Scroll down 3 lines
+            0x00, 0xCF, // Scroll down 15 lines
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
-
-        emulator.run_instruction();
-        emulator.run_instruction();
-        emulator.run_instruction();
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        draw_block(&mut emulator, 10, 5);
+        draw_block(&mut emulator, 20, 29);
+        emulator.tick();
 
-        assert_eq!(emulator.registers[0], 1);
-        assert_eq!(emulator.registers[0xF], 1);
+        // The bottom block is scrolled off the screen
+        assert_eq!(lit_pixels(&emulator), vec![(10, 8), (11, 8), (10, 9), (11, 9)]);
 
-        emulator.run_instruction();
+        emulator.tick();
 
-        assert_eq!(emulator.registers[1], 254);
-        assert_eq!(emulator.registers[0xF], 0);
+        assert_eq!(lit_pixels(&emulator), vec![(10, 23), (11, 23), (10, 24), (11, 24)]);
     }
 
     #[test]
-    fn test_8xye() {
+    fn test_00fb_00fc() {
         let program = vec![
-            0x60, 0b0100_0000, // Set V0
-            0x80, 0x0e, // V0 <<= 1, Expect V0 == 0b1000_0000 and VF == 0
-            0x80, 0x0e, // V0 <<= 1, Expect V0 == 0 and VF == 1
+            0x00, 0xFB, // Scroll right 4 pixels
+            0x00, 0xFC, // Scroll left 4 pixels
+            0x00, 0xFC, // Scroll left 4 pixels
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
-        emulator.run_instruction();
-        emulator.run_instruction();
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        draw_block(&mut emulator, 2, 0);
+        draw_block(&mut emulator, 61, 30);
+        emulator.tick();
 
-        assert_eq!(emulator.registers[0], 0b1000_0000);
-        assert_eq!(emulator.registers[0xF], 0);
+        assert_eq!(lit_pixels(&emulator), vec![(6, 0), (7, 0), (6, 1), (7, 1)]);
 
-        emulator.run_instruction();
+        emulator.tick();
 
-        assert_eq!(emulator.registers[0], 0);
-        assert_eq!(emulator.registers[0xF], 1);
+        assert_eq!(lit_pixels(&emulator), vec![(2, 0), (3, 0), (2, 1), (3, 1)]);
+
+        // Scrolled off the left edge this time
+        emulator.tick();
+
+        assert!(lit_pixels(&emulator).is_empty());
     }
 
     #[test]
-    fn test_9xy0() {
+    fn test_scroll_hires() {
         let program = vec![
-            0x60, 0x01, // Set V0 to 1
-            0x91, 0x20, // Skip if V1 != V2, so don't skip
-            0x90, 0x10, // Skip if V0 != V1, so skip
+            0x00, 0xFF, // Switch to high resolution
+            0x00, 0xCA, // Scroll down 10 lines
+            0x00, 0xFC, // Scroll left 4 pixels
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.tick();
+        draw_block(&mut emulator, 100, 40);
+        emulator.tick();
+        emulator.tick();
 
-        emulator.run_instruction();
-        emulator.run_instruction(); // Should not skip
-        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 0x4);
-
-        emulator.run_instruction(); // Should skip
-        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 0x8);
+        assert_eq!(lit_pixels(&emulator), vec![(96, 50), (97, 50), (96, 51), (97, 51)]);
     }
 
     #[test]
-    fn test_dxyn() {
-        let program: Vec<u8> = vec![
-            0x60, 1, // Set register 0 to 1
-            0x61, 2, // Set register 1 to 2
-            0x62, 3, // Set register 2 to 3
-            0xA2, 0x0C, // Set index register to 0x20C
-            0xD0, 0x12, // Display to location (1, 2), height 2
-            0xD0, 0x22,       // Display to location (1, 3), height 2
-            0xFF,       // Bitmask row 1
-            0b10101010, // Bitmask row 2
+    fn test_half_scroll_in_lores() {
+        let program = vec![
+            0x00, 0xC3, // Scroll down 3 lines, i.e. 1 low resolution line
+            0x00, 0xFB, // Scroll right 4 pixels, i.e. 2 low resolution pixels
+            0x00, 0xFF, // Switch to high resolution
+            0x00, 0xC3, // Scroll down 3 lines
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
-        for _ in 0..5 {
-            emulator.run_instruction();
-        }
+        let mut emulator = Chip8Emulator::new(
+            program,
+            Chip8Config { quirks: Quirks::super_chip(), ..Default::default() },
+        );
+        draw_block(&mut emulator, 10, 5);
+        emulator.tick();
+        emulator.tick();
 
-        assert_eq!(emulator.registers[0xF], 0); // no collision
+        assert_eq!(lit_pixels(&emulator), vec![(12, 6), (13, 6), (12, 7), (13, 7)]);
 
-        // First row, everything should be set
-        assert_pixel(&emulator, 2 * WIDTH, false);
-        for i in 1..=8 {
-            assert_pixel(&emulator, 2 * WIDTH + i, true);
-        }
-        assert_pixel(&emulator, 2 * WIDTH + 9, false);
+        // High resolution scrolls the full distance
+        emulator.tick();
+        draw_block(&mut emulator, 10, 5);
+        emulator.tick();
+
+        assert_eq!(lit_pixels(&emulator), vec![(10, 8), (11, 8), (10, 9), (11, 9)]);
+    }
+
+    #[test]
+    fn test_00fe_00ff() {
+        let program = vec![
+            0x00, 0xFF, // Switch to high resolution
+            0x00, 0xFE, // Switch to low resolution
+        ];
 
-        // Second row, alternating
-        assert_pixel(&emulator, 3 * WIDTH, false);
-        for i in 1..=8 {
-            assert_pixel(&emulator, 3 * WIDTH + i, i % 2 == 1);
-        }
-        assert_pixel(&emulator, 3 * WIDTH + 9, false);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        assert_eq!(emulator.resolution(), Resolution::Low);
 
-        // Should overwrite row 3, leave row 2
-        emulator.run_instruction();
+        emulator.set_pixel_planes(0, 1);
+        emulator.tick();
 
-        assert_eq!(emulator.registers[0xF], 1); // Collision
+        // Switching resolution clears the screen
+        assert_eq!(emulator.resolution(), Resolution::High);
+        assert_eq!(emulator.display_buffer.len(), HIRES_WIDTH * HIRES_HEIGHT);
+        assert!(emulator.display_buffer.iter().all(|pixel| *pixel == emulator.background_color));
 
-        // First row, everything should be set
-        assert_pixel(&emulator, 2 * WIDTH, false);
-        for i in 1..=8 {
-            assert_pixel(&emulator, 2 * WIDTH + i, true);
-        }
-        assert_pixel(&emulator, 2 * WIDTH + 9, false);
+        emulator.tick();
 
-        // Second row, alternating the other way around now because all the 1s got flipped to 0
-        assert_pixel(&emulator, 3 * WIDTH, false);
-        for i in 1..=8 {
-            assert_pixel(&emulator, 3 * WIDTH + i, i % 2 == 0);
-        }
-        assert_pixel(&emulator, 3 * WIDTH + 9, false);
+        assert_eq!(emulator.resolution(), Resolution::Low);
+        assert_eq!(emulator.display_buffer.len(), WIDTH * HEIGHT);
 
-        // New third row, alternating
-        assert_pixel(&emulator, 4 * WIDTH, false);
-        for i in 1..=8 {
-            assert_pixel(&emulator, 4 * WIDTH + i, i % 2 == 1);
-        }
-        assert_pixel(&emulator, 4 * WIDTH + 9, false);
+        // Reset goes back to low resolution
+        emulator.set_resolution(Resolution::High);
+        emulator.reset();
+        assert_eq!(emulator.resolution(), Resolution::Low);
     }
 
     #[test]
@@ -929,16 +3572,16 @@ mod test {
             0xE1, 0x9E, // Skip if key stored in V1 is pressed
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
 
         emulator.registers[0] = 5;
         emulator.registers[1] = 0xF6; // Top nibble should be ignored
         emulator.keyboard_state[6] = true;
 
-        emulator.run_instruction();
+        emulator.tick();
         assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 2); // Should not have skipped
 
-        emulator.run_instruction();
+        emulator.tick();
         assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 6); // Should have skipped
     }
 
@@ -949,30 +3592,79 @@ mod test {
             0xE1, 0xA1, // Skip if key stored in V1 is not pressed
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
 
         emulator.registers[0] = 0xAA; // Top nibble should be ignored
         emulator.registers[1] = 0xBB;
         emulator.keyboard_state[0xA] = true;
 
-        emulator.run_instruction();
+        emulator.tick();
         assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 2); // Should not have skipped
 
-        emulator.run_instruction();
+        emulator.tick();
         assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 6); // Should have skipped
     }
 
+    #[test]
+    fn test_ex9e_exa1_complementary() {
+        // For every key, exactly one of EX9E / EXA1 should skip
+        for key in 0..16 {
+            for pressed in [false, true] {
+                let mut ex9e = Chip8Emulator::new(vec![0xE0, 0x9E], Chip8Config::default());
+                let mut exa1 = Chip8Emulator::new(vec![0xE0, 0xA1], Chip8Config::default());
+
+                for emulator in [&mut ex9e, &mut exa1] {
+                    emulator.registers[0] = key;
+                    emulator.keyboard_state[key as usize] = pressed;
+                    emulator.tick();
+                }
+
+                let ex9e_skipped = ex9e.program_counter == PROGRAM_START_ADDRESS + 4;
+                let exa1_skipped = exa1.program_counter == PROGRAM_START_ADDRESS + 4;
+
+                assert_eq!(ex9e_skipped, pressed);
+                assert_eq!(exa1_skipped, !pressed);
+            }
+        }
+    }
+
+    #[test]
+    fn test_keyboard_visible_to_frame() {
+        let program = vec![
+            0xE0, 0x9E, // Skip if key in V0 is pressed
+            0x61, 0x01, // V1 = 1 (skipped)
+            0xE0, 0x9E, // Skip if key in V0 is pressed, key state still held
+            0x62, 0x01, // V2 = 1 (skipped)
+        ];
+
+        let mut emulator = Chip8Emulator::new(
+            program,
+            Chip8Config { instructions_per_frame: 2, ..Default::default() },
+        );
+        emulator.registers[0] = 0x7;
+
+        let mut keyboard_state = [false; 16];
+        keyboard_state[0x7] = true;
+
+        emulator.run_60hz_frame(keyboard_state);
+
+        assert_eq!(emulator.registers[1], 0);
+        assert_eq!(emulator.registers[2], 0);
+        assert_eq!(emulator.keyboard_state, keyboard_state);
+    }
+
     #[test]
     fn test_fx07() {
         let program = vec![
             0xF4, 0x07, // Set V4 to delay timer
         ];
 
-        let mut emulater = Chip8Emulator::new(program, 10);
-        emulater.delay_timer = 0x69;
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.delay_timer = 0x69;
 
-        emulater.run_instruction();
-        assert_eq!(emulater.registers[4], 0x69);
+        emulator.tick();
+        assert_eq!(emulator.registers[4], 0x69);
+        assert_eq!(emulator.delay_timer, 0x69); // Reading shouldn't modify the timer
     }
 
     #[test]
@@ -982,7 +3674,7 @@ mod test {
             0x12, 0x02, // Jump to 0x202, i.e. infinite loop
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
         let mut keyboard_state = [false; 16];
 
         emulator.run_60hz_frame(keyboard_state); // Should block
@@ -1000,6 +3692,90 @@ mod test {
         assert_eq!(emulator.registers[0xA], 0xF);
     }
 
+    #[test]
+    fn test_fx0a_on_press() {
+        let program = vec![
+            0xFA, 0x0A, // V0 = get_key()
+            0x12, 0x02, // Jump to 0x202, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.quirks.wait_for_key_release = false;
+        let mut keyboard_state = [false; 16];
+
+        emulator.run_60hz_frame(keyboard_state); // Should block
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS);
+
+        keyboard_state[0x3] = true;
+        keyboard_state[0xC] = true;
+
+        emulator.run_60hz_frame(keyboard_state); // Should continue as soon as the key is down
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS + 2);
+        assert_eq!(emulator.registers[0xA], 0x3);
+    }
+
+    #[test]
+    fn test_fx0a_at_end_of_ram() {
+        let mut emulator = Chip8Emulator::new(vec![], Chip8Config::default());
+        let address = (RAM_SIZE - 2) as u16;
+        emulator.ram[RAM_SIZE - 2] = 0xFA;
+        emulator.ram[RAM_SIZE - 1] = 0x0A;
+        emulator.program_counter = address;
+
+        // The PC wraps to 0 when fetching, so blocking has to go back to the instruction itself
+        emulator.tick();
+        assert_eq!(emulator.program_counter, address);
+
+        let mut keyboard_state = [false; 16];
+        keyboard_state[0x7] = true;
+        emulator.keyboard_state = keyboard_state;
+        emulator.quirks.wait_for_key_release = false;
+        emulator.tick();
+        assert_eq!(emulator.program_counter, 0);
+        assert_eq!(emulator.registers[0xA], 0x7);
+    }
+
+    #[test]
+    fn test_fx15() {
+        let program = vec![
+            0x63, 0x20, // V3 = 0x20
+            0xF3, 0x15, // Set delay timer to V3
+            0x12, 0x04, // Jump to 0x204, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.tick();
+        emulator.tick();
+        assert_eq!(emulator.delay_timer, 0x20);
+
+        // Many instructions run during the frame, but the timer should only tick once
+        emulator.run_60hz_frame([false; 16]);
+        assert_eq!(emulator.delay_timer, 0x1F);
+    }
+
+    #[test]
+    fn test_fx18() {
+        let program = vec![
+            0x63, 0x02, // V3 = 2
+            0xF3, 0x18, // Set sound timer to V3
+            0x12, 0x04, // Jump to 0x204, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        assert!(!emulator.is_beeping());
+
+        emulator.tick();
+        emulator.tick();
+        assert_eq!(emulator.sound_timer, 2);
+        assert!(emulator.is_beeping());
+
+        emulator.run_60hz_frame([false; 16]);
+        assert!(emulator.is_beeping());
+
+        emulator.run_60hz_frame([false; 16]);
+        assert!(!emulator.is_beeping());
+    }
+
     #[test]
     fn test_fx1e() {
         let program = vec![
@@ -1008,27 +3784,98 @@ mod test {
             0xF5, 0x1E, // Adds register 5 to index register
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
         for _ in 0..3 {
-            emulator.run_instruction();
+            emulator.tick();
         }
         assert_eq!(emulator.index_register, 0x125);
     }
 
+    #[test]
+    fn test_fx1e_wraps() {
+        let program = vec![
+            0xF0, 0x1E, // Adds V0 to index register
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.index_register = 0xFFFF;
+        emulator.registers[0] = 0x02;
+        emulator.tick();
+
+        assert_eq!(emulator.index_register, 0x0001);
+        assert_eq!(emulator.registers[0xF], 0); // VF is untouched without the quirk
+    }
+
+    #[test]
+    fn test_fx1e_index_overflow_sets_vf() {
+        let program = vec![
+            0xF0, 0x1E, // Adds V0 to index register, stays in range
+            0xF0, 0x1E, // Adds V0 to index register, goes past 0x0FFF
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.quirks.index_overflow_sets_vf = true;
+        emulator.index_register = 0x0FF0;
+        emulator.registers[0] = 0x0F;
+        emulator.registers[0xF] = 0x69;
+
+        emulator.tick();
+        assert_eq!(emulator.index_register, 0x0FFF);
+        assert_eq!(emulator.registers[0xF], 0);
+
+        emulator.tick();
+        assert_eq!(emulator.index_register, 0x100E);
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
     #[test]
     fn test_fx29() {
         let program = vec![
             0xF7, 0x29 // Store sprite 7 at index register
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
 
         emulator.registers[7] = 0xFA; // Top nibble should be ignored
-        emulator.run_instruction();
+        emulator.tick();
 
         assert_eq!(emulator.index_register, 0x50 + 0xA * 5);
     }
 
+    #[test]
+    fn test_fx29_points_at_font() {
+        let program = vec![
+            0xF3, 0x29, // Store sprite for V3 at index register
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+
+        emulator.registers[3] = 0xB;
+        emulator.tick();
+
+        let index = emulator.index_register as usize;
+        assert_eq!(&emulator.ram[index..index + 5], &[0xE0, 0x90, 0xE0, 0x90, 0xE0]);
+    }
+
+    #[test]
+    fn test_fx30() {
+        let program = vec![
+            0xF3, 0x30, // Store big sprite for V3 at index register
+        ];
+
+        // Each big character is 10 copies of its digit, after 80 bytes of small characters
+        let font: Vec<u8> = (0..80).map(|_| 0xFF).chain((0..160).map(|byte| byte / 10)).collect();
+        let config = Chip8Config { font, ..Default::default() };
+        let mut emulator = Chip8Emulator::new(program, config);
+
+        emulator.registers[3] = 0xFB; // Top nibble should be ignored
+        emulator.tick();
+
+        assert_eq!(emulator.index_register, 0x50 + 80 + 0xB * 10);
+        let index = emulator.index_register as usize;
+        assert_eq!(emulator.ram[index..index + 10], [0xB; 10]);
+    }
+
     #[test]
     fn test_u8_bcd() {
         assert_eq!(u8_bcd(0), [0, 0, 0]);
@@ -1043,12 +3890,12 @@ mod test {
             0xF0, 0x33, // Store V0 as BCD to index register
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
 
         emulator.registers[0] = 255;
         emulator.index_register = 0x300;
 
-        emulator.run_instruction();
+        emulator.tick();
 
         assert_eq!(&emulator.ram[0x300..0x303], &[2, 5, 5]);
     }
@@ -1056,17 +3903,17 @@ mod test {
     #[test]
     fn test_fx55() {
         let program = vec![
-            0xFA, 0x55 // memcpy V0-V1 -> ram[index_register]
+            0xFA, 0x55 // memcpy V0-VA -> ram[index_register]
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
 
         for i in 0..=0xA {
             emulator.registers[i] = i as u8 + 0x60;
         }
 
         emulator.index_register = 0x300;
-        emulator.run_instruction();
+        emulator.tick();
 
         for i in 0..=0xA {
             assert_eq!(emulator.ram[0x300 + i], i as u8 + 0x60)
@@ -1075,6 +3922,9 @@ mod test {
         // Check some surrounding values to make sure they haven't changed
         assert_eq!(emulator.ram[0x2FF], 0);
         assert_eq!(emulator.ram[0x30B], 0);
+
+        // Index register should not change, matching FX65
+        assert_eq!(emulator.index_register, 0x300);
     }
 
     #[test]
@@ -1084,9 +3934,9 @@ mod test {
             0x50, 0x51, 0x52, 0x53, 0x54, 0x55,
         ];
 
-        let mut emulator = Chip8Emulator::new(program, 10);
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
         emulator.index_register = 0x202;
-        emulator.run_instruction();
+        emulator.tick();
 
         for i in 0..=5 {
             assert_eq!(emulator.registers[i] as usize, 0x50 + i);
@@ -1099,4 +3949,391 @@ mod test {
         // Index register should not change. There is some conflicting info on this online
         assert_eq!(emulator.index_register, 0x202);
     }
+
+    #[test]
+    fn test_fx75_fx85() {
+        let program = vec![
+            0xF3, 0x75, // Save V0 - V3 to the RPL flags
+            0xF2, 0x85, // Load V0 - V2 from the RPL flags
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[0..5].copy_from_slice(&[0x11, 0x22, 0x33, 0x44, 0x55]);
+        emulator.tick();
+
+        assert_eq!(emulator.rpl_flags, [0x11, 0x22, 0x33, 0x44, 0, 0, 0, 0]);
+
+        emulator.registers = [0xFF; 16];
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0..4], [0x11, 0x22, 0x33, 0xFF]);
+
+        // The flags survive a reset
+        emulator.reset();
+        assert_eq!(emulator.rpl_flags, [0x11, 0x22, 0x33, 0x44, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn test_fx75_fx85_limited_to_v7() {
+        let program = vec![
+            0xFF, 0x75, // Save V0 - VF to the RPL flags, only V0 - V7 fit
+            0xFF, 0x85, // Load V0 - VF from the RPL flags, only V0 - V7 fit
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers = std::array::from_fn(|i| i as u8 + 1);
+        emulator.tick();
+
+        assert_eq!(emulator.rpl_flags, [1, 2, 3, 4, 5, 6, 7, 8]);
+
+        emulator.registers = [0; 16];
+        emulator.tick();
+
+        assert_eq!(emulator.registers[0..8], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(emulator.registers[8..], [0; 8]);
+    }
+
+    #[test]
+    fn test_load_store_increments_i() {
+        let program = vec![
+            0xF3, 0x55, // memcpy V0-V3 -> ram[index_register]
+            0xF1, 0x65, // memcpy ram[index_register] -> V0-V1
+        ];
+
+        let unchanged_quirks = Quirks { load_store_increments_i: false, ..Default::default() };
+        let increments_quirks = Quirks { load_store_increments_i: true, ..Default::default() };
+
+        let mut unchanged = Chip8Emulator::new(
+            program.clone(),
+            Chip8Config { quirks: unchanged_quirks, ..Default::default() },
+        );
+        let mut increments = Chip8Emulator::new(
+            program,
+            Chip8Config { quirks: increments_quirks, ..Default::default() },
+        );
+
+        for emulator in [&mut unchanged, &mut increments] {
+            emulator.index_register = 0x300;
+        }
+
+        unchanged.tick();
+        increments.tick();
+
+        assert_eq!(unchanged.index_register, 0x300);
+        assert_eq!(increments.index_register, 0x304);
+
+        unchanged.tick();
+        increments.tick();
+
+        assert_eq!(unchanged.index_register, 0x300);
+        assert_eq!(increments.index_register, 0x306);
+    }
+
+    #[test]
+    fn test_call_stack() {
+        let program = vec![
+            0x22, 0x04, // Call 0x204
+            0x00, 0x00,
+            0x22, 0x08, // Call 0x208
+            0x00, 0x00,
+            0x00, 0xEE, // Return
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        assert!(emulator.call_stack().is_empty());
+
+        emulator.tick();
+        emulator.tick();
+        assert_eq!(emulator.call_stack(), [0x202, 0x206]);
+
+        emulator.tick();
+        assert_eq!(emulator.call_stack(), [0x202]);
+    }
+
+    fn memory_fill_emulator(fill: MemoryFill, seed: Option<u64>) -> Chip8Emulator {
+        Chip8EmulatorBuilder::new().rom(vec![0x60, 0x12]).memory_fill(fill).seed(seed).build().unwrap()
+    }
+
+    #[test]
+    fn test_memory_fill_pattern() {
+        let emulator = memory_fill_emulator(MemoryFill::Pattern(0xAA), None);
+
+        assert!(emulator.ram[0..0x50].iter().all(|byte| *byte == 0xAA));
+        assert_eq!(emulator.ram[0x50..0xA0], FONTS);
+        assert!(emulator.ram[0xA0..0x200].iter().all(|byte| *byte == 0xAA));
+        assert_eq!(emulator.ram[0x200..0x202], [0x60, 0x12]);
+        assert!(emulator.ram[0x202..].iter().all(|byte| *byte == 0xAA));
+    }
+
+    #[test]
+    fn test_memory_fill_random() {
+        let mut first = memory_fill_emulator(MemoryFill::Random, Some(7));
+        let second = memory_fill_emulator(MemoryFill::Random, Some(7));
+        let other_seed = memory_fill_emulator(MemoryFill::Random, Some(8));
+
+        assert_eq!(first.ram, second.ram);
+        assert_ne!(first.ram, other_seed.ram);
+        assert_eq!(first.ram[0x50..0xA0], FONTS);
+        assert_eq!(first.ram[0x200..0x202], [0x60, 0x12]);
+
+        // Resetting fills memory the same way again
+        let before = first.ram.clone();
+        first.ram[0x300] ^= 0xFF;
+        first.reset();
+        assert_eq!(first.ram, before);
+    }
+
+    #[test]
+    fn test_parse_memory_fill() {
+        assert_eq!("zero".parse(), Ok(MemoryFill::Zero));
+        assert_eq!("random".parse(), Ok(MemoryFill::Random));
+        assert_eq!("0xAA".parse(), Ok(MemoryFill::Pattern(0xAA)));
+        assert_eq!("ff".parse(), Ok(MemoryFill::Pattern(0xFF)));
+        assert!("0x100".parse::<MemoryFill>().is_err());
+        assert!("ones".parse::<MemoryFill>().is_err());
+    }
+
+    fn xo_chip_emulator(program: Vec<u8>) -> Chip8Emulator {
+        Chip8EmulatorBuilder::new().rom(program).ipf(10).memory_size(XO_CHIP_RAM_SIZE).build().unwrap()
+    }
+
+    #[test]
+    fn test_f000_long_index() {
+        let program = vec![
+            0xF0, 0x00, 0xAB, 0xCD, // I = 0xABCD
+            0xF2, 0x65,             // Load V0 - V2 from I
+            0xF0, 0x00, 0xFF, 0xFE, // I = 0xFFFE
+            0xF1, 0x55,             // Store V0 - V1 at I
+        ];
+
+        let mut emulator = xo_chip_emulator(program);
+        emulator.ram[0xABCD..0xABD0].copy_from_slice(&[0x11, 0x22, 0x33]);
+
+        emulator.tick();
+        assert_eq!(emulator.index_register, 0xABCD);
+        assert_eq!(emulator.program_counter, 0x204);
+
+        emulator.tick();
+        assert_eq!(emulator.registers[0..3], [0x11, 0x22, 0x33]);
+
+        emulator.tick();
+        emulator.tick();
+        assert_eq!(emulator.ram[0xFFFE..], [0x11, 0x22]);
+    }
+
+    #[test]
+    fn test_high_address_draw_and_bcd() {
+        let program = vec![
+            0xF0, 0x00, 0xFF, 0xFF, // I = 0xFFFF
+            0x60, 0xEA,             // V0 = 234
+            0xF0, 0x33,             // BCD of V0 at I, wrapping past the end of memory
+            0xF0, 0x00, 0x80, 0x00, // I = 0x8000
+            0x61, 0x00,             // V1 = 0
+            0xD1, 0x11,             // Draw 1 row at (0, 0)
+        ];
+
+        let mut emulator = xo_chip_emulator(program);
+        emulator.ram[0x8000] = 0b1000_0001;
+
+        for _ in 0..3 {
+            emulator.tick();
+        }
+        assert_eq!(emulator.ram[0xFFFF], 2);
+        assert_eq!(emulator.ram[0..2], [3, 4]);
+
+        for _ in 0..3 {
+            emulator.tick();
+        }
+        assert_pixel(&emulator, 0, true);
+        assert_pixel(&emulator, 7, true);
+        assert_pixel(&emulator, 1, false);
+    }
+
+    #[test]
+    fn test_skip_over_f000() {
+        let program = vec![
+            0x30, 0x00,             // Skip if V0 == 0
+            0xF0, 0x00, 0x12, 0x34, // I = 0x1234, skipped as a whole
+            0x61, 0x01,             // V1 = 1
+        ];
+
+        let mut emulator = xo_chip_emulator(program);
+        emulator.tick();
+        assert_eq!(emulator.program_counter, 0x206);
+
+        emulator.tick();
+        assert_eq!(emulator.registers[1], 1);
+        assert_eq!(emulator.index_register, 0);
+    }
+
+    /// Draws an 8x2 sprite at (0, 0) with the given planes selected. The sprite data is two
+    /// sprites back to back, the first with the left half lit and the second the right half.
+    fn draw_on_planes(planes: u8) -> Chip8Emulator {
+        let program = vec![
+            0xF0 | planes, 0x01, // Select planes
+            0xA2, 0x0A,          // I = 0x20A
+            0x60, 0x00,          // V0 = 0
+            0xD0, 0x02,          // Draw 2 rows at (0, 0)
+            0x12, 0x08,          // Jump to 0x208, i.e. infinite loop
+            0xF0, 0xF0,          // Plane 1 sprite
+            0x0F, 0x0F,          // Plane 2 sprite
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        for _ in 0..4 {
+            emulator.tick();
+        }
+        emulator
+    }
+
+    #[test]
+    fn test_fn01_plane_1() {
+        let emulator = draw_on_planes(1);
+
+        // Only the plane 1 sprite is read, so it looks just like a normal draw
+        for row in 0..2 {
+            assert_eq!(emulator.plane_buffer[row * WIDTH..][..8], [1, 1, 1, 1, 0, 0, 0, 0]);
+        }
+        assert_pixel(&emulator, 0, true);
+        assert_pixel(&emulator, 4, false);
+    }
+
+    #[test]
+    fn test_fn01_plane_2() {
+        let emulator = draw_on_planes(2);
+
+        // Plane 2 on its own still reads the first sprite
+        assert_eq!(emulator.plane_buffer[0..8], [2, 2, 2, 2, 0, 0, 0, 0]);
+        assert_eq!(emulator.display_buffer[0], DEFAULT_PLANE_2_COLOR);
+        assert_eq!(emulator.display_buffer[4], DEFAULT_BACKGROUND_COLOR);
+        assert!(emulator.is_pixel_on(0, 0));
+    }
+
+    #[test]
+    fn test_fn01_both_planes() {
+        let mut emulator = draw_on_planes(3);
+
+        // Plane 1 gets the first sprite and plane 2 the one after it
+        assert_eq!(emulator.plane_buffer[0..8], [1, 1, 1, 1, 2, 2, 2, 2]);
+        assert_eq!(emulator.display_buffer[4], DEFAULT_PLANE_2_COLOR);
+        assert_eq!(emulator.registers[0xF], 0);
+
+        // Drawing the plane 2 sprite onto plane 1 lights the right half on both planes
+        emulator.plane_mask = 1;
+        emulator.index_register = 0x20C;
+        emulator.program_counter = 0x206;
+        emulator.tick();
+
+        assert_eq!(emulator.plane_buffer[0..8], [1, 1, 1, 1, 3, 3, 3, 3]);
+        assert_eq!(emulator.display_buffer[4], DEFAULT_BOTH_PLANES_COLOR);
+        assert_eq!(emulator.registers[0xF], 0);
+
+        // Then drawing it onto plane 2 again collides, leaving just plane 1
+        emulator.plane_mask = 2;
+        emulator.program_counter = 0x206;
+        emulator.tick();
+
+        assert_eq!(emulator.plane_buffer[0..8], [1; 8]);
+        assert_eq!(emulator.registers[0xF], 1);
+    }
+
+    #[test]
+    fn test_clear_and_scroll_selected_planes() {
+        let program = vec![
+            0xF2, 0x01, // Select plane 2
+            0x00, 0xC1, // Scroll down 1
+            0x00, 0xE0, // Clear
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.set_pixel_planes(0, 3);
+
+        emulator.tick();
+        emulator.tick();
+        assert_eq!(emulator.plane_buffer[0], 1);
+        assert_eq!(emulator.plane_buffer[WIDTH], 2);
+
+        emulator.tick();
+        assert_eq!(emulator.plane_buffer[0], 1);
+        assert_eq!(emulator.plane_buffer[WIDTH], 0);
+        assert_pixel(&emulator, 0, true);
+    }
+
+    /// Operands that cluster around where the arithmetic wraps, mixed with anything else
+    fn alu_operand() -> impl Strategy<Value = u8> {
+        prop_oneof![
+            prop::sample::select(vec![0, 1, 0x7F, 0x80, 0xFE, 0xFF]),
+            any::<u8>(),
+        ]
+    }
+
+    fn alu_operands() -> impl Strategy<Value = (u8, u8)> {
+        (alu_operand(), alu_operand())
+    }
+
+    /// Two different registers, either of which can be VF
+    fn alu_registers() -> impl Strategy<Value = (u8, u8)> {
+        (0..=0xFu8, 0..=0xFu8).prop_filter("registers must differ", |(x, y)| x != y)
+    }
+
+    /// The (VX, VF) an 8XY_ instruction should leave after writing `result` to VX and then `flag`
+    /// to VF. When X is F, the flag overwrites the result.
+    fn alu_expected(x: u8, result: u8, flag: u8) -> (u8, u8) {
+        if x == 0xF { (flag, flag) } else { (result, flag) }
+    }
+
+    /// Runs a single 8XY_ instruction with VX and VY preloaded, returning (VX, VF) afterwards
+    fn run_alu(operation: u8, (x, y): (u8, u8), x_value: u8, y_value: u8) -> (u8, u8) {
+        let program = vec![0x80 | x, (y << 4) | operation];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.registers[x as usize] = x_value;
+        emulator.registers[y as usize] = y_value;
+        emulator.tick();
+
+        (emulator.registers[x as usize], emulator.registers[0xF])
+    }
+
+    proptest! {
+        #[test]
+        fn test_7xnn_reference(x in 0..=0xFu8, (x_value, nn) in alu_operands(), vf in any::<u8>()) {
+            let mut emulator = Chip8Emulator::new(vec![0x70 | x, nn], Chip8Config::default());
+            emulator.registers[0xF] = vf;
+            emulator.registers[x as usize] = x_value;
+            emulator.tick();
+
+            let sum = x_value as u16 + nn as u16;
+            prop_assert_eq!(emulator.registers[x as usize], (sum % 256) as u8);
+            // Unlike 8XY4, there's no carry flag, so VF is only changed by adding to it directly
+            if x != 0xF {
+                prop_assert_eq!(emulator.registers[0xF], vf);
+            }
+        }
+
+        #[test]
+        fn test_8xy4_reference(registers in alu_registers(), (x_value, y_value) in alu_operands()) {
+            let sum = x_value as u16 + y_value as u16;
+            let expected = alu_expected(registers.0, (sum % 256) as u8, (sum > 0xFF) as u8);
+
+            prop_assert_eq!(run_alu(0x4, registers, x_value, y_value), expected);
+        }
+
+        #[test]
+        fn test_8xy5_reference(registers in alu_registers(), (x_value, y_value) in alu_operands()) {
+            let difference = x_value as i16 - y_value as i16;
+            let no_borrow = (difference >= 0) as u8;
+            let expected = alu_expected(registers.0, difference.rem_euclid(256) as u8, no_borrow);
+
+            prop_assert_eq!(run_alu(0x5, registers, x_value, y_value), expected);
+        }
+
+        #[test]
+        fn test_8xy7_reference(registers in alu_registers(), (x_value, y_value) in alu_operands()) {
+            let difference = y_value as i16 - x_value as i16;
+            let no_borrow = (difference >= 0) as u8;
+            let expected = alu_expected(registers.0, difference.rem_euclid(256) as u8, no_borrow);
+
+            prop_assert_eq!(run_alu(0x7, registers, x_value, y_value), expected);
+        }
+    }
 }
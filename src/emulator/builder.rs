@@ -0,0 +1,305 @@
+use super::{
+    Chip8Config, Chip8Emulator, MemoryFill, Resolution, UnknownOpcodePolicy, FONT_SIZES, FONT_START_ADDRESS,
+    HEIGHT, MEMORY_SIZES, RPL_FLAG_COUNT, WIDTH,
+};
+use crate::error::Chip8Error;
+use crate::opcode::OPCODE_KINDS;
+use crate::quirks::Quirks;
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+use std::collections::HashSet;
+
+/// Configures a [`Chip8Emulator`] one setting at a time. Anything left unset gets the same
+/// default as [`Chip8Config::default`], and an empty ROM.
+#[derive(Debug, Clone, Default)]
+pub struct Chip8EmulatorBuilder {
+    rom: Vec<u8>,
+    config: Chip8Config,
+}
+
+impl Chip8EmulatorBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn rom(mut self, rom: Vec<u8>) -> Self {
+        self.rom = rom;
+        self
+    }
+
+    /// Replaces every setting at once
+    pub fn config(mut self, config: Chip8Config) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Number of instructions run per 60Hz frame, i.e. the CPU speed
+    pub fn ipf(mut self, instructions_per_frame: u16) -> Self {
+        self.config.instructions_per_frame = instructions_per_frame;
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Self {
+        self.config.quirks = quirks;
+        self
+    }
+
+    /// Seed for the random number generator used by CXNN. `None` seeds from entropy.
+    pub fn seed(mut self, seed: Option<u64>) -> Self {
+        self.config.seed = seed;
+        self
+    }
+
+    /// Replaces the built in font. It must be one of the [`FONT_SIZES`].
+    pub fn font(mut self, font: &[u8]) -> Self {
+        self.config.font = font.to_vec();
+        self
+    }
+
+    /// Colors used for on and off pixels
+    pub fn colors(mut self, foreground_color: u32, background_color: u32) -> Self {
+        self.config.foreground_color = foreground_color;
+        self.config.background_color = background_color;
+        self
+    }
+
+    /// Colors for XO-CHIP pixels lit on only the second plane, and on both planes. Pixels lit on
+    /// only the first plane use the foreground color.
+    pub fn plane_colors(mut self, plane_2_color: u32, both_planes_color: u32) -> Self {
+        self.config.plane_2_color = plane_2_color;
+        self.config.both_planes_color = both_planes_color;
+        self
+    }
+
+    /// What to do when the program runs an opcode that isn't recognized
+    pub fn unknown_opcode_policy(mut self, policy: UnknownOpcodePolicy) -> Self {
+        self.config.unknown_opcode_policy = policy;
+        self
+    }
+
+    /// Size of RAM in bytes, one of the [`MEMORY_SIZES`]. XO-CHIP ROMs need the full 64KB.
+    pub fn memory_size(mut self, memory_size: usize) -> Self {
+        self.config.memory_size = memory_size;
+        self
+    }
+
+    /// Where the ROM is loaded and execution starts. Most ROMs expect the default of
+    /// [`PROGRAM_START_ADDRESS`](super::PROGRAM_START_ADDRESS), but some were written for the ETI-660 and load at
+    /// [`ETI_660_START_ADDRESS`](super::ETI_660_START_ADDRESS). It can't be inside the font.
+    pub fn program_start(mut self, address: u16) -> Self {
+        self.config.program_start = address;
+        self
+    }
+
+    /// What RAM is filled with outside the font and ROM
+    pub fn memory_fill(mut self, memory_fill: MemoryFill) -> Self {
+        self.config.memory_fill = memory_fill;
+        self
+    }
+
+    /// Reports the program as finished once it has jumped to itself for this many frames in a
+    /// row, stopping it from spinning through a full frame of instructions each time. See
+    /// [`FrameStatus::IdleLoop`](super::FrameStatus::IdleLoop). Off by default.
+    pub fn idle_loop_frames(mut self, frames: Option<u32>) -> Self {
+        self.config.idle_loop_frames = frames;
+        self
+    }
+
+    /// Checks the configuration and creates the emulator, ready to run the ROM from the start
+    pub fn build(self) -> Result<Chip8Emulator, Chip8Error> {
+        let Chip8EmulatorBuilder { rom, config } = self;
+
+        if !MEMORY_SIZES.contains(&config.memory_size) {
+            return Err(Chip8Error::InvalidMemorySize(config.memory_size));
+        }
+
+        // A start address past the end of memory leaves no room for any ROM at all
+        let program_max_size = config.memory_size.saturating_sub(config.program_start as usize);
+        if rom.len() > program_max_size {
+            return Err(Chip8Error::RomTooLarge { size: rom.len(), max: program_max_size });
+        }
+
+        if !FONT_SIZES.contains(&config.font.len()) {
+            return Err(Chip8Error::InvalidFontSize(config.font.len()));
+        }
+
+        // Loading the ROM would overwrite the end of the font
+        let font_end = FONT_START_ADDRESS + config.font.len() as u16;
+        if config.program_start < font_end {
+            return Err(Chip8Error::ProgramOverlapsFont { program_start: config.program_start, font_end });
+        }
+
+        if config.foreground_color == config.background_color {
+            return Err(Chip8Error::IdenticalColors);
+        }
+
+        let mut emulator = Chip8Emulator {
+            registers: Default::default(),
+            ram: vec![0; config.memory_size],
+            index_register: 0,
+            program_counter: 0,
+            stack: Default::default(),
+            stack_pointer: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            rpl_flags: [0; RPL_FLAG_COUNT],
+            previous_keyboard_state: [false; 16],
+            keyboard_state: [false; 16],
+            display_buffer: vec![config.background_color; WIDTH * HEIGHT],
+            plane_buffer: vec![0; WIDTH * HEIGHT],
+            plane_mask: 1,
+            resolution: Resolution::Low,
+            display_dirty: true,
+            foreground_color: config.foreground_color,
+            background_color: config.background_color,
+            plane_2_color: config.plane_2_color,
+            both_planes_color: config.both_planes_color,
+            rom,
+            program_start: config.program_start,
+            font: config.font,
+            instructions_per_frame: config.instructions_per_frame,
+            rng: ChaCha12Rng::from_os_rng(),
+            seed: config.seed,
+            quirks: config.quirks,
+            breakpoints: HashSet::new(),
+            watchpoints: Vec::new(),
+            break_conditions: Vec::new(),
+            break_conditions_met: false,
+            watchpoint_hit: None,
+            instruction_counts: [0; OPCODE_KINDS],
+            instructions_run: 0,
+            stopped_at_breakpoint: false,
+            unknown_opcode_policy: config.unknown_opcode_policy,
+            memory_fill: config.memory_fill,
+            halted: false,
+            idle_loop_frames: config.idle_loop_frames,
+            idle_frames: 0,
+            frame_cycle: 0,
+            pending_time: 0,
+            waiting_for_vblank: false,
+            pre_hook: None,
+            post_hook: None,
+        };
+
+        // Loads the font and ROM into RAM and seeds the random number generator
+        emulator.reset();
+        Ok(emulator)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::{
+        DEFAULT_FOREGROUND_COLOR, DEFAULT_INSTRUCTIONS_PER_FRAME, ETI_660_START_ADDRESS, FONTS, FONT_START_ADDRESS,
+        PROGRAM_MAX_SIZE, PROGRAM_START_ADDRESS, RAM_SIZE, XO_CHIP_RAM_SIZE,
+    };
+
+    #[test]
+    fn test_build() {
+        let quirks = Quirks { shift_uses_vy: true, ..Quirks::default() };
+        let font = [0xAB; 80];
+
+        let emulator = Chip8EmulatorBuilder::new()
+            .rom(vec![0x60, 0x12])
+            .ipf(20)
+            .quirks(quirks)
+            .seed(Some(7))
+            .font(&font)
+            .colors(0xFF33FF66, 0xFF101010)
+            .build()
+            .unwrap();
+
+        assert_eq!(emulator.program_counter, PROGRAM_START_ADDRESS);
+        assert_eq!(emulator.ram[PROGRAM_START_ADDRESS as usize..][..2], [0x60, 0x12]);
+        assert_eq!(emulator.ram[FONT_START_ADDRESS as usize..][..80], font);
+        assert_eq!(emulator.instructions_per_frame, 20);
+        assert_eq!(emulator.quirks, quirks);
+        assert_eq!(emulator.seed, Some(7));
+        assert!(emulator.display_buffer.iter().all(|pixel| *pixel == 0xFF101010));
+    }
+
+    #[test]
+    fn test_build_defaults() {
+        let emulator = Chip8EmulatorBuilder::new().build().unwrap();
+
+        assert_eq!(emulator.instructions_per_frame, DEFAULT_INSTRUCTIONS_PER_FRAME);
+        assert_eq!(emulator.quirks, Quirks::default());
+        assert_eq!(emulator.ram[FONT_START_ADDRESS as usize..][..80], FONTS);
+        assert_eq!(emulator.foreground_color, DEFAULT_FOREGROUND_COLOR);
+    }
+
+    #[test]
+    fn test_build_rom_too_large() {
+        let result = Chip8EmulatorBuilder::new().rom(vec![0; PROGRAM_MAX_SIZE + 1]).build();
+        assert!(matches!(
+            result.err(),
+            Some(Chip8Error::RomTooLarge { size, max: PROGRAM_MAX_SIZE }) if size == PROGRAM_MAX_SIZE + 1
+        ));
+
+        // A ROM filling all of memory is fine
+        assert!(Chip8EmulatorBuilder::new().rom(vec![0; PROGRAM_MAX_SIZE]).build().is_ok());
+    }
+
+    #[test]
+    fn test_build_xo_chip_memory() {
+        let rom = vec![0; PROGRAM_MAX_SIZE + 1];
+        let emulator = Chip8EmulatorBuilder::new().rom(rom).memory_size(XO_CHIP_RAM_SIZE).build().unwrap();
+
+        assert_eq!(emulator.memory_size(), XO_CHIP_RAM_SIZE);
+        assert_eq!(emulator.program_max_size(), XO_CHIP_RAM_SIZE - 0x200);
+    }
+
+    #[test]
+    fn test_build_program_start() {
+        let program = vec![
+            0x60, 0x12, // V0 = 0x12
+            0x16, 0x00, // Jump to 0x600
+        ];
+
+        let mut emulator =
+            Chip8EmulatorBuilder::new().rom(program).program_start(ETI_660_START_ADDRESS).build().unwrap();
+
+        assert_eq!(emulator.program_counter, 0x600);
+        assert_eq!(emulator.ram[0x600..0x602], [0x60, 0x12]);
+        assert!(emulator.ram[0x200..0x600].iter().all(|byte| *byte == 0));
+
+        assert_eq!(emulator.fetch(), 0x6012);
+        emulator.tick();
+        assert_eq!(emulator.registers[0], 0x12);
+
+        // Resetting starts from the same place
+        emulator.tick();
+        emulator.reset();
+        assert_eq!(emulator.program_counter, 0x600);
+
+        // The ROM has less room than usual
+        let max = RAM_SIZE - 0x600;
+        assert_eq!(emulator.program_max_size(), max);
+        let result = Chip8EmulatorBuilder::new().rom(vec![0; max + 1]).program_start(0x600).build();
+        assert!(matches!(result.err(), Some(Chip8Error::RomTooLarge { max: 0xA00, .. })));
+    }
+
+    #[test]
+    fn test_build_invalid() {
+        let result = Chip8EmulatorBuilder::new().font(&[0; 100]).build();
+        assert!(matches!(result.err(), Some(Chip8Error::InvalidFontSize(100))));
+
+        let result = Chip8EmulatorBuilder::new().colors(0xFF000000, 0xFF000000).build();
+        assert!(matches!(result.err(), Some(Chip8Error::IdenticalColors)));
+
+        let result = Chip8EmulatorBuilder::new().memory_size(8192).build();
+        assert!(matches!(result.err(), Some(Chip8Error::InvalidMemorySize(8192))));
+
+        // The small font ends at 0xA0, and a big font at 0x140
+        let result = Chip8EmulatorBuilder::new().program_start(0x9F).build();
+        assert!(matches!(
+            result.err(),
+            Some(Chip8Error::ProgramOverlapsFont { program_start: 0x9F, font_end: 0xA0 })
+        ));
+        assert!(Chip8EmulatorBuilder::new().program_start(0xA0).build().is_ok());
+
+        let result = Chip8EmulatorBuilder::new().font(&[0; 240]).program_start(0xA0).build();
+        assert!(matches!(result.err(), Some(Chip8Error::ProgramOverlapsFont { font_end: 0x140, .. })));
+    }
+}
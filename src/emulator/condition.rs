@@ -0,0 +1,134 @@
+use super::Chip8Emulator;
+use std::str::FromStr;
+
+/// A register a [`BreakCondition`] can look at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakRegister {
+    /// One of the general purpose registers, V0 - VF
+    V(u8),
+
+    /// The index register
+    I,
+}
+
+/// How a [`BreakCondition`] compares the register to its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl CmpOp {
+    /// Longest first, so `<=` isn't parsed as `<` when both match at the same place
+    const SYMBOLS: [(&'static str, CmpOp); 7] = [
+        ("==", CmpOp::Eq),
+        ("!=", CmpOp::Ne),
+        ("<=", CmpOp::Le),
+        (">=", CmpOp::Ge),
+        ("=", CmpOp::Eq),
+        ("<", CmpOp::Lt),
+        (">", CmpOp::Gt),
+    ];
+
+    fn compare(self, left: u16, right: u16) -> bool {
+        match self {
+            CmpOp::Eq => left == right,
+            CmpOp::Ne => left != right,
+            CmpOp::Lt => left < right,
+            CmpOp::Le => left <= right,
+            CmpOp::Gt => left > right,
+            CmpOp::Ge => left >= right,
+        }
+    }
+}
+
+/// Compares a register against a constant, e.g. `V5 == 0xFF` or `I > 0xF00`. See
+/// [`Chip8Emulator::add_break_condition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakCondition {
+    pub register: BreakRegister,
+    pub op: CmpOp,
+
+    /// Wide enough for the index register, V registers only ever match values up to 0xFF
+    pub value: u16,
+}
+
+impl BreakCondition {
+    pub fn is_met(&self, emulator: &Chip8Emulator) -> bool {
+        let current = match self.register {
+            BreakRegister::V(x) => emulator.registers[x as usize & 0xF] as u16,
+            BreakRegister::I => emulator.index_register,
+        };
+
+        self.op.compare(current, self.value)
+    }
+}
+
+impl FromStr for BreakCondition {
+    type Err = String;
+
+    /// Parses a register, comparison and value with no spaces, e.g. `V5=255`, `VA!=0x10` or
+    /// `I>0xF00`. Values are decimal unless prefixed with `0x`.
+    fn from_str(condition: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid condition '{condition}', expected e.g. V5=255 or I>0xF00");
+
+        let (index, symbol, op) = CmpOp::SYMBOLS
+            .iter()
+            .filter_map(|&(symbol, op)| condition.find(symbol).map(|index| (index, symbol, op)))
+            .min_by_key(|&(index, _, _)| index)
+            .ok_or_else(invalid)?;
+        let (register, value) = (&condition[..index], &condition[index + symbol.len()..]);
+
+        let register = match register.to_ascii_uppercase().as_str() {
+            "I" => BreakRegister::I,
+            name => {
+                let x = name.strip_prefix('V').filter(|x| x.len() == 1).ok_or_else(invalid)?;
+                BreakRegister::V(u8::from_str_radix(x, 16).map_err(|_| invalid())?)
+            }
+        };
+
+        let value = match value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+            Some(hex) => u16::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .map_err(|_| invalid())?;
+
+        Ok(BreakCondition { register, op, value })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_break_condition() {
+        let parse = |condition: &str| condition.parse::<BreakCondition>();
+
+        assert_eq!(
+            parse("V5=255"),
+            Ok(BreakCondition { register: BreakRegister::V(5), op: CmpOp::Eq, value: 255 })
+        );
+        assert_eq!(
+            parse("va!=0x10"),
+            Ok(BreakCondition { register: BreakRegister::V(0xA), op: CmpOp::Ne, value: 0x10 })
+        );
+        assert_eq!(
+            parse("I>0xF00"),
+            Ok(BreakCondition { register: BreakRegister::I, op: CmpOp::Gt, value: 0xF00 })
+        );
+        assert_eq!(parse("V0<=3").map(|condition| condition.op), Ok(CmpOp::Le));
+        assert_eq!(parse("V0==3").map(|condition| condition.op), Ok(CmpOp::Eq));
+
+        assert!(parse("V5").is_err());
+        assert!(parse("VG=1").is_err());
+        assert!(parse("V10=1").is_err());
+        assert!(parse("X=1").is_err());
+        assert!(parse("V5=").is_err());
+        assert!(parse("V5=0x").is_err());
+    }
+}
@@ -0,0 +1,64 @@
+use super::{
+    MemoryFill, UnknownOpcodePolicy, DEFAULT_BACKGROUND_COLOR, DEFAULT_BOTH_PLANES_COLOR, DEFAULT_FOREGROUND_COLOR,
+    DEFAULT_INSTRUCTIONS_PER_FRAME, DEFAULT_PLANE_2_COLOR, FONTS, PROGRAM_START_ADDRESS, RAM_SIZE,
+};
+use crate::quirks::Quirks;
+
+/// Everything about a [`Chip8Emulator`](super::Chip8Emulator) apart from the ROM, passed to
+/// [`Chip8Emulator::new`](super::Chip8Emulator::new). Start from [`Chip8Config::default`] and
+/// override the fields that matter, e.g.
+/// `Chip8Config { quirks: Quirks::super_chip(), ..Default::default() }`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chip8Config {
+    /// Number of instructions run per 60Hz frame, i.e. the CPU speed
+    pub instructions_per_frame: u16,
+    pub quirks: Quirks,
+
+    /// Seed for the random number generator used by CXNN. `None` seeds from entropy.
+    pub seed: Option<u64>,
+
+    /// Must be one of the [`FONT_SIZES`](super::FONT_SIZES)
+    pub font: Vec<u8>,
+
+    /// Colors used for on and off pixels, which must be different
+    pub foreground_color: u32,
+    pub background_color: u32,
+
+    /// Colors for XO-CHIP pixels lit on only the second plane, and on both planes
+    pub plane_2_color: u32,
+    pub both_planes_color: u32,
+
+    /// Where the ROM is loaded and execution starts, which must be past the end of the font
+    pub program_start: u16,
+
+    /// Size of RAM in bytes, one of the [`MEMORY_SIZES`](super::MEMORY_SIZES)
+    pub memory_size: usize,
+
+    /// What RAM is filled with outside the font and ROM
+    pub memory_fill: MemoryFill,
+    pub unknown_opcode_policy: UnknownOpcodePolicy,
+
+    /// How many frames a jump to itself runs before it's reported as an idle loop, `None` to never
+    /// check
+    pub idle_loop_frames: Option<u32>,
+}
+
+impl Default for Chip8Config {
+    fn default() -> Self {
+        Self {
+            instructions_per_frame: DEFAULT_INSTRUCTIONS_PER_FRAME,
+            quirks: Quirks::default(),
+            seed: None,
+            font: FONTS.to_vec(),
+            foreground_color: DEFAULT_FOREGROUND_COLOR,
+            background_color: DEFAULT_BACKGROUND_COLOR,
+            plane_2_color: DEFAULT_PLANE_2_COLOR,
+            both_planes_color: DEFAULT_BOTH_PLANES_COLOR,
+            program_start: PROGRAM_START_ADDRESS,
+            memory_size: RAM_SIZE,
+            memory_fill: MemoryFill::default(),
+            unknown_opcode_policy: UnknownOpcodePolicy::default(),
+            idle_loop_frames: None,
+        }
+    }
+}
@@ -0,0 +1,360 @@
+use super::{Chip8Emulator, Resolution, HIRES_HEIGHT, HIRES_WIDTH, RPL_FLAG_COUNT};
+use crate::error::Chip8Error;
+use crate::replay::{keys_to_mask, mask_to_keys};
+use rand::SeedableRng;
+use rand_chacha::ChaCha12Rng;
+
+/// Identifies a blob as an iron-chip save state
+const STATE_MAGIC: [u8; 4] = *b"ICHP";
+
+/// Bump this whenever the layout below changes, so stale save states are detected
+const STATE_VERSION: u8 = 5;
+
+const HEADER_SIZE: usize = STATE_MAGIC.len() + 1;
+
+/// The random number generator's seed, stream and word position, which together pick up the
+/// sequence exactly where it left off
+const RNG_STATE_SIZE: usize = 32 + 8 + 16;
+
+/// Where the stack pointer is stored, after the header and: registers, RAM, I, PC, stack
+const fn stack_pointer_offset(memory_size: usize) -> usize {
+    HEADER_SIZE + 16 + memory_size + 2 + 2 + 16 * 2
+}
+
+/// Where the resolution is stored, after the stack pointer and: delay timer, sound timer, RPL
+/// flags, plane mask
+const fn resolution_offset(memory_size: usize) -> usize {
+    stack_pointer_offset(memory_size) + 1 + 1 + 1 + RPL_FLAG_COUNT + 1
+}
+
+/// Size of a save state, header included. After the resolution come the previous frame's keys,
+/// so FX0A sees the same key releases, and the random number generator state. The plane buffer
+/// is last, one byte per pixel, and is always stored at the high resolution size so the state
+/// size only depends on the memory size. Colors aren't part of the state, the display is
+/// recolored on restore.
+const fn state_size(memory_size: usize) -> usize {
+    resolution_offset(memory_size) + 1 + 2 + RNG_STATE_SIZE + HIRES_WIDTH * HIRES_HEIGHT
+}
+
+/// Reads fields out of a save state in the order they were written
+struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> StateReader<'a> {
+    fn take(&mut self, len: usize) -> &'a [u8] {
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        bytes
+    }
+
+    fn bytes<const N: usize>(&mut self) -> [u8; N] {
+        self.take(N).try_into().unwrap()
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes::<1>()[0]
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_be_bytes(self.bytes())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_be_bytes(self.bytes())
+    }
+
+    fn u128(&mut self) -> u128 {
+        u128::from_be_bytes(self.bytes())
+    }
+}
+
+impl Chip8Emulator {
+    /// Serializes the full machine state into a versioned blob
+    pub fn snapshot(&self) -> Vec<u8> {
+        let state_size = state_size(self.ram.len());
+        let mut data = Vec::with_capacity(state_size);
+
+        data.extend_from_slice(&STATE_MAGIC);
+        data.push(STATE_VERSION);
+
+        data.extend_from_slice(&self.registers);
+        data.extend_from_slice(&self.ram);
+        data.extend_from_slice(&self.index_register.to_be_bytes());
+        data.extend_from_slice(&self.program_counter.to_be_bytes());
+        for address in self.stack {
+            data.extend_from_slice(&address.to_be_bytes());
+        }
+        data.push(self.stack_pointer);
+        data.push(self.delay_timer);
+        data.push(self.sound_timer);
+        data.extend_from_slice(&self.rpl_flags);
+        data.push(self.plane_mask);
+        data.push(match self.resolution {
+            Resolution::Low => 0,
+            Resolution::High => 1,
+        });
+        data.extend_from_slice(&keys_to_mask(&self.previous_keyboard_state).to_be_bytes());
+        data.extend_from_slice(&self.rng.get_seed());
+        data.extend_from_slice(&self.rng.get_stream().to_be_bytes());
+        data.extend_from_slice(&self.rng.get_word_pos().to_be_bytes());
+        data.extend_from_slice(&self.plane_buffer);
+        data.resize(state_size, 0);
+
+        debug_assert_eq!(data.len(), state_size);
+        data
+    }
+
+    /// Restores the machine state from a blob produced by [`Chip8Emulator::snapshot`]. The
+    /// emulator is left untouched if the blob is invalid, including when it came from an emulator
+    /// with a different memory size.
+    pub fn restore(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        if data.len() < HEADER_SIZE || data[0..STATE_MAGIC.len()] != STATE_MAGIC {
+            return Err(Chip8Error::NotAState);
+        }
+
+        let version = data[STATE_MAGIC.len()];
+        if version != STATE_VERSION {
+            return Err(Chip8Error::StateVersionMismatch { found: version, expected: STATE_VERSION });
+        }
+
+        let expected = state_size(self.ram.len());
+        if data.len() != expected {
+            return Err(Chip8Error::StateWrongSize { found: data.len(), expected });
+        }
+
+        let resolution = match data[resolution_offset(self.ram.len())] {
+            0 => Resolution::Low,
+            1 => Resolution::High,
+            invalid => return Err(Chip8Error::InvalidResolution(invalid)),
+        };
+
+        // Checked up front like the resolution, since a stack pointer past the end of the stack
+        // would panic on the next 00EE
+        let stack_pointer = data[stack_pointer_offset(self.ram.len())];
+        if stack_pointer as usize > self.stack.len() {
+            return Err(Chip8Error::InvalidStackPointer(stack_pointer));
+        }
+
+        let mut reader = StateReader { data: &data[HEADER_SIZE..] };
+
+        self.registers = reader.bytes();
+        let ram = reader.take(self.ram.len());
+        self.ram.copy_from_slice(ram);
+        self.index_register = reader.u16();
+        self.program_counter = reader.u16();
+        for address in self.stack.iter_mut() {
+            *address = reader.u16();
+        }
+        self.stack_pointer = reader.u8();
+        self.delay_timer = reader.u8();
+        self.sound_timer = reader.u8();
+        self.rpl_flags = reader.bytes();
+        self.plane_mask = reader.u8();
+        reader.u8();
+        self.previous_keyboard_state = mask_to_keys(reader.u16());
+        self.rng = ChaCha12Rng::from_seed(reader.bytes());
+        self.rng.set_stream(reader.u64());
+        self.rng.set_word_pos(reader.u128());
+        self.set_resolution(resolution);
+        let planes = reader.take(self.plane_buffer.len());
+        self.plane_buffer.copy_from_slice(planes);
+        self.composite_display();
+        self.break_conditions_met = self.break_conditions_hold();
+        self.idle_frames = 0;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::{Chip8Config, Chip8EmulatorBuilder, RAM_SIZE, XO_CHIP_RAM_SIZE};
+
+    const STATE_SIZE: usize = state_size(RAM_SIZE);
+    const RESOLUTION_OFFSET: usize = resolution_offset(RAM_SIZE);
+    const STACK_POINTER_OFFSET: usize = stack_pointer_offset(RAM_SIZE);
+
+    #[test]
+    fn test_snapshot() {
+        let mut emulator = Chip8Emulator::new(vec![0x60, 0x12], Chip8Config::default());
+        emulator.registers[3] = 0x45;
+        emulator.index_register = 0x321;
+
+        let snapshot = emulator.snapshot();
+
+        assert_eq!(snapshot.len(), STATE_SIZE);
+        assert_eq!(snapshot[0..4], STATE_MAGIC);
+        assert_eq!(snapshot[4], STATE_VERSION);
+        assert_eq!(snapshot[HEADER_SIZE + 3], 0x45);
+
+        // Mutating the emulator afterwards shouldn't affect the captured state
+        emulator.tick();
+        emulator.registers[3] = 0;
+
+        let program_counter_offset = HEADER_SIZE + 16 + RAM_SIZE + 2;
+        assert_eq!(snapshot[HEADER_SIZE + 3], 0x45);
+        assert_eq!(snapshot[program_counter_offset..program_counter_offset + 2], [0x02, 0x00]);
+        assert_ne!(emulator.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn test_restore() {
+        let program = vec![
+            0x60, 0x12, // V0 = 0x12
+            0x22, 0x08, // Call 0x208
+            0x00, 0x00,
+            0x00, 0x00,
+            0xA3, 0x45, // I = 0x345
+        ];
+
+        let mut emulator = Chip8Emulator::new(program.clone(), Chip8Config::default());
+        for _ in 0..3 {
+            emulator.tick();
+        }
+        emulator.set_pixel_planes(5, 1);
+        emulator.plane_mask = 2;
+        emulator.rpl_flags[2] = 0x67;
+
+        let snapshot = emulator.snapshot();
+
+        let mut restored = Chip8Emulator::new(vec![], Chip8Config::default());
+        restored.restore(&snapshot).unwrap();
+
+        assert_eq!(restored.program_counter, 0x20A);
+        assert_eq!(restored.index_register, 0x345);
+        assert_eq!(restored.registers, emulator.registers);
+        assert_eq!(restored.stack_pointer, 1);
+        assert_eq!(restored.stack[0], 0x204);
+        assert_eq!(restored.ram[0x200..0x20A], program[..]);
+        assert_eq!(restored.display_buffer, emulator.display_buffer);
+        assert_eq!(restored.plane_mask, 2);
+        assert_eq!(restored.rpl_flags, emulator.rpl_flags);
+    }
+
+    #[test]
+    fn test_restore_rng_and_keys() {
+        let program = vec![
+            0xC0, 0xFF, // V0 = random
+            0x12, 0x00, // Jump to 0x200
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.previous_keyboard_state[0xA] = true;
+        for _ in 0..5 {
+            emulator.tick();
+        }
+
+        let snapshot = emulator.snapshot();
+        let random_numbers: Vec<u8> = (0..10)
+            .map(|_| {
+                emulator.tick();
+                emulator.tick();
+                emulator.registers[0]
+            })
+            .collect();
+
+        // Restoring carries on with the same random numbers, even in a differently seeded emulator
+        let mut restored = Chip8Emulator::new(vec![], Chip8Config { seed: Some(1), ..Default::default() });
+        restored.restore(&snapshot).unwrap();
+        let restored_numbers: Vec<u8> = (0..10)
+            .map(|_| {
+                restored.tick();
+                restored.tick();
+                restored.registers[0]
+            })
+            .collect();
+
+        assert_eq!(restored_numbers, random_numbers);
+        assert_eq!(restored.previous_keyboard_state, mask_to_keys(1 << 0xA));
+    }
+
+    #[test]
+    fn test_restore_hires() {
+        let program = vec![
+            0x00, 0xFF, // Switch to high resolution
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.tick();
+        emulator.set_pixel_planes(HIRES_WIDTH * HIRES_HEIGHT - 1, 3);
+
+        let snapshot = emulator.snapshot();
+        assert_eq!(snapshot.len(), STATE_SIZE);
+
+        let mut restored = Chip8Emulator::new(vec![], Chip8Config::default());
+        restored.restore(&snapshot).unwrap();
+
+        assert_eq!(restored.resolution, Resolution::High);
+        assert_eq!(restored.display_buffer, emulator.display_buffer);
+
+        // Restoring a low resolution state switches back
+        restored.reset();
+        emulator.restore(&restored.snapshot()).unwrap();
+
+        assert_eq!(emulator.resolution, Resolution::Low);
+        assert_eq!(emulator.display_buffer.len(), 64 * 32);
+    }
+
+    #[test]
+    fn test_restore_invalid() {
+        let mut emulator = Chip8Emulator::new(vec![0x60, 0x12], Chip8Config::default());
+        let snapshot = emulator.snapshot();
+
+        assert!(matches!(emulator.restore(&[]), Err(Chip8Error::NotAState)));
+        assert!(matches!(emulator.restore(b"not a save state"), Err(Chip8Error::NotAState)));
+
+        let mut old_version = snapshot.clone();
+        old_version[4] = STATE_VERSION - 1;
+        assert!(matches!(
+            emulator.restore(&old_version),
+            Err(Chip8Error::StateVersionMismatch { found, expected: STATE_VERSION }) if found == STATE_VERSION - 1
+        ));
+
+        assert!(matches!(
+            emulator.restore(&snapshot[..100]),
+            Err(Chip8Error::StateWrongSize { found: 100, expected: STATE_SIZE })
+        ));
+
+        let mut bad_resolution = snapshot.clone();
+        bad_resolution[RESOLUTION_OFFSET] = 2;
+        assert!(matches!(emulator.restore(&bad_resolution), Err(Chip8Error::InvalidResolution(2))));
+
+        let mut bad_stack_pointer = snapshot.clone();
+        bad_stack_pointer[STACK_POINTER_OFFSET] = 200;
+        assert!(matches!(emulator.restore(&bad_stack_pointer), Err(Chip8Error::InvalidStackPointer(200))));
+
+        // A full stack is fine, one past it isn't
+        let mut full_stack = snapshot.clone();
+        full_stack[STACK_POINTER_OFFSET] = 16;
+        let mut restored = Chip8Emulator::new(vec![], Chip8Config::default());
+        restored.restore(&full_stack).unwrap();
+        assert_eq!(restored.stack_pointer, 16);
+
+        full_stack[STACK_POINTER_OFFSET] = 17;
+        assert!(matches!(emulator.restore(&full_stack), Err(Chip8Error::InvalidStackPointer(17))));
+
+        // The emulator is untouched by the failed restores
+        assert_eq!(emulator.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn test_restore_xo_chip_memory() {
+        let build = || Chip8EmulatorBuilder::new().memory_size(XO_CHIP_RAM_SIZE).build().unwrap();
+
+        let mut emulator = build();
+        emulator.ram[0xFFFF] = 0x42;
+
+        let snapshot = emulator.snapshot();
+        assert_eq!(snapshot.len(), state_size(XO_CHIP_RAM_SIZE));
+
+        let mut restored = build();
+        restored.restore(&snapshot).unwrap();
+        assert_eq!(restored.ram[0xFFFF], 0x42);
+
+        // A 64KB state doesn't fit in a 4KB emulator
+        let mut small = Chip8Emulator::new(vec![], Chip8Config::default());
+        assert!(matches!(small.restore(&snapshot), Err(Chip8Error::StateWrongSize { .. })));
+    }
+}
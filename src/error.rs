@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::{fmt, io};
+
+/// Everything that can go wrong when creating, running or restoring an emulator
+#[derive(Debug)]
+pub enum Chip8Error {
+    /// The ROM doesn't fit in RAM after the program start address
+    RomTooLarge { size: usize, max: usize },
+
+    /// A custom font isn't one of the [`FONT_SIZES`](crate::emulator::FONT_SIZES)
+    InvalidFontSize(usize),
+
+    /// Memory isn't one of the [`MEMORY_SIZES`](crate::emulator::MEMORY_SIZES)
+    InvalidMemorySize(usize),
+
+    /// On and off pixels would be indistinguishable
+    IdenticalColors,
+
+    /// The program would be loaded over the end of the font
+    ProgramOverlapsFont { program_start: u16, font_end: u16 },
+
+    /// 2NNN at `address` called a subroutine with all 16 stack slots in use
+    StackOverflow { address: u16 },
+
+    /// 00EE at `address` returned with nothing on the stack
+    StackUnderflow { address: u16 },
+
+    /// The instruction at `address` isn't a valid or supported opcode
+    InvalidOpcode { address: u16, opcode: u16 },
+
+    /// The program counter ran past the last full instruction in RAM
+    ProgramCounterOutOfBounds { address: u16 },
+
+    /// A read or write from outside the emulator was past the end of RAM
+    AddressOutOfBounds { address: u16, memory_size: usize },
+
+    /// A save state blob doesn't start with the save state magic bytes
+    NotAState,
+
+    /// A save state was written by an incompatible version of the emulator
+    StateVersionMismatch { found: u8, expected: u8 },
+
+    /// A save state has a recognized version but the wrong length
+    StateWrongSize { found: usize, expected: usize },
+
+    /// A save state has a resolution byte that isn't low or high resolution
+    InvalidResolution(u8),
+
+    /// A save state has a stack pointer past the end of the stack
+    InvalidStackPointer(u8),
+
+    Io(io::Error),
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM is {size} bytes, but at most {max} bytes fit in memory")
+            }
+            Chip8Error::InvalidFontSize(size) => {
+                write!(f, "font is {size} bytes, expected 80 or 240 bytes")
+            }
+            Chip8Error::InvalidMemorySize(size) => {
+                write!(f, "memory size is {size} bytes, expected 4096 or 65536 bytes")
+            }
+            Chip8Error::IdenticalColors => {
+                write!(f, "foreground and background colors must be different")
+            }
+            Chip8Error::ProgramOverlapsFont { program_start, font_end } => {
+                write!(f, "program start {program_start:#05X} overlaps the font ending at {font_end:#05X}")
+            }
+            Chip8Error::StackOverflow { address } => {
+                write!(f, "stack overflow calling a subroutine at {address:#05X}")
+            }
+            Chip8Error::StackUnderflow { address } => {
+                write!(f, "stack underflow returning from a subroutine at {address:#05X}")
+            }
+            Chip8Error::InvalidOpcode { address, opcode } => {
+                write!(f, "invalid opcode {opcode:04X} at {address:#05X}")
+            }
+            Chip8Error::ProgramCounterOutOfBounds { address } => {
+                write!(f, "program counter {address:#05X} ran off the end of memory")
+            }
+            Chip8Error::AddressOutOfBounds { address, memory_size } => {
+                write!(f, "address {address:#05X} is outside the {memory_size} bytes of memory")
+            }
+            Chip8Error::NotAState => write!(f, "data is not a save state"),
+            Chip8Error::StateVersionMismatch { found, expected } => {
+                write!(f, "save state version {found} is incompatible, expected version {expected}")
+            }
+            Chip8Error::StateWrongSize { found, expected } => {
+                write!(f, "save state is {found} bytes, expected {expected} bytes")
+            }
+            Chip8Error::InvalidResolution(resolution) => {
+                write!(f, "save state has an invalid resolution {resolution}")
+            }
+            Chip8Error::InvalidStackPointer(stack_pointer) => {
+                write!(f, "save state has a stack pointer {stack_pointer} past the end of the stack")
+            }
+            Chip8Error::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Chip8Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(err: io::Error) -> Self {
+        Chip8Error::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let err = Chip8Error::RomTooLarge { size: 4000, max: 3584 };
+        assert_eq!(err.to_string(), "ROM is 4000 bytes, but at most 3584 bytes fit in memory");
+
+        let err = Chip8Error::InvalidOpcode { address: 0x2AE, opcode: 0x5001 };
+        assert_eq!(err.to_string(), "invalid opcode 5001 at 0x2AE");
+
+        let err = Chip8Error::StateVersionMismatch { found: 2, expected: 3 };
+        assert_eq!(err.to_string(), "save state version 2 is incompatible, expected version 3");
+
+        let err = Chip8Error::from(io::Error::new(io::ErrorKind::NotFound, "missing"));
+        assert_eq!(err.to_string(), "missing");
+        assert!(err.source().is_some());
+    }
+}
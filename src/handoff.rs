@@ -0,0 +1,97 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// A display buffer published by the emulator thread, ready to draw
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub buffer: Vec<u32>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Passes frames from the thread running the emulator to the thread drawing them. Only the newest
+/// frame is kept, so the emulator never waits on a slow renderer, the renderer just skips frames.
+#[derive(Debug, Default)]
+pub struct FrameHandoff {
+    latest: Mutex<Option<Frame>>,
+    published: Condvar,
+
+    /// Kept apart from the frames, since the sound timer changes without the display changing
+    beeping: AtomicBool,
+}
+
+impl FrameHandoff {
+    /// Replaces any frame that hasn't been taken yet
+    pub fn publish(&self, frame: Frame) {
+        *self.latest.lock().unwrap() = Some(frame);
+        self.published.notify_one();
+    }
+
+    /// Takes the newest frame, waiting up to `timeout` for one to be published. `None` if nothing
+    /// new was published in time.
+    pub fn take(&self, timeout: Duration) -> Option<Frame> {
+        let latest = self.latest.lock().unwrap();
+        let (mut latest, _) = self.published.wait_timeout_while(latest, timeout, |latest| latest.is_none()).unwrap();
+        latest.take()
+    }
+
+    pub fn set_beeping(&self, beeping: bool) {
+        self.beeping.store(beeping, Ordering::Relaxed);
+    }
+
+    pub fn is_beeping(&self) -> bool {
+        self.beeping.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Instant;
+
+    fn frame(pixel: u32) -> Frame {
+        Frame { buffer: vec![pixel; 4], width: 2, height: 2 }
+    }
+
+    #[test]
+    fn test_frame_handoff() {
+        let handoff = FrameHandoff::default();
+        assert_eq!(handoff.take(Duration::ZERO), None);
+
+        // Frames published before the renderer gets to them are skipped, not queued
+        handoff.publish(frame(1));
+        handoff.publish(frame(2));
+        assert_eq!(handoff.take(Duration::ZERO), Some(frame(2)));
+        assert_eq!(handoff.take(Duration::ZERO), None);
+
+        // Nothing is published, so the wait runs out
+        let start = Instant::now();
+        assert_eq!(handoff.take(Duration::from_millis(20)), None);
+        assert!(start.elapsed() >= Duration::from_millis(20));
+
+        handoff.set_beeping(true);
+        assert!(handoff.is_beeping());
+    }
+
+    #[test]
+    fn test_frame_handoff_across_threads() {
+        let handoff = Arc::new(FrameHandoff::default());
+
+        let publisher = {
+            let handoff = handoff.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                handoff.publish(frame(3));
+            })
+        };
+
+        // Woken by the publish rather than waiting out the whole timeout
+        let start = Instant::now();
+        assert_eq!(handoff.take(Duration::from_secs(10)), Some(frame(3)));
+        assert!(start.elapsed() < Duration::from_secs(10));
+        publisher.join().unwrap();
+    }
+}
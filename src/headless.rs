@@ -0,0 +1,259 @@
+use crate::emulator::{Chip8Emulator, FrameStatus, KeyboardState};
+use crate::shutdown::Shutdown;
+
+/// Runs the emulator for a number of frames with no window, feeding in one keyboard state per frame
+/// from `inputs`. Once the inputs run out no keys are pressed. Stops early if a breakpoint is hit
+/// or an unknown opcode halts the emulator.
+pub fn run_headless(emulator: &mut Chip8Emulator, frames: u32, inputs: &[KeyboardState]) -> FrameStatus {
+    run_headless_until(emulator, frames, inputs.iter().copied(), &Shutdown::default())
+}
+
+/// Like [`run_headless`], but also stops early, after a whole frame, once `shutdown` is requested.
+/// Inputs can come from any iterator, e.g. [`InputLog::frames`](crate::replay::InputLog::frames).
+pub fn run_headless_until(
+    emulator: &mut Chip8Emulator,
+    frames: u32,
+    inputs: impl IntoIterator<Item = KeyboardState>,
+    shutdown: &Shutdown,
+) -> FrameStatus {
+    let mut inputs = inputs.into_iter();
+    for frame in 0..frames {
+        if shutdown.is_requested() {
+            info!("Shutting down headless run after {frame} frames");
+            break;
+        }
+
+        let keys = inputs.next().unwrap_or_default();
+        let status = emulator.run_60hz_frame(keys);
+        if status != FrameStatus::Completed {
+            info!("Stopped headless run after {frame} frames");
+            return status;
+        }
+    }
+
+    FrameStatus::Completed
+}
+
+/// Runs whole frames until at least `instructions` more instructions have run, as fast as the host
+/// allows. The timers still tick once per frame's worth of instructions, so the program sees the
+/// same amount of time pass as it would running in real time. Inputs are fed in per frame like
+/// [`run_headless`].
+pub fn run_for_instructions(emulator: &mut Chip8Emulator, instructions: u64, inputs: &[KeyboardState]) -> FrameStatus {
+    let target = emulator.instructions_run() + instructions;
+
+    for frame in 0.. {
+        if emulator.instructions_run() >= target {
+            break;
+        }
+
+        let keys = inputs.get(frame).copied().unwrap_or_default();
+        let status = emulator.run_60hz_frame(keys);
+        if status != FrameStatus::Completed {
+            info!("Stopped after {frame} frames, {} instructions short", target - emulator.instructions_run());
+            return status;
+        }
+    }
+
+    FrameStatus::Completed
+}
+
+/// Renders the display as text, one character per pixel with `#` for lit pixels and `.` for
+/// unlit ones, and every row ending in a newline. The same display always gives the same text, so
+/// it can be diffed.
+pub fn display_to_ascii(emulator: &Chip8Emulator) -> String {
+    let resolution = emulator.resolution();
+
+    (0..resolution.height())
+        .flat_map(|y| {
+            (0..resolution.width())
+                .map(move |x| if emulator.is_pixel_on(x, y) { '#' } else { '.' })
+                .chain(['\n'])
+        })
+        .collect()
+}
+
+/// A 64-bit FNV-1a hash of the display buffer, for checking a run against a known good display.
+/// Unlike `std`'s hashers this never changes between Rust versions, so hashes can be committed.
+pub fn display_hash(emulator: &Chip8Emulator) -> u64 {
+    const OFFSET_BASIS: u64 = 0xCBF29CE484222325;
+    const PRIME: u64 = 0x100000001B3;
+
+    emulator
+        .display_buffer()
+        .iter()
+        .flat_map(|pixel| pixel.to_le_bytes())
+        .fold(OFFSET_BASIS, |hash, byte| (hash ^ byte as u64).wrapping_mul(PRIME))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::{Chip8Config, HEIGHT, HIRES_HEIGHT, HIRES_WIDTH, WIDTH};
+
+    #[test]
+    fn test_run_headless() {
+        let program = vec![
+            0x60, 0x02, // V0 = 2
+            0xF0, 0x29, // I = sprite for V0
+            0xD1, 0x15, // Draw the 2 at (0, 0)
+            0x12, 0x06, // Jump to 0x206, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        assert_eq!(run_headless(&mut emulator, 3, &[]), FrameStatus::Completed);
+
+        let ascii = display_to_ascii(&emulator);
+        let lines: Vec<&str> = ascii.lines().collect();
+
+        assert_eq!(lines.len(), HEIGHT);
+        assert!(lines.iter().all(|line| line.len() == WIDTH));
+
+        assert_eq!(&lines[0][0..5], "####.");
+        assert_eq!(&lines[1][0..5], "...#.");
+        assert_eq!(&lines[2][0..5], "####.");
+        assert_eq!(&lines[3][0..5], "#....");
+        assert_eq!(&lines[4][0..5], "####.");
+        assert!(lines[5..].iter().all(|line| !line.contains('#')));
+    }
+
+    #[test]
+    fn test_run_headless_until_shutdown() {
+        let program = vec![
+            0x70, 0x01, // V0 += 1
+            0x12, 0x00, // Jump to 0x200
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        let shutdown = Shutdown::default();
+        assert_eq!(run_headless_until(&mut emulator, 3, [], &shutdown), FrameStatus::Completed);
+        let instructions_run = emulator.instructions_run();
+        assert!(instructions_run > 0);
+
+        shutdown.request();
+        assert_eq!(run_headless_until(&mut emulator, 3, [], &shutdown), FrameStatus::Completed);
+        assert_eq!(emulator.instructions_run(), instructions_run);
+    }
+
+    #[test]
+    fn test_display_to_ascii() {
+        let program = vec![
+            0x60, 0x01, // V0 = 1
+            0xA2, 0x08, // I = 0x208
+            0xD0, 0x01, // Draw 1 row at (1, 1)
+            0x12, 0x06, // Jump to 0x206, i.e. infinite loop
+            0x80,       // Sprite with only the leftmost pixel set
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        run_headless(&mut emulator, 1, &[]);
+
+        let blank_row = format!("{}\n", ".".repeat(WIDTH));
+        let lit_row = format!(".#{}\n", ".".repeat(WIDTH - 2));
+        let expected = blank_row.clone() + &lit_row + &blank_row.repeat(HEIGHT - 2);
+        assert_eq!(display_to_ascii(&emulator), expected);
+    }
+
+    #[test]
+    fn test_display_to_ascii_hires() {
+        let program = vec![
+            0x00, 0xFF, // Switch to high resolution
+            0x60, 0x7F, // V0 = 127
+            0x61, 0x3F, // V1 = 63
+            0xA2, 0x0A, // I = 0x20A
+            0xD0, 0x11, // Draw 1 row at (127, 63)
+            0x80,       // Sprite with only the leftmost pixel set
+        ];
+
+        let mut emulator = Chip8Emulator::new(
+            program,
+            Chip8Config { instructions_per_frame: 5, ..Default::default() },
+        );
+        run_headless(&mut emulator, 1, &[]);
+
+        let ascii = display_to_ascii(&emulator);
+        let lines: Vec<&str> = ascii.lines().collect();
+
+        assert_eq!(lines.len(), HIRES_HEIGHT);
+        assert!(lines.iter().all(|line| line.len() == HIRES_WIDTH));
+        assert!(lines[HIRES_HEIGHT - 1].ends_with(".#"));
+        assert_eq!(ascii.matches('#').count(), 1);
+    }
+
+    #[test]
+    fn test_run_headless_inputs() {
+        let program = vec![
+            0xF0, 0x0A, // Wait for a key, storing it in V0
+            0x12, 0x02, // Jump to 0x202, i.e. infinite loop
+        ];
+
+        let mut held = [false; 16];
+        held[0xB] = true;
+        let inputs = [[false; 16], held, held, [false; 16]];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        run_headless(&mut emulator, 3, &inputs);
+
+        // Still waiting, since the key hasn't been released yet
+        assert_eq!(emulator.program_counter(), 0x200);
+
+        run_headless(&mut emulator, 1, &inputs[3..]);
+
+        assert_eq!(emulator.program_counter(), 0x202);
+        assert_eq!(emulator.registers()[0], 0xB);
+    }
+
+    #[test]
+    fn test_run_for_instructions() {
+        let program = vec![
+            0x60, 0x0A, // V0 = 10
+            0xF0, 0x15, // Delay timer = V0
+            0x71, 0x01, // V1 += 1
+            0xF2, 0x07, // V2 = delay timer
+            0x12, 0x04, // Jump to 0x204
+        ];
+
+        let config = Chip8Config { instructions_per_frame: 10, ..Default::default() };
+        let mut emulator = Chip8Emulator::new(program, config);
+        assert_eq!(run_for_instructions(&mut emulator, 100, &[]), FrameStatus::Completed);
+
+        // 10 frames ran, and the timer ticked on the 9 after it was set
+        assert_eq!(emulator.instructions_run(), 100);
+        assert_eq!(emulator.registers()[1], 33);
+        assert_eq!(emulator.registers()[2], 1);
+        assert_eq!(emulator.delay_timer(), 1);
+    }
+
+    #[test]
+    fn test_run_headless_breakpoint() {
+        let program = vec![
+            0x12, 0x00, // Jump to 0x200, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        emulator.add_breakpoint(0x200);
+
+        assert_eq!(run_headless(&mut emulator, 3, &[]), FrameStatus::HitBreakpoint(0x200));
+    }
+
+    #[test]
+    fn test_display_hash() {
+        let program = vec![
+            0x60, 0x02, // V0 = 2
+            0xF0, 0x29, // I = sprite for V0
+            0xD1, 0x15, // Draw the 2 at (0, 0)
+            0x12, 0x06, // Jump to 0x206, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        let blank = display_hash(&emulator);
+
+        run_headless(&mut emulator, 1, &[]);
+        let drawn = display_hash(&emulator);
+        assert_ne!(drawn, blank);
+
+        // Running the same program again gives the same hash
+        emulator.reset();
+        run_headless(&mut emulator, 1, &[]);
+        assert_eq!(display_hash(&emulator), drawn);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::emulator::KeyboardState;
+
+#[cfg(feature = "gamepad")]
+use gilrs::{Button, EventType, Gilrs};
+
+/// Which keypad key each gamepad button presses. The D-pad matches WASD on the keyboard, i.e.
+/// 5 / 7 / 8 / 9, which most games use for movement, and the face buttons cover the keys
+/// around them.
+#[cfg(feature = "gamepad")]
+pub const DEFAULT_GAMEPAD_MAPPING: [(Button, u8); 10] = [
+    (Button::DPadUp, 0x5),
+    (Button::DPadLeft, 0x7),
+    (Button::DPadDown, 0x8),
+    (Button::DPadRight, 0x9),
+    (Button::South, 0x6),
+    (Button::East, 0x4),
+    (Button::West, 0xA),
+    (Button::North, 0x0),
+    (Button::Start, 0x1),
+    (Button::Select, 0x2),
+];
+
+/// Combines the keys held on two devices, a key counts as held if it's held on either
+pub fn merge_keypads(first: KeyboardState, second: KeyboardState) -> KeyboardState {
+    std::array::from_fn(|key| first[key] || second[key])
+}
+
+/// Merges gamepad input into the keyboard state. Without the `gamepad` feature, or when gamepads
+/// aren't available, only the keyboard is used.
+pub struct Chip8Input {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<Gilrs>,
+}
+
+impl Chip8Input {
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "gamepad")]
+            gilrs: Self::open_gamepads(),
+        }
+    }
+
+    #[cfg(feature = "gamepad")]
+    fn open_gamepads() -> Option<Gilrs> {
+        match Gilrs::new() {
+            Ok(gilrs) => {
+                for (_, gamepad) in gilrs.gamepads() {
+                    info!("Using gamepad {}", gamepad.name());
+                }
+                Some(gilrs)
+            }
+            Err(err) => {
+                warn!("Couldn't open gamepads, only using the keyboard: {err}");
+                None
+            }
+        }
+    }
+
+    /// The keypad state from the keyboard plus any connected gamepads. Should be called every
+    /// frame, since this is also where gamepads being plugged in and out is picked up.
+    pub fn keypad(&mut self, keyboard: KeyboardState) -> KeyboardState {
+        #[cfg(feature = "gamepad")]
+        if let Some(gilrs) = &mut self.gilrs {
+            // Button state is tracked by gilrs itself, the events just need draining
+            while let Some(event) = gilrs.next_event() {
+                match event.event {
+                    EventType::Connected => {
+                        info!("Gamepad {} connected", gilrs.gamepad(event.id).name());
+                    }
+                    EventType::Disconnected => {
+                        info!("Gamepad {} disconnected", gilrs.gamepad(event.id).name());
+                    }
+                    _ => {}
+                }
+            }
+
+            return gilrs.gamepads().fold(keyboard, |keypad, (_, gamepad)| {
+                merge_keypads(keypad, gamepad_keypad(|button| gamepad.is_pressed(button)))
+            });
+        }
+
+        keyboard
+    }
+}
+
+impl Default for Chip8Input {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps the buttons held on a gamepad onto the keypad using [`DEFAULT_GAMEPAD_MAPPING`]
+#[cfg(feature = "gamepad")]
+fn gamepad_keypad(is_pressed: impl Fn(Button) -> bool) -> KeyboardState {
+    let mut keypad = [false; 16];
+    for (button, key) in DEFAULT_GAMEPAD_MAPPING {
+        keypad[key as usize] |= is_pressed(button);
+    }
+    keypad
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge_keypads() {
+        let mut keyboard = [false; 16];
+        keyboard[0x1] = true;
+        keyboard[0x5] = true;
+
+        let mut gamepad = [false; 16];
+        gamepad[0x5] = true;
+        gamepad[0xF] = true;
+
+        let merged = merge_keypads(keyboard, gamepad);
+
+        let held: Vec<usize> = (0..16).filter(|&key| merged[key]).collect();
+        assert_eq!(held, vec![0x1, 0x5, 0xF]);
+        assert_eq!(merge_keypads([false; 16], [false; 16]), [false; 16]);
+    }
+
+    #[cfg(feature = "gamepad")]
+    #[test]
+    fn test_gamepad_keypad() {
+        let keypad = gamepad_keypad(|button| matches!(button, Button::DPadUp | Button::South));
+
+        let held: Vec<usize> = (0..16).filter(|&key| keypad[key]).collect();
+        assert_eq!(held, vec![0x5, 0x6]);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::emulator::KeyboardState;
+use minifb::Key;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+/// Which keypad key, 0x0 - 0xF, each keyboard key presses
+pub type Keymap = HashMap<Key, u8>;
+
+/// Keys that can be named in a keymap file, by their minifb name
+const NAMED_KEYS: [Key; 86] = [
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4,
+    Key::Key5, Key::Key6, Key::Key7, Key::Key8, Key::Key9,
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R,
+    Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z,
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8,
+    Key::F9, Key::F10, Key::F11, Key::F12,
+    Key::Down, Key::Left, Key::Right, Key::Up,
+    Key::Apostrophe, Key::Backquote, Key::Backslash, Key::Comma, Key::Equal,
+    Key::LeftBracket, Key::Minus, Key::Period, Key::RightBracket, Key::Semicolon, Key::Slash,
+    Key::Space, Key::Enter, Key::LeftShift, Key::RightShift, Key::LeftCtrl, Key::RightCtrl,
+    Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3, Key::NumPad4,
+    Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9,
+    Key::NumPadDot, Key::NumPadSlash, Key::NumPadAsterisk, Key::NumPadMinus, Key::NumPadPlus,
+    Key::NumPadEnter, Key::Delete,
+];
+
+/// A keymap file that couldn't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeymapError {
+    /// 1-based line number of the malformed mapping
+    pub line: usize,
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "malformed keymap on line {}", self.line)
+    }
+}
+
+impl Error for KeymapError {}
+
+/// The conventional layout, mapping the 4x4 block of keys from 1 to V onto the COSMAC VIP keypad:
+///
+/// ```text
+/// 1 2 3 4      1 2 3 C
+/// Q W E R  ->  4 5 6 D
+/// A S D F      7 8 9 E
+/// Z X C V      A 0 B F
+/// ```
+pub fn default_keymap() -> Keymap {
+    HashMap::from([
+        (Key::Key1, 0x1),
+        (Key::Key2, 0x2),
+        (Key::Key3, 0x3),
+        (Key::Key4, 0xC),
+
+        (Key::Q, 0x4),
+        (Key::W, 0x5),
+        (Key::E, 0x6),
+        (Key::R, 0xD),
+
+        (Key::A, 0x7),
+        (Key::S, 0x8),
+        (Key::D, 0x9),
+        (Key::F, 0xE),
+
+        (Key::Z, 0xA),
+        (Key::X, 0x0),
+        (Key::C, 0xB),
+        (Key::V, 0xF),
+    ])
+}
+
+/// Looks up a key by its minifb name, e.g. `Key1`, `A` or `NumPad5`, ignoring case
+fn parse_key(name: &str) -> Option<Key> {
+    NAMED_KEYS.into_iter().find(|key| format!("{key:?}").eq_ignore_ascii_case(name))
+}
+
+/// Parses a keymap file, one `<keyboard key>=<keypad key>` mapping per line, e.g. `A=0x7`.
+/// Keyboard keys use their minifb names and keypad keys are hex digits, with or without a leading
+/// 0x. Blank lines and lines starting with `#` are ignored.
+///
+/// The file replaces the default keymap entirely, so every key that should do something needs
+/// listing.
+pub fn parse_keymap(text: &str) -> Result<Keymap, KeymapError> {
+    let mut keymap = HashMap::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let error = KeymapError { line: index + 1 };
+
+        let (key, keypad_key) = line.split_once('=').ok_or(error)?;
+        let key = parse_key(key.trim()).ok_or(error)?;
+
+        let keypad_key = keypad_key.trim();
+        let hex = keypad_key
+            .strip_prefix("0x")
+            .or_else(|| keypad_key.strip_prefix("0X"))
+            .unwrap_or(keypad_key);
+        let keypad_key = u8::from_str_radix(hex, 16).ok().filter(|key| *key <= 0xF).ok_or(error)?;
+
+        keymap.insert(key, keypad_key);
+    }
+
+    Ok(keymap)
+}
+
+/// Which keypad keys are held, given the keyboard keys that are down
+pub fn keypad_state(keymap: &Keymap, keys_down: &[Key]) -> KeyboardState {
+    let mut keypad = [false; 16];
+    for key in keys_down {
+        if let Some(&keypad_key) = keymap.get(key) {
+            keypad[keypad_key as usize] = true;
+        }
+    }
+    keypad
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_keymap() {
+        let keypad = keypad_state(&default_keymap(), &[Key::Key4, Key::X, Key::P]);
+
+        let held: Vec<usize> = (0..16).filter(|&key| keypad[key]).collect();
+        assert_eq!(held, vec![0x0, 0xC]);
+    }
+
+    #[test]
+    fn test_parse_keymap() {
+        let keymap = parse_keymap("# AZERTY movement\nZ=0x5\n q = 7\n\nNumPad5=c\n").unwrap();
+
+        assert_eq!(keymap.len(), 3);
+
+        let keypad = keypad_state(&keymap, &[Key::Z, Key::Q, Key::NumPad5, Key::W]);
+        let held: Vec<usize> = (0..16).filter(|&key| keypad[key]).collect();
+        assert_eq!(held, vec![0x5, 0x7, 0xC]);
+    }
+
+    #[test]
+    fn test_parse_keymap_invalid() {
+        assert_eq!(parse_keymap("A=1\nB"), Err(KeymapError { line: 2 }));
+        assert_eq!(parse_keymap("Hyper=1"), Err(KeymapError { line: 1 }));
+        assert_eq!(parse_keymap("A=0x10"), Err(KeymapError { line: 1 }));
+        assert_eq!(parse_keymap("A=G"), Err(KeymapError { line: 1 }));
+    }
+}
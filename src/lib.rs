@@ -0,0 +1,41 @@
+//! CHIP-8 emulator core, plus the minifb frontend pieces used by the `iron-chip` binary behind the
+//! default `frontend` feature
+
+#[macro_use]
+extern crate log;
+
+#[cfg(feature = "frontend")]
+pub mod audio;
+pub mod bench;
+pub mod disasm;
+pub mod emulator;
+pub mod error;
+#[cfg(feature = "frontend")]
+pub mod handoff;
+pub mod headless;
+#[cfg(feature = "frontend")]
+pub mod input;
+#[cfg(feature = "frontend")]
+pub mod keymap;
+pub mod opcode;
+pub mod overlay;
+pub mod quirks;
+#[cfg(feature = "frontend")]
+pub mod renderer;
+pub mod replay;
+pub mod rewind;
+pub mod screenshot;
+pub mod shutdown;
+#[cfg(feature = "frontend")]
+pub mod terminal;
+pub mod trace;
+#[cfg(feature = "frontend")]
+pub mod window;
+
+pub use emulator::{
+    BreakCondition, BreakRegister, Chip8Config, Chip8Emulator, Chip8EmulatorBuilder, CmpOp,
+    DecodedInstruction, FrameStatus, InstructionHook, KeyboardState, MemoryFill, Resolution,
+    UnknownOpcodePolicy, HEIGHT, WIDTH,
+};
+pub use error::Chip8Error;
+pub use quirks::Quirks;
@@ -1,23 +1,543 @@
-use crate::emulator::Chip8Emulator;
-use crate::window::Chip8Window;
-use clap::Parser;
-use std::{fs, io};
-use std::io::Write;
-use std::path::PathBuf;
-use std::thread::sleep;
-use std::time::{Duration, Instant};
-
-mod emulator;
-mod window;
+use clap::{Parser, ValueEnum};
+use flate2::read::GzDecoder;
+use iron_chip::audio::{Chip8Audio, DEFAULT_BEEP_FREQUENCY};
+use iron_chip::bench::{self, BenchReport};
+use iron_chip::emulator::{
+    DEFAULT_BACKGROUND_COLOR, DEFAULT_BOTH_PLANES_COLOR, DEFAULT_FOREGROUND_COLOR,
+    DEFAULT_IDLE_LOOP_FRAMES, DEFAULT_INSTRUCTIONS_PER_FRAME, DEFAULT_PLANE_2_COLOR, RAM_SIZE,
+    XO_CHIP_RAM_SIZE,
+};
+use iron_chip::handoff::{Frame, FrameHandoff};
+use iron_chip::input::Chip8Input;
+use iron_chip::overlay::{self, SpeedCounter};
+use iron_chip::renderer::Renderer;
+use iron_chip::replay::{InputLog, InputTimeline};
+use iron_chip::rewind::{RewindBuffer, DEFAULT_REWIND_FRAMES};
+use iron_chip::shutdown::Shutdown;
+use iron_chip::terminal::TerminalRenderer;
+use iron_chip::trace::Tracer;
+use iron_chip::window::{parse_hex_color, parse_scale, Chip8Window};
+use iron_chip::{
+    disasm, headless, keymap, replay, screenshot, BreakCondition, Chip8Config, Chip8Emulator,
+    FrameStatus, KeyboardState, MemoryFill, Quirks, UnknownOpcodePolicy,
+};
+use minifb::{Key, Scale};
+use std::io::{self, Read};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, sleep};
+use std::{fs, process};
+use std::time::{Duration, Instant, SystemTime};
 
 extern crate pretty_env_logger;
 #[macro_use]
 extern crate log;
 
+const PAUSE_KEY: Key = Key::P;
+const STEP_KEY: Key = Key::N;
+const STEP_OVER_KEY: Key = Key::O;
+const BREAKPOINT_KEY: Key = Key::B;
+const MEMORY_DUMP_KEY: Key = Key::M;
+const SAVE_STATE_KEY: Key = Key::F5;
+const LOAD_STATE_KEY: Key = Key::F9;
+const RESET_KEY: Key = Key::F1;
+const SCREENSHOT_KEY: Key = Key::F12;
+const REWIND_KEY: Key = Key::Backspace;
+const STATS_KEY: Key = Key::I;
+const OVERLAY_KEY: Key = Key::F2;
+const TURBO_KEY: Key = Key::Tab;
+const MUTE_KEY: Key = Key::F3;
+const FULLSCREEN_KEY: Key = Key::F11;
+
+/// Hotkeys passed on to the emulator thread. The rest only affect the window and audio, so are
+/// handled on the main thread.
+const EMULATOR_HOTKEYS: [Key; 11] = [
+    PAUSE_KEY,
+    STEP_KEY,
+    STEP_OVER_KEY,
+    BREAKPOINT_KEY,
+    MEMORY_DUMP_KEY,
+    SAVE_STATE_KEY,
+    LOAD_STATE_KEY,
+    RESET_KEY,
+    SCREENSHOT_KEY,
+    STATS_KEY,
+    OVERLAY_KEY,
+];
+
+const INTERVAL: Duration = Duration::from_micros(16667); // 60Hz
+
+/// How many times faster the emulator runs while the turbo key is held
+const TURBO_MULTIPLIER: u32 = 4;
+
+/// Gzipped ROMs start with these bytes, whatever the file is called
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+
+/// How often --watch checks the ROM for changes
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Where the display is drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum RendererKind {
+    /// A window, with the debugging hotkeys
+    Window,
+
+    /// Block characters in the terminal, with no keyboard input
+    Ascii,
+
+    /// Braille patterns in the terminal, a quarter of the size of ascii
+    Braille,
+}
+
 #[derive(Parser, Debug)]
 struct Args {
+    /// ROM to run, or - to read it from stdin. Gzipped ROMs such as game.ch8.gz are decompressed.
     #[arg(long, value_name = "FILE")]
     rom_file: PathBuf,
+
+    /// Emulate the quirks of another interpreter: chip8 (the COSMAC VIP), chip48, superchip or
+    /// xochip. The individual quirk flags below are applied on top. xochip also extends memory to
+    /// 64KB.
+    #[arg(long, value_name = "PRESET")]
+    quirks: Option<Quirks>,
+
+    /// Shift VY into VX for 8XY6 / 8XYE like the COSMAC VIP, rather than shifting VX in place
+    #[arg(long)]
+    shift_quirk: bool,
+
+    /// Leave I incremented after FX55 / FX65 like the COSMAC VIP, rather than unchanged
+    #[arg(long)]
+    load_store_quirk: bool,
+
+    /// Set VF after DXYN when a sprite is clipped off the bottom of the screen like SUPER-CHIP 1.1,
+    /// not only on collisions
+    #[arg(long)]
+    clip_quirk: bool,
+
+    /// Frequency of the tone played while the sound timer is active
+    #[arg(long, value_name = "HZ", default_value_t = DEFAULT_BEEP_FREQUENCY)]
+    beep_frequency: f32,
+
+    /// Start with the sound muted, press F3 to toggle it
+    #[arg(long)]
+    mute: bool,
+
+    /// Color of lit pixels, as #RRGGBB [default: #FFFFFF]
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_color)]
+    fg_color: Option<u32>,
+
+    /// Color of unlit pixels, as #RRGGBB [default: #000000]
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_color)]
+    bg_color: Option<u32>,
+
+    /// Color of XO-CHIP pixels lit on only the second plane, as #RRGGBB [default: #555555]
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_color)]
+    plane2_color: Option<u32>,
+
+    /// Color of XO-CHIP pixels lit on both planes, as #RRGGBB [default: #AAAAAA]
+    #[arg(long, value_name = "COLOR", value_parser = parse_hex_color)]
+    both_planes_color: Option<u32>,
+
+    /// Font to use instead of the built in one, either 80 bytes of small characters or 240 bytes
+    /// with big characters following them
+    #[arg(long, value_name = "FILE")]
+    font_file: Option<PathBuf>,
+
+    /// File remapping keyboard keys onto the keypad, one `<key>=<keypad key>` per line, e.g.
+    /// `A=0x7`. Replaces the default QWERTY layout.
+    #[arg(long, value_name = "FILE")]
+    keymap: Option<PathBuf>,
+
+    /// Where to draw the display
+    #[arg(long, value_enum, default_value_t = RendererKind::Window)]
+    renderer: RendererKind,
+
+    /// Window scale factor, one of 1, 2, 4, 8, 16 or 32
+    #[arg(long, value_name = "FACTOR", value_parser = parse_scale, default_value = "16")]
+    scale: Scale,
+
+    /// Start in a borderless fullscreen window, with the display scaled to fit. F11 switches back
+    /// and forth.
+    #[arg(long)]
+    fullscreen: bool,
+
+    /// Size of the fullscreen window, usually the screen's resolution, e.g. 2560x1440
+    #[arg(long, value_name = "SIZE", value_parser = parse_size, default_value = "1920x1080")]
+    fullscreen_size: (usize, usize),
+
+    /// Only scale the display by whole numbers, so pixels stay square and crisp in fullscreen or a
+    /// resized window. Makes the window resizable, and overrides --scanlines.
+    #[arg(long)]
+    integer_scaling: bool,
+
+    /// Darken every other row of pixels, like the scanlines of a CRT
+    #[arg(long)]
+    scanlines: bool,
+
+    /// Fade pixels out over a few frames instead of turning them off instantly, to reduce flicker
+    #[arg(long)]
+    fade: bool,
+
+    /// Number of instructions to run per 60Hz frame, i.e. the CPU speed
+    #[arg(
+        long,
+        visible_alias = "ipf",
+        value_name = "COUNT",
+        default_value_t = DEFAULT_INSTRUCTIONS_PER_FRAME,
+        value_parser = clap::value_parser!(u16).range(1..),
+    )]
+    instructions_per_frame: u16,
+
+    /// Start paused, and run a single instruction each time N is pressed
+    #[arg(long)]
+    step: bool,
+
+    /// Start paused, press P to start running
+    #[arg(long)]
+    start_paused: bool,
+
+    /// What to do when the ROM runs an unknown opcode: ignore, halt or panic. Halting pauses and
+    /// reports the opcode, which is handy while developing a ROM.
+    #[arg(long, value_name = "POLICY", default_value = "ignore")]
+    on_unknown_opcode: UnknownOpcodePolicy,
+
+    /// What RAM starts out holding outside the font and ROM: zero, random, or a hex byte such as
+    /// 0xAA. Anything but zero catches ROMs that rely on memory being cleared. random follows
+    /// --seed.
+    #[arg(long, value_name = "FILL", default_value = "zero")]
+    memory_fill: MemoryFill,
+
+    /// Run as fast as possible instead of at 60 frames per second. The timers still tick once per
+    /// frame's worth of instructions, and the display is drawn about 60 times a second.
+    #[arg(long)]
+    max_speed: bool,
+
+    /// Treat the program as finished once it has jumped to itself for this many frames, reporting
+    /// it and no longer running the loop. 0 turns this off.
+    #[arg(long, value_name = "FRAMES", default_value_t = DEFAULT_IDLE_LOOP_FRAMES)]
+    idle_loop_frames: u32,
+
+    /// Pause once the program is finished, see --idle-loop-frames
+    #[arg(long)]
+    pause_on_idle_loop: bool,
+
+    /// Address the ROM is loaded at and starts running from. ETI-660 ROMs need 0x600.
+    #[arg(long, value_name = "ADDRESS", value_parser = parse_address, default_value = "0x200")]
+    program_start: u16,
+
+    /// Print a disassembly of the ROM and exit
+    #[arg(long)]
+    disassemble: bool,
+
+    /// Pause before the instruction at this address runs, e.g. 0x2AE. Can be repeated.
+    #[arg(long = "break", value_name = "ADDRESS", value_parser = parse_address)]
+    breakpoints: Vec<u16>,
+
+    /// Pause when a register condition first becomes true, e.g. V5=255 or I>0xF00. With --break,
+    /// only pause at those addresses while the condition holds. Can be repeated, all conditions
+    /// must hold at once.
+    #[arg(long = "break-when", value_name = "CONDITION")]
+    break_conditions: Vec<BreakCondition>,
+
+    /// Pause after any instruction that writes to this address or range, e.g. 0x300 or
+    /// 0x300-0x30F. Can be repeated.
+    #[arg(long = "break-on-write", value_name = "ADDRESS", value_parser = parse_address_range)]
+    watchpoints: Vec<RangeInclusive<u16>>,
+
+    /// Seed for the random number generator, so runs are reproducible
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Write the registers, opcode and mnemonic of every instruction run to this file, for diffing
+    /// against another emulator
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Write the keys held on each frame to this file on exit, for replaying later
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Feed in keys recorded with --record instead of reading the keyboard
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Reload the ROM and reset whenever the file changes
+    #[arg(long)]
+    watch: bool,
+
+    /// Print how often each kind of instruction ran on exit
+    #[arg(long)]
+    stats: bool,
+
+    /// Run without a window, as fast as possible. Add --dump-display to see what it drew.
+    #[arg(long)]
+    headless: bool,
+
+    /// Print the final display to stdout once the run ends, one character per pixel with # for lit
+    /// pixels and . for unlit ones. Pair with --headless --frames N for scripting and diffing.
+    #[arg(long)]
+    dump_display: bool,
+
+    /// Number of frames to run for in headless mode
+    #[arg(long, value_name = "N", default_value_t = 60, requires = "headless")]
+    frames: u32,
+
+    /// Time the ROM running flat out with no window, printing the speed and how long each kind of
+    /// instruction takes
+    #[arg(long, conflicts_with = "headless")]
+    bench: bool,
+
+    /// Number of instructions to run for in bench mode
+    #[arg(long, value_name = "N", default_value_t = 10_000_000, requires = "bench")]
+    bench_instructions: u64,
+}
+
+/// Reads the ROM from `stdin` if the path is `-`, otherwise from the file. Gzipped ROMs are
+/// decompressed.
+fn read_rom(path: &Path, stdin: &mut impl Read) -> io::Result<Vec<u8>> {
+    let rom = if path == Path::new("-") {
+        let mut rom = vec![];
+        stdin.read_to_end(&mut rom)?;
+        rom
+    } else {
+        fs::read(path)?
+    };
+
+    decompress_rom(rom)
+}
+
+/// Decompresses the ROM if it starts with the gzip magic bytes, otherwise returns it untouched
+fn decompress_rom(rom: Vec<u8>) -> io::Result<Vec<u8>> {
+    if !rom.starts_with(&GZIP_MAGIC) {
+        return Ok(rom);
+    }
+
+    let mut decompressed = vec![];
+    GzDecoder::new(&rom[..]).read_to_end(&mut decompressed)?;
+    Ok(decompressed)
+}
+
+/// Polls the ROM file's modification time so it can be reloaded when it changes
+struct RomWatcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_poll: Instant,
+}
+
+impl RomWatcher {
+    fn new(path: PathBuf) -> Self {
+        let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+        Self { path, modified, last_poll: Instant::now() }
+    }
+
+    /// Returns the new ROM if the file has changed since it was last loaded. If the file looks
+    /// like it's still being written, it's retried on the next poll instead.
+    fn poll(&mut self) -> Option<Vec<u8>> {
+        if self.last_poll.elapsed() < WATCH_POLL_INTERVAL {
+            return None;
+        }
+        self.last_poll = Instant::now();
+
+        let modified_time = || fs::metadata(&self.path).and_then(|metadata| metadata.modified()).ok();
+
+        let modified = modified_time()?;
+        if self.modified == Some(modified) {
+            return None;
+        }
+
+        // Editors often truncate the file before writing it out, so an empty read or a change
+        // during the read means the write hasn't finished
+        let rom = fs::read(&self.path).and_then(decompress_rom).ok().filter(|rom| !rom.is_empty())?;
+        if modified_time() != Some(modified) {
+            return None;
+        }
+
+        self.modified = Some(modified);
+        Some(rom)
+    }
+}
+
+/// Formats memory as a hex listing with 16 bytes per line, each prefixed by its address
+fn hex_dump(start: u16, memory: &[u8]) -> String {
+    memory
+        .chunks(16)
+        .enumerate()
+        .map(|(line, bytes)| {
+            let bytes: Vec<String> = bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            format!("{:#05X}: {}", start as usize + line * 16, bytes.join(" "))
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Formats instruction counts as a table, one instruction per line, with its share of the total
+fn format_instruction_stats(stats: &[(&str, u64)]) -> String {
+    let total: u64 = stats.iter().map(|(_, count)| count).sum();
+
+    stats
+        .iter()
+        .map(|(pattern, count)| {
+            format!("{pattern}: {count:>12} {:>6.2}%", *count as f64 * 100.0 / total as f64)
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn format_bench_report(report: &BenchReport) -> String {
+    let mut lines = vec![format!(
+        "{} instructions in {:.2?}, {:.0} instructions per second",
+        report.instructions,
+        report.elapsed,
+        report.instructions_per_second()
+    )];
+
+    lines.extend(report.opcodes.iter().map(|timing| {
+        let (total, average) = (timing.total, timing.average());
+        format!("{}: {:>12} {total:>10.2?} total {average:>10.2?} average", timing.pattern, timing.count)
+    }));
+    lines.join("\n")
+}
+
+/// The input the main thread sends the emulator thread on each pass of its loop
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct Controls {
+    keypad: KeyboardState,
+
+    /// Which of the [`EMULATOR_HOTKEYS`] went down since the last update
+    pressed: Vec<Key>,
+    turbo: bool,
+    rewind: bool,
+}
+
+impl Controls {
+    /// Folds in every update sent since the last frame. Presses from all of them are kept, so none
+    /// are lost if the main thread runs ahead, but held keys are as of the newest. Returns false
+    /// once the main thread has hung up.
+    fn receive(&mut self, receiver: &Receiver<Controls>) -> bool {
+        self.pressed.clear();
+        loop {
+            match receiver.try_recv() {
+                Ok(newer) => {
+                    self.pressed.extend(newer.pressed);
+                    self.keypad = newer.keypad;
+                    self.turbo = newer.turbo;
+                    self.rewind = newer.rewind;
+                }
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Disconnected) => return false,
+            }
+        }
+    }
+
+    fn was_pressed(&self, key: Key) -> bool {
+        self.pressed.contains(&key)
+    }
+}
+
+/// Whether the main loop should go round again: until the window is closed or Ctrl-C is pressed
+fn keep_running(renderer: &dyn Renderer, shutdown: &Shutdown) -> bool {
+    !shutdown.is_requested() && renderer.should_run()
+}
+
+/// What the emulator thread does with the emulator on each pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameAction {
+    /// Step back to the previous frame
+    Rewind,
+
+    /// Run a frame's worth of instructions
+    Run,
+
+    /// Run a single instruction
+    Step,
+
+    /// Leave the emulator alone, just keep the window responsive
+    Idle,
+}
+
+/// Holding the rewind key takes priority over everything else. While paused, pressing the step key
+/// runs one instruction.
+fn frame_action(paused: bool, rewind_held: bool, step_pressed: bool) -> FrameAction {
+    if rewind_held {
+        FrameAction::Rewind
+    } else if !paused {
+        FrameAction::Run
+    } else if step_pressed {
+        FrameAction::Step
+    } else {
+        FrameAction::Idle
+    }
+}
+
+/// Prints why a frame stopped early along with the registers, returning whether it did stop early
+fn print_stop(status: FrameStatus, emulator: &Chip8Emulator) -> bool {
+    match status {
+        FrameStatus::Completed => return false,
+        FrameStatus::HitBreakpoint(address) => {
+            println!("Breakpoint at {address:#05X}: {}", disasm::disassemble(emulator.next_instruction()));
+        }
+        FrameStatus::HitBreakCondition { program_counter } => {
+            println!("Break condition met after {program_counter:#05X}");
+        }
+        FrameStatus::HitWatchpoint { address, program_counter } => {
+            println!("Write to {address:#05X} by {program_counter:#05X}");
+        }
+        FrameStatus::Halted { address, opcode } => {
+            println!("Halted at {address:#05X} on unknown opcode {opcode:04X}");
+        }
+        FrameStatus::IdleLoop { address } => {
+            println!("Program halted, jumping to itself at {address:#05X}");
+        }
+    }
+
+    println!("{}", emulator.register_dump());
+    if !emulator.call_stack().is_empty() {
+        let call_stack: Vec<String> =
+            emulator.call_stack().iter().map(|address| format!("{address:#05X}")).collect();
+        println!("Call stack: {}", call_stack.join(" > "));
+    }
+    true
+}
+
+/// Flushes the trace written by --trace, if any
+fn finish_trace(tracer: Option<&Tracer>) {
+    if let Some(Err(err)) = tracer.map(Tracer::flush) {
+        error!("Couldn't write trace: {err}");
+    }
+}
+
+/// Parses a size in pixels like `1920x1080`
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("Invalid size '{size}', expected WIDTHxHEIGHT, e.g. 1920x1080");
+
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    match (width.parse(), height.parse()) {
+        (Ok(width), Ok(height)) if width > 0 && height > 0 => Ok((width, height)),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses a hex address, with or without a leading 0x
+fn parse_address(address: &str) -> Result<u16, String> {
+    let hex = address.strip_prefix("0x").or_else(|| address.strip_prefix("0X")).unwrap_or(address);
+
+    u16::from_str_radix(hex, 16).map_err(|_| format!("Invalid hex address '{address}'"))
+}
+
+/// Parses a single hex address or an inclusive range of them, e.g. `0x300-0x30F`
+fn parse_address_range(range: &str) -> Result<RangeInclusive<u16>, String> {
+    match range.split_once('-') {
+        Some((start, end)) => {
+            let (start, end) = (parse_address(start)?, parse_address(end)?);
+            if start > end {
+                return Err(format!("Range '{range}' ends before it starts"));
+            }
+            Ok(start..=end)
+        }
+        None => parse_address(range).map(|address| address..=address),
+    }
 }
 
 fn main() {
@@ -25,29 +545,598 @@ fn main() {
     info!("Starting Emulator");
 
     let args = Args::parse();
-    let rom_data = fs::read(args.rom_file).expect("Couldn't read ROM");
+    let fg_color = args.fg_color.unwrap_or(DEFAULT_FOREGROUND_COLOR);
+    let bg_color = args.bg_color.unwrap_or(DEFAULT_BACKGROUND_COLOR);
+
+    let rom_data = read_rom(&args.rom_file, &mut io::stdin()).unwrap_or_else(|err| {
+        error!("Couldn't read ROM {}: {err}", args.rom_file.display());
+        process::exit(1);
+    });
+
+    if args.disassemble {
+        for (address, mnemonic) in disasm::disassemble_rom(&rom_data, args.program_start) {
+            println!("{address:#05X}: {mnemonic}");
+        }
+        return;
+    }
+
+    // The size is checked when the emulator is created
+    let font = args.font_file.as_ref().map(|font_file| {
+        fs::read(font_file).unwrap_or_else(|err| {
+            error!("Couldn't read font {}: {err}", font_file.display());
+            process::exit(1);
+        })
+    });
+
+    let memory_size = if args.quirks == Some(Quirks::xo_chip()) { XO_CHIP_RAM_SIZE } else { RAM_SIZE };
+
+    let mut quirks = args.quirks.unwrap_or_default();
+    quirks.shift_uses_vy |= args.shift_quirk;
+    quirks.load_store_increments_i |= args.load_store_quirk;
+    quirks.clipping_sets_vf |= args.clip_quirk;
+
+    let mut config = Chip8Config {
+        instructions_per_frame: args.instructions_per_frame,
+        quirks,
+        seed: args.seed,
+        foreground_color: fg_color,
+        background_color: bg_color,
+        plane_2_color: args.plane2_color.unwrap_or(DEFAULT_PLANE_2_COLOR),
+        both_planes_color: args.both_planes_color.unwrap_or(DEFAULT_BOTH_PLANES_COLOR),
+        program_start: args.program_start,
+        memory_size,
+        memory_fill: args.memory_fill,
+        unknown_opcode_policy: args.on_unknown_opcode,
+        idle_loop_frames: (args.idle_loop_frames > 0).then_some(args.idle_loop_frames),
+        ..Default::default()
+    };
+    if let Some(font) = font {
+        config.font = font;
+    }
+
+    let mut emulator = Chip8Emulator::try_new(rom_data, config).unwrap_or_else(|err| {
+        error!("Couldn't start the emulator: {err}");
+        process::exit(1);
+    });
+
+    for &address in &args.breakpoints {
+        emulator.add_breakpoint(address);
+    }
+
+    for &condition in &args.break_conditions {
+        emulator.add_break_condition(condition);
+    }
+
+    for addresses in &args.watchpoints {
+        emulator.add_watchpoint_range(addresses.clone());
+    }
+
+    let tracer = args.trace.as_ref().map(|trace_file| {
+        let file = fs::File::create(trace_file).unwrap_or_else(|err| {
+            error!("Couldn't create trace file {}: {err}", trace_file.display());
+            process::exit(1);
+        });
+        Tracer::attach(&mut emulator, file)
+    });
+
+    let replay_inputs = match &args.replay {
+        Some(replay_file) => {
+            let log = fs::read_to_string(replay_file).unwrap_or_else(|err| {
+                error!("Couldn't read input log {}: {err}", replay_file.display());
+                process::exit(1);
+            });
+            replay::decode_inputs(&log).unwrap_or_else(|err| {
+                error!("Couldn't replay {}: {err}", replay_file.display());
+                process::exit(1);
+            })
+        }
+        None => InputLog::default(),
+    };
+
+    let keymap = args.keymap.as_ref().map(|keymap_file| {
+        let text = fs::read_to_string(keymap_file).unwrap_or_else(|err| {
+            error!("Couldn't read keymap {}: {err}", keymap_file.display());
+            process::exit(1);
+        });
+        keymap::parse_keymap(&text).unwrap_or_else(|err| {
+            error!("Couldn't load keymap {}: {err}", keymap_file.display());
+            process::exit(1);
+        })
+    });
+
+    if args.bench {
+        let report = bench::run_benchmark(&mut emulator, args.bench_instructions);
+        println!("{}", format_bench_report(&report));
+        return;
+    }
+
+    // Installed after the benchmark, which doesn't check it, so Ctrl-C still stops that straight away
+    let shutdown = Shutdown::on_ctrl_c().unwrap_or_else(|err| {
+        warn!("Couldn't handle Ctrl-C, it won't shut down cleanly: {err}");
+        Shutdown::default()
+    });
+
+    if args.headless {
+        let status = headless::run_headless_until(&mut emulator, args.frames, replay_inputs.frames(), &shutdown);
+        print_stop(status, &emulator);
+        if args.dump_display {
+            print!("{}", headless::display_to_ascii(&emulator));
+        }
+        if args.stats {
+            println!("{}", format_instruction_stats(&emulator.instruction_stats()));
+        }
+        finish_trace(tracer.as_ref());
+        return;
+    }
+
+    let mut renderer: Box<dyn Renderer> = match args.renderer {
+        RendererKind::Window => {
+            let mut window = Chip8Window::new(args.scale, args.scanlines).unwrap_or_else(|err| {
+                error!("Couldn't open a window: {err}");
+                process::exit(1);
+            });
+            if args.fade {
+                window = window.with_phosphor_fade(fg_color, bg_color);
+            }
+            if let Some(keymap) = keymap {
+                window = window.with_keymap(keymap);
+            }
+            window = window.with_fullscreen_size(args.fullscreen_size.0, args.fullscreen_size.1);
+            if args.integer_scaling {
+                window = window.with_integer_scaling().unwrap_or_else(|err| {
+                    error!("Couldn't open a window: {err}");
+                    process::exit(1);
+                });
+            }
+            if args.fullscreen {
+                if let Err(err) = window.set_fullscreen(true) {
+                    error!("Couldn't go fullscreen: {err}");
+                }
+            }
+            Box::new(window)
+        }
+        RendererKind::Ascii => Box::new(TerminalRenderer::new(bg_color)),
+        RendererKind::Braille => Box::new(TerminalRenderer::new(bg_color).with_braille()),
+    };
+    let mut audio = Chip8Audio::new(args.beep_frequency);
+    audio.set_muted(args.mute);
+    let mut input = Chip8Input::new();
+
+    let stats = args.stats;
+    let dump_display = args.dump_display;
+    let record_file = args.record.clone();
+
+    // The emulator runs on its own thread so a slow draw can't hold it up. It publishes frames to
+    // draw through the handoff, and gets input back through the channel.
+    let handoff = Arc::new(FrameHandoff::default());
+    let (controls_sender, controls_receiver) = mpsc::channel();
+    let emulator_thread = {
+        let handoff = handoff.clone();
+        thread::spawn(move || {
+            run_emulator(emulator, args, replay_inputs, &handoff, &controls_receiver, (fg_color, bg_color))
+        })
+    };
 
-    let mut window = Chip8Window::new();
-    let mut emulator = Chip8Emulator::new(rom_data, 12);
+    // Whether the last frame needs drawing again, e.g. after a failed draw or a new window
+    let mut redraw = false;
+    let mut last_frame: Option<Frame> = None;
 
-    const INTERVAL: Duration = Duration::from_micros(16667); // 60Hz
+    while keep_running(renderer.as_ref(), &shutdown) && !emulator_thread.is_finished() {
+        if let Some(frame) = handoff.take(INTERVAL) {
+            last_frame = Some(frame);
+            redraw = true;
+        }
+
+        match last_frame.as_ref().filter(|_| redraw || renderer.needs_redraw()) {
+            Some(frame) => match renderer.update(&frame.buffer, frame.width, frame.height) {
+                Ok(()) => redraw = false,
+                // A dropped frame isn't worth ending the session over, just try drawing it again
+                Err(err) => warn!("Couldn't draw frame: {err}"),
+            },
+            None => renderer.refresh(),
+        }
+
+        if renderer.was_key_pressed(FULLSCREEN_KEY) {
+            match renderer.toggle_fullscreen() {
+                // The new window starts out blank
+                Ok(()) => redraw = true,
+                Err(err) => error!("Couldn't switch fullscreen: {err}"),
+            }
+        }
+
+        if renderer.was_key_pressed(MUTE_KEY) {
+            audio.set_muted(!audio.is_muted());
+            info!("{}", if audio.is_muted() { "Muted" } else { "Unmuted" });
+        }
+        audio.set_playing(handoff.is_beeping());
+
+        let controls = Controls {
+            keypad: input.keypad(renderer.keyboard_state()),
+            pressed: EMULATOR_HOTKEYS.into_iter().filter(|&key| renderer.was_key_pressed(key)).collect(),
+            turbo: renderer.is_key_down(TURBO_KEY),
+            rewind: renderer.is_key_down(REWIND_KEY),
+        };
+        if controls_sender.send(controls).is_err() {
+            break;
+        }
+    }
+    audio.set_playing(false);
+
+    // Hanging up tells the emulator thread to stop
+    drop(controls_sender);
+    let (emulator, recorded_inputs) = emulator_thread.join().unwrap_or_else(|_| {
+        error!("The emulator thread crashed");
+        process::exit(1);
+    });
+
+    if dump_display {
+        print!("{}", headless::display_to_ascii(&emulator));
+    }
+
+    if stats {
+        println!("{}", format_instruction_stats(&emulator.instruction_stats()));
+    }
+
+    finish_trace(tracer.as_ref());
 
-    while window.should_run() {
+    if let (Some(record_file), Some(recorded_inputs)) = (&record_file, &recorded_inputs) {
+        match fs::write(record_file, replay::encode_inputs(recorded_inputs)) {
+            Ok(()) => info!("Recorded {} frames of input to {}", recorded_inputs.len(), record_file.display()),
+            Err(err) => error!("Couldn't record input to {}: {err}", record_file.display()),
+        }
+    }
+}
+
+/// Runs the emulator at 60Hz on the emulator thread until the main thread hangs up, publishing
+/// frames to `handoff` and acting on the [`Controls`] it's sent. Returns the emulator and, with
+/// --record, every keypad state it was given.
+fn run_emulator(
+    mut emulator: Chip8Emulator,
+    args: Args,
+    replay_inputs: InputLog,
+    handoff: &FrameHandoff,
+    controls_receiver: &Receiver<Controls>,
+    (fg_color, bg_color): (u32, u32),
+) -> (Chip8Emulator, Option<Vec<KeyboardState>>) {
+    let state_file = args.rom_file.with_extension("state");
+
+    // The display starts dirty, so the first frame is still drawn when starting paused
+    let mut paused = args.start_paused || args.step;
+
+    // An idle loop is reported on every frame while it lasts, but only worth printing once
+    let mut idle_loop_reported = false;
+
+    let mut inputs = InputTimeline::new(replay_inputs, args.record.is_some());
+    let mut rewind = RewindBuffer::new(DEFAULT_REWIND_FRAMES);
+
+    let mut watcher = args.watch.then(|| RomWatcher::new(args.rom_file.clone()));
+
+    let mut controls = Controls::default();
+    let mut show_overlay = false;
+    let mut speed = SpeedCounter::default();
+    let mut previous_frame_start_time = Instant::now();
+    let mut previous_instructions_run = 0;
+
+    // Frames are scheduled against a fixed deadline so sleeping doesn't let the rate drift
+    let mut next_frame_time = Instant::now();
+
+    loop {
         let frame_start_time = Instant::now();
 
-        emulator.run_60hz_frame(window.keyboard_state());
-        window.update(&emulator.display_buffer);
-        if emulator.sound_timer > 0 {
-            print!("\x07");
-            io::stdout().flush().unwrap();
+        speed.record_frame(
+            frame_start_time.duration_since(previous_frame_start_time),
+            emulator.instructions_run() - previous_instructions_run,
+        );
+        previous_frame_start_time = frame_start_time;
+        previous_instructions_run = emulator.instructions_run();
+
+        if !controls.receive(controls_receiver) {
+            break;
+        }
+
+        if let Some(rom) = watcher.as_mut().and_then(RomWatcher::poll) {
+            match emulator.load_rom(rom) {
+                Ok(()) => {
+                    rewind = RewindBuffer::new(DEFAULT_REWIND_FRAMES);
+                    info!("Reloaded {}", args.rom_file.display());
+                }
+                Err(err) => error!("Not reloading {}: {err}", args.rom_file.display()),
+            }
+        }
+
+        if controls.was_pressed(OVERLAY_KEY) {
+            show_overlay = !show_overlay;
+            // Redraw so the overlay disappears straight away when hidden
+            emulator.mark_display_dirty();
         }
 
-        let current_runtime = Instant::now().duration_since(frame_start_time);
+        if controls.was_pressed(PAUSE_KEY) {
+            paused = !paused;
+            info!("{}", if paused { "Paused" } else { "Resumed" });
+        }
+
+        if controls.was_pressed(SAVE_STATE_KEY) {
+            match fs::write(&state_file, emulator.snapshot()) {
+                Ok(()) => info!("Saved state to {}", state_file.display()),
+                Err(err) => error!("Couldn't save state to {}: {err}", state_file.display()),
+            }
+        }
+
+        if controls.was_pressed(LOAD_STATE_KEY) {
+            match fs::read(&state_file) {
+                Ok(data) => match emulator.restore(&data) {
+                    Ok(()) => info!("Loaded state from {}", state_file.display()),
+                    Err(err) => error!("Couldn't load state from {}: {err}", state_file.display()),
+                },
+                Err(err) => error!("Couldn't read state from {}: {err}", state_file.display()),
+            }
+        }
+
+        if controls.was_pressed(RESET_KEY) {
+            emulator.reset();
+            info!("Reset");
+        }
+
+        if controls.was_pressed(SCREENSHOT_KEY) {
+            let resolution = emulator.resolution();
+            let (width, height) = (resolution.width(), resolution.height());
+            match screenshot::save_screenshot(emulator.display_buffer(), width, height) {
+                Ok(path) => info!("Saved screenshot to {}", path.display()),
+                Err(err) => error!("Couldn't save screenshot: {err}"),
+            }
+        }
+
+        // Toggles a breakpoint on the next instruction, for use while stepping
+        if paused && controls.was_pressed(BREAKPOINT_KEY) {
+            let address = emulator.program_counter();
+            if emulator.has_breakpoint(address) {
+                emulator.remove_breakpoint(address);
+                println!("Removed breakpoint at {address:#05X}");
+            } else {
+                emulator.add_breakpoint(address);
+                println!("Added breakpoint at {address:#05X}");
+            }
+        }
+
+        // Runs a whole subroutine call while stepping
+        if paused && controls.was_pressed(STEP_OVER_KEY) {
+            let status = emulator.step_over();
+            if !print_stop(status, &emulator) {
+                println!("{}", emulator.register_dump());
+            }
+        }
+
+        // Dumps the memory around I, which is usually the interesting part
+        if controls.was_pressed(MEMORY_DUMP_KEY) {
+            let start = emulator.index_register().saturating_sub(0x10) & !0xF;
+            println!("{}", hex_dump(start, emulator.dump_memory(start, 0x40)));
+        }
+
+        if controls.was_pressed(STATS_KEY) {
+            println!("{}", format_instruction_stats(&emulator.instruction_stats()));
+        }
+
+        // Holding the rewind key steps back a frame at a time instead of running
+        match frame_action(paused, controls.rewind, controls.was_pressed(STEP_KEY)) {
+            FrameAction::Rewind => match rewind.rewind(&mut emulator) {
+                // Step the input back too, so a recording or replay carries on from the same frame
+                Some(frame) => inputs.rewind_to(frame),
+                None => debug!("Nothing left to rewind"),
+            },
+            FrameAction::Run => {
+                rewind.push(&emulator, inputs.frame());
+                // Once a replay runs out, carry on with the live keyboard
+                let mut next_keys = || inputs.next_frame(controls.keypad);
+
+                let status = if args.max_speed {
+                    // Run emulated frames back to back until a frame's worth of time has passed
+                    loop {
+                        let status = emulator.run_60hz_frame(next_keys());
+                        if status != FrameStatus::Completed || frame_start_time.elapsed() >= INTERVAL {
+                            break status;
+                        }
+                    }
+                } else if controls.turbo {
+                    emulator.run_turbo_frame(next_keys(), TURBO_MULTIPLIER)
+                } else {
+                    emulator.run_60hz_frame(next_keys())
+                };
+
+                if let FrameStatus::IdleLoop { .. } = status {
+                    if !idle_loop_reported {
+                        print_stop(status, &emulator);
+                        paused |= args.pause_on_idle_loop;
+                        idle_loop_reported = true;
+                    }
+                } else {
+                    idle_loop_reported = false;
+                    if print_stop(status, &emulator) {
+                        paused = true;
+                    }
+                }
+            }
+            FrameAction::Step => {
+                let (instruction, status) = emulator.step();
+                println!("{instruction:X?}");
+                print_stop(status, &emulator);
+                println!("{}", emulator.register_dump());
+            }
+            FrameAction::Idle => {}
+        }
+
+        // The overlay changes every frame, so is always published
+        if emulator.take_display_dirty() || show_overlay {
+            let resolution = emulator.resolution();
+            let mut buffer = emulator.display_buffer().to_vec();
+            if show_overlay {
+                let colors = (fg_color, bg_color);
+                let fps = format!("{:.0}", speed.frames_per_second());
+                let ips = format!("{:.0}", speed.instructions_per_second());
+                overlay::draw_text(&mut buffer, resolution.width(), (1, 1), &fps, colors);
+                overlay::draw_text(&mut buffer, resolution.width(), (1, 7), &ips, colors);
+            }
+            handoff.publish(Frame { buffer, width: resolution.width(), height: resolution.height() });
+        }
+        handoff.set_beeping(!paused && emulator.is_beeping());
+
+        // Turbo runs frames back to back as fast as possible, and max speed has already used up
+        // the frame's time running. Neither runs anything while paused, so still sleep then.
+        if (controls.turbo || args.max_speed) && !paused {
+            next_frame_time = Instant::now();
+            continue;
+        }
 
-        if current_runtime >= INTERVAL {
-            warn!("WARNING: Exceeded 60Hz Frame! Runtime: {:?}", current_runtime);
+        next_frame_time += INTERVAL;
+        let now = Instant::now();
+        if now >= next_frame_time {
+            warn!("WARNING: Exceeded 60Hz Frame! Runtime: {:?}", now.duration_since(frame_start_time));
+            // Start afresh rather than rushing through frames to catch up
+            next_frame_time = now;
         } else {
-            sleep(INTERVAL - current_runtime);
+            sleep(next_frame_time - now);
         }
     }
+
+    (emulator, inputs.into_recorded())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use iron_chip::window::DEFAULT_FULLSCREEN_SIZE;
+    use std::io::Write;
+
+    #[test]
+    fn test_read_rom() {
+        let mut stdin: &[u8] = &[0x60, 0x12];
+        assert_eq!(read_rom(Path::new("-"), &mut stdin).unwrap(), vec![0x60, 0x12]);
+
+        let path = std::env::temp_dir().join(format!("iron-chip-test-{}.ch8", process::id()));
+        fs::write(&path, [0xA2, 0x00]).unwrap();
+
+        // Stdin is ignored for real paths
+        let mut stdin: &[u8] = &[0x60, 0x12];
+        let rom = read_rom(&path, &mut stdin);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(rom.unwrap(), vec![0xA2, 0x00]);
+        assert!(read_rom(Path::new("does-not-exist.ch8"), &mut stdin).is_err());
+    }
+
+    #[test]
+    fn test_read_rom_gzip() {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(&[0x60, 0x12, 0xA2, 0x00]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(compressed[..2], GZIP_MAGIC);
+
+        let mut stdin = &compressed[..];
+        assert_eq!(read_rom(Path::new("-"), &mut stdin).unwrap(), vec![0x60, 0x12, 0xA2, 0x00]);
+
+        // A corrupt gzip stream is an error rather than being loaded as is
+        let mut stdin = &compressed[..compressed.len() / 2];
+        assert!(read_rom(Path::new("-"), &mut stdin).is_err());
+    }
+
+    #[test]
+    fn test_controls_receive() {
+        let (sender, receiver) = mpsc::channel();
+        let mut controls = Controls::default();
+
+        let mut keypad = [false; 16];
+        keypad[5] = true;
+        sender.send(Controls { pressed: vec![PAUSE_KEY], turbo: true, ..Default::default() }).unwrap();
+        sender.send(Controls { keypad, pressed: vec![STEP_KEY], ..Default::default() }).unwrap();
+
+        // Presses from every update are kept, but held keys come from the newest
+        assert!(controls.receive(&receiver));
+        assert!(controls.was_pressed(PAUSE_KEY) && controls.was_pressed(STEP_KEY));
+        assert_eq!(controls.keypad, keypad);
+        assert!(!controls.turbo);
+
+        // With nothing new, presses are cleared but held keys stay held
+        assert!(controls.receive(&receiver));
+        assert!(controls.pressed.is_empty());
+        assert_eq!(controls.keypad, keypad);
+
+        drop(sender);
+        assert!(!controls.receive(&receiver));
+    }
+
+    #[test]
+    fn test_keep_running() {
+        let renderer = TerminalRenderer::new(DEFAULT_BACKGROUND_COLOR);
+        let shutdown = Shutdown::default();
+        assert!(keep_running(&renderer, &shutdown));
+
+        // Stops even though the renderer would carry on forever
+        shutdown.clone().request();
+        assert!(!keep_running(&renderer, &shutdown));
+    }
+
+    #[test]
+    fn test_frame_action() {
+        let args = Args::parse_from(["iron-chip", "--rom-file", "game.ch8", "--start-paused"]);
+        let paused = args.start_paused;
+
+        assert_eq!(frame_action(paused, false, false), FrameAction::Idle);
+        assert_eq!(frame_action(paused, false, true), FrameAction::Step);
+        assert_eq!(frame_action(paused, true, false), FrameAction::Rewind);
+
+        assert_eq!(frame_action(false, false, false), FrameAction::Run);
+        assert_eq!(frame_action(false, false, true), FrameAction::Run);
+        assert_eq!(frame_action(false, true, false), FrameAction::Rewind);
+    }
+
+    #[test]
+    fn test_hex_dump() {
+        let memory: Vec<u8> = (0..20).collect();
+
+        assert_eq!(
+            hex_dump(0x300, &memory),
+            "0x300: 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F\n0x310: 10 11 12 13"
+        );
+        assert_eq!(hex_dump(0x300, &[]), "");
+    }
+
+    #[test]
+    fn test_format_instruction_stats() {
+        assert_eq!(
+            format_instruction_stats(&[("7XNN", 3), ("1NNN", 1)]),
+            "7XNN:            3  75.00%\n1NNN:            1  25.00%"
+        );
+        assert_eq!(format_instruction_stats(&[]), "");
+    }
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("0x2AE"), Ok(0x2AE));
+        assert_eq!(parse_address("0X2ae"), Ok(0x2AE));
+        assert_eq!(parse_address("2AE"), Ok(0x2AE));
+
+        assert!(parse_address("0x").is_err());
+        assert!(parse_address("0x10000").is_err());
+        assert!(parse_address("xyz").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("1920x1080"), Ok(DEFAULT_FULLSCREEN_SIZE));
+        assert_eq!(parse_size("800x600"), Ok((800, 600)));
+
+        assert!(parse_size("1920").is_err());
+        assert!(parse_size("0x600").is_err());
+        assert!(parse_size("widexhigh").is_err());
+    }
+
+    #[test]
+    fn test_parse_address_range() {
+        assert_eq!(parse_address_range("0x300"), Ok(0x300..=0x300));
+        assert_eq!(parse_address_range("0x300-0x30F"), Ok(0x300..=0x30F));
+
+        assert!(parse_address_range("0x30F-0x300").is_err());
+        assert!(parse_address_range("0x300-").is_err());
+    }
 }
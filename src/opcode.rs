@@ -0,0 +1,397 @@
+use crate::emulator::{Chip8Emulator, DecodedInstruction};
+
+/// A parsed instruction. X and Y are register numbers, N / NN / NNN are the constants encoded in
+/// the instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    /// 00E0
+    ClearScreen,
+    /// 00EE
+    Return,
+    /// 00CN (SUPER-CHIP)
+    ScrollDown(u8),
+    /// 00FB (SUPER-CHIP)
+    ScrollRight,
+    /// 00FC (SUPER-CHIP)
+    ScrollLeft,
+    /// 00FE (SUPER-CHIP)
+    LowResolution,
+    /// 00FF (SUPER-CHIP)
+    HighResolution,
+    /// 0NNN, a call to a machine code routine on the original hardware
+    MachineCall(u16),
+    /// 1NNN
+    Jump(u16),
+    /// 2NNN
+    Call(u16),
+    /// 3XNN
+    SkipIfEqual { x: u8, nn: u8 },
+    /// 4XNN
+    SkipIfNotEqual { x: u8, nn: u8 },
+    /// 5XY0
+    SkipIfRegistersEqual { x: u8, y: u8 },
+    /// 6XNN
+    SetReg { x: u8, nn: u8 },
+    /// 7XNN
+    AddToReg { x: u8, nn: u8 },
+    /// 8XY0
+    CopyReg { x: u8, y: u8 },
+    /// 8XY1
+    Or { x: u8, y: u8 },
+    /// 8XY2
+    And { x: u8, y: u8 },
+    /// 8XY3
+    Xor { x: u8, y: u8 },
+    /// 8XY4
+    Add { x: u8, y: u8 },
+    /// 8XY5
+    Subtract { x: u8, y: u8 },
+    /// 8XY6
+    ShiftRight { x: u8, y: u8 },
+    /// 8XY7
+    SubtractReversed { x: u8, y: u8 },
+    /// 8XYE
+    ShiftLeft { x: u8, y: u8 },
+    /// 9XY0
+    SkipIfRegistersNotEqual { x: u8, y: u8 },
+    /// ANNN
+    SetIndex(u16),
+    /// BNNN, X is only used with the jump quirk
+    JumpWithOffset { x: u8, nnn: u16 },
+    /// CXNN
+    Random { x: u8, nn: u8 },
+    /// DXYN
+    Draw { x: u8, y: u8, n: u8 },
+    /// EX9E
+    SkipIfKeyPressed { x: u8 },
+    /// EXA1
+    SkipIfKeyNotPressed { x: u8 },
+    /// FX07
+    GetDelayTimer { x: u8 },
+    /// FX0A
+    WaitForKey { x: u8 },
+    /// FX15
+    SetDelayTimer { x: u8 },
+    /// FX18
+    SetSoundTimer { x: u8 },
+    /// FX1E
+    AddToIndex { x: u8 },
+    /// FX29
+    SetIndexToFont { x: u8 },
+    /// FX30 (SUPER-CHIP)
+    SetIndexToBigFont { x: u8 },
+    /// FX33
+    StoreBcd { x: u8 },
+    /// FX55
+    StoreRegisters { x: u8 },
+    /// FX65
+    LoadRegisters { x: u8 },
+    /// FX75 (SUPER-CHIP)
+    SaveRplFlags { x: u8 },
+    /// FX85 (SUPER-CHIP)
+    LoadRplFlags { x: u8 },
+    /// F000 NNNN (XO-CHIP), with the address in the two bytes after the instruction
+    SetIndexLong,
+    /// FN01 (XO-CHIP)
+    SelectPlanes(u8),
+    /// Anything that isn't a valid instruction
+    Unknown(u16),
+}
+
+/// How many kinds of instruction [`Opcode`] tells apart, counting [`Opcode::Unknown`]
+pub const OPCODE_KINDS: usize = 46;
+
+/// The pattern identifying each kind of instruction, indexed by [`Opcode::kind`]
+pub const PATTERNS: [&str; OPCODE_KINDS] = [
+    "00E0",
+    "00EE",
+    "00CN",
+    "00FB",
+    "00FC",
+    "00FE",
+    "00FF",
+    "0NNN",
+    "1NNN",
+    "2NNN",
+    "3XNN",
+    "4XNN",
+    "5XY0",
+    "6XNN",
+    "7XNN",
+    "8XY0",
+    "8XY1",
+    "8XY2",
+    "8XY3",
+    "8XY4",
+    "8XY5",
+    "8XY6",
+    "8XY7",
+    "8XYE",
+    "9XY0",
+    "ANNN",
+    "BNNN",
+    "CXNN",
+    "DXYN",
+    "EX9E",
+    "EXA1",
+    "FX07",
+    "FX0A",
+    "FX15",
+    "FX18",
+    "FX1E",
+    "FX29",
+    "FX30",
+    "FX33",
+    "FX55",
+    "FX65",
+    "FX75",
+    "FX85",
+    "F000",
+    "FN01",
+    "????",
+];
+
+/// Parses one class of instruction, i.e. instructions sharing a first nibble
+type ClassParser = fn(DecodedInstruction) -> Opcode;
+
+/// Indexed by the first nibble, so parsing jumps straight to the right class rather than testing
+/// every pattern in turn. This measured around 5-10% more instructions per second with `cargo
+/// bench` than a single match over every pattern.
+const CLASS_PARSERS: [ClassParser; 16] = [
+    parse_0_class,
+    |i| Opcode::Jump(i.nnn_12_bit_address),
+    |i| Opcode::Call(i.nnn_12_bit_address),
+    |i| Opcode::SkipIfEqual { x: i.x_register, nn: i.nn_8_bit_constant },
+    |i| Opcode::SkipIfNotEqual { x: i.x_register, nn: i.nn_8_bit_constant },
+    |i| match i.n_4_bit_constant {
+        0x0 => Opcode::SkipIfRegistersEqual { x: i.x_register, y: i.y_register },
+        _ => Opcode::Unknown(i.raw_instruction),
+    },
+    |i| Opcode::SetReg { x: i.x_register, nn: i.nn_8_bit_constant },
+    |i| Opcode::AddToReg { x: i.x_register, nn: i.nn_8_bit_constant },
+    parse_8_class,
+    |i| match i.n_4_bit_constant {
+        0x0 => Opcode::SkipIfRegistersNotEqual { x: i.x_register, y: i.y_register },
+        _ => Opcode::Unknown(i.raw_instruction),
+    },
+    |i| Opcode::SetIndex(i.nnn_12_bit_address),
+    |i| Opcode::JumpWithOffset { x: i.x_register, nnn: i.nnn_12_bit_address },
+    |i| Opcode::Random { x: i.x_register, nn: i.nn_8_bit_constant },
+    |i| Opcode::Draw { x: i.x_register, y: i.y_register, n: i.n_4_bit_constant },
+    parse_e_class,
+    parse_f_class,
+];
+
+fn parse_0_class(instruction: DecodedInstruction) -> Opcode {
+    match instruction.nnn_12_bit_address {
+        0x0E0 => Opcode::ClearScreen,
+        0x0EE => Opcode::Return,
+        0x0C0..=0x0CF => Opcode::ScrollDown(instruction.n_4_bit_constant),
+        0x0FB => Opcode::ScrollRight,
+        0x0FC => Opcode::ScrollLeft,
+        0x0FE => Opcode::LowResolution,
+        0x0FF => Opcode::HighResolution,
+        nnn => Opcode::MachineCall(nnn),
+    }
+}
+
+fn parse_8_class(instruction: DecodedInstruction) -> Opcode {
+    let (x, y) = (instruction.x_register, instruction.y_register);
+
+    match instruction.n_4_bit_constant {
+        0x0 => Opcode::CopyReg { x, y },
+        0x1 => Opcode::Or { x, y },
+        0x2 => Opcode::And { x, y },
+        0x3 => Opcode::Xor { x, y },
+        0x4 => Opcode::Add { x, y },
+        0x5 => Opcode::Subtract { x, y },
+        0x6 => Opcode::ShiftRight { x, y },
+        0x7 => Opcode::SubtractReversed { x, y },
+        0xE => Opcode::ShiftLeft { x, y },
+        _ => Opcode::Unknown(instruction.raw_instruction),
+    }
+}
+
+fn parse_e_class(instruction: DecodedInstruction) -> Opcode {
+    let x = instruction.x_register;
+
+    match instruction.nn_8_bit_constant {
+        0x9E => Opcode::SkipIfKeyPressed { x },
+        0xA1 => Opcode::SkipIfKeyNotPressed { x },
+        _ => Opcode::Unknown(instruction.raw_instruction),
+    }
+}
+
+fn parse_f_class(instruction: DecodedInstruction) -> Opcode {
+    let x = instruction.x_register;
+
+    match instruction.nn_8_bit_constant {
+        0x07 => Opcode::GetDelayTimer { x },
+        0x0A => Opcode::WaitForKey { x },
+        0x15 => Opcode::SetDelayTimer { x },
+        0x18 => Opcode::SetSoundTimer { x },
+        0x1E => Opcode::AddToIndex { x },
+        0x29 => Opcode::SetIndexToFont { x },
+        0x30 => Opcode::SetIndexToBigFont { x },
+        0x33 => Opcode::StoreBcd { x },
+        0x55 => Opcode::StoreRegisters { x },
+        0x65 => Opcode::LoadRegisters { x },
+        0x75 => Opcode::SaveRplFlags { x },
+        0x85 => Opcode::LoadRplFlags { x },
+        0x00 if x == 0 => Opcode::SetIndexLong,
+        0x01 => Opcode::SelectPlanes(x),
+        _ => Opcode::Unknown(instruction.raw_instruction),
+    }
+}
+
+impl Opcode {
+    pub fn parse(raw: u16) -> Self {
+        let instruction = Chip8Emulator::decode(raw);
+        CLASS_PARSERS[instruction.first_nibble as usize](instruction)
+    }
+
+    /// Which kind of instruction this is, as an index into [`PATTERNS`], so instructions can be
+    /// counted by kind in a small array
+    pub fn kind(&self) -> usize {
+        match self {
+            Opcode::ClearScreen => 0,
+            Opcode::Return => 1,
+            Opcode::ScrollDown(_) => 2,
+            Opcode::ScrollRight => 3,
+            Opcode::ScrollLeft => 4,
+            Opcode::LowResolution => 5,
+            Opcode::HighResolution => 6,
+            Opcode::MachineCall(_) => 7,
+            Opcode::Jump(_) => 8,
+            Opcode::Call(_) => 9,
+            Opcode::SkipIfEqual { .. } => 10,
+            Opcode::SkipIfNotEqual { .. } => 11,
+            Opcode::SkipIfRegistersEqual { .. } => 12,
+            Opcode::SetReg { .. } => 13,
+            Opcode::AddToReg { .. } => 14,
+            Opcode::CopyReg { .. } => 15,
+            Opcode::Or { .. } => 16,
+            Opcode::And { .. } => 17,
+            Opcode::Xor { .. } => 18,
+            Opcode::Add { .. } => 19,
+            Opcode::Subtract { .. } => 20,
+            Opcode::ShiftRight { .. } => 21,
+            Opcode::SubtractReversed { .. } => 22,
+            Opcode::ShiftLeft { .. } => 23,
+            Opcode::SkipIfRegistersNotEqual { .. } => 24,
+            Opcode::SetIndex(_) => 25,
+            Opcode::JumpWithOffset { .. } => 26,
+            Opcode::Random { .. } => 27,
+            Opcode::Draw { .. } => 28,
+            Opcode::SkipIfKeyPressed { .. } => 29,
+            Opcode::SkipIfKeyNotPressed { .. } => 30,
+            Opcode::GetDelayTimer { .. } => 31,
+            Opcode::WaitForKey { .. } => 32,
+            Opcode::SetDelayTimer { .. } => 33,
+            Opcode::SetSoundTimer { .. } => 34,
+            Opcode::AddToIndex { .. } => 35,
+            Opcode::SetIndexToFont { .. } => 36,
+            Opcode::SetIndexToBigFont { .. } => 37,
+            Opcode::StoreBcd { .. } => 38,
+            Opcode::StoreRegisters { .. } => 39,
+            Opcode::LoadRegisters { .. } => 40,
+            Opcode::SaveRplFlags { .. } => 41,
+            Opcode::LoadRplFlags { .. } => 42,
+            Opcode::SetIndexLong => 43,
+            Opcode::SelectPlanes(_) => 44,
+            Opcode::Unknown(_) => 45,
+        }
+    }
+
+    /// The pattern identifying this kind of instruction, e.g. `8XY4`
+    pub fn pattern(&self) -> &'static str {
+        PATTERNS[self.kind()]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Opcode::parse(0x00E0), Opcode::ClearScreen);
+        assert_eq!(Opcode::parse(0x00EE), Opcode::Return);
+        assert_eq!(Opcode::parse(0x00C5), Opcode::ScrollDown(5));
+        assert_eq!(Opcode::parse(0x00FB), Opcode::ScrollRight);
+        assert_eq!(Opcode::parse(0x00FC), Opcode::ScrollLeft);
+        assert_eq!(Opcode::parse(0x00FE), Opcode::LowResolution);
+        assert_eq!(Opcode::parse(0x00FF), Opcode::HighResolution);
+        assert_eq!(Opcode::parse(0x0123), Opcode::MachineCall(0x123));
+        assert_eq!(Opcode::parse(0x1ABC), Opcode::Jump(0xABC));
+        assert_eq!(Opcode::parse(0x2ABC), Opcode::Call(0xABC));
+        assert_eq!(Opcode::parse(0x3312), Opcode::SkipIfEqual { x: 3, nn: 0x12 });
+        assert_eq!(Opcode::parse(0x4312), Opcode::SkipIfNotEqual { x: 3, nn: 0x12 });
+        assert_eq!(Opcode::parse(0x5340), Opcode::SkipIfRegistersEqual { x: 3, y: 4 });
+        assert_eq!(Opcode::parse(0x6312), Opcode::SetReg { x: 3, nn: 0x12 });
+        assert_eq!(Opcode::parse(0x7312), Opcode::AddToReg { x: 3, nn: 0x12 });
+        assert_eq!(Opcode::parse(0x8AB0), Opcode::CopyReg { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x8AB1), Opcode::Or { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x8AB2), Opcode::And { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x8AB3), Opcode::Xor { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x8AB4), Opcode::Add { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x8AB5), Opcode::Subtract { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x8AB6), Opcode::ShiftRight { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x8AB7), Opcode::SubtractReversed { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x8ABE), Opcode::ShiftLeft { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0x9AB0), Opcode::SkipIfRegistersNotEqual { x: 0xA, y: 0xB });
+        assert_eq!(Opcode::parse(0xA123), Opcode::SetIndex(0x123));
+        assert_eq!(Opcode::parse(0xB123), Opcode::JumpWithOffset { x: 1, nnn: 0x123 });
+        assert_eq!(Opcode::parse(0xC3FF), Opcode::Random { x: 3, nn: 0xFF });
+        assert_eq!(Opcode::parse(0xD016), Opcode::Draw { x: 0, y: 1, n: 6 });
+        assert_eq!(Opcode::parse(0xE59E), Opcode::SkipIfKeyPressed { x: 5 });
+        assert_eq!(Opcode::parse(0xE5A1), Opcode::SkipIfKeyNotPressed { x: 5 });
+        assert_eq!(Opcode::parse(0xF507), Opcode::GetDelayTimer { x: 5 });
+        assert_eq!(Opcode::parse(0xF50A), Opcode::WaitForKey { x: 5 });
+        assert_eq!(Opcode::parse(0xF515), Opcode::SetDelayTimer { x: 5 });
+        assert_eq!(Opcode::parse(0xF518), Opcode::SetSoundTimer { x: 5 });
+        assert_eq!(Opcode::parse(0xF51E), Opcode::AddToIndex { x: 5 });
+        assert_eq!(Opcode::parse(0xF529), Opcode::SetIndexToFont { x: 5 });
+        assert_eq!(Opcode::parse(0xF530), Opcode::SetIndexToBigFont { x: 5 });
+        assert_eq!(Opcode::parse(0xF533), Opcode::StoreBcd { x: 5 });
+        assert_eq!(Opcode::parse(0xF555), Opcode::StoreRegisters { x: 5 });
+        assert_eq!(Opcode::parse(0xF565), Opcode::LoadRegisters { x: 5 });
+        assert_eq!(Opcode::parse(0xF575), Opcode::SaveRplFlags { x: 5 });
+        assert_eq!(Opcode::parse(0xF585), Opcode::LoadRplFlags { x: 5 });
+        assert_eq!(Opcode::parse(0xF000), Opcode::SetIndexLong);
+        assert_eq!(Opcode::parse(0xF301), Opcode::SelectPlanes(3));
+    }
+
+    #[test]
+    fn test_pattern() {
+        assert_eq!(Opcode::parse(0x00E0).pattern(), "00E0");
+        assert_eq!(Opcode::parse(0x00C5).pattern(), "00CN");
+        assert_eq!(Opcode::parse(0x8AB4).pattern(), "8XY4");
+        assert_eq!(Opcode::parse(0xD016).pattern(), "DXYN");
+        assert_eq!(Opcode::parse(0xF533).pattern(), "FX33");
+        assert_eq!(Opcode::parse(0xFFFF).pattern(), "????");
+    }
+
+    #[test]
+    fn test_kind() {
+        // Every kind is reachable from some instruction, so no count slot goes unused
+        let mut seen = [false; OPCODE_KINDS];
+        for raw_instruction in 0..=u16::MAX {
+            seen[Opcode::parse(raw_instruction).kind()] = true;
+        }
+        assert!(seen.iter().all(|seen| *seen));
+
+        assert_eq!(PATTERNS[Opcode::parse(0x8AB4).kind()], "8XY4");
+        assert_ne!(Opcode::parse(0x8AB4).kind(), Opcode::parse(0x8AB5).kind());
+        assert_eq!(Opcode::parse(0x1234).kind(), Opcode::parse(0x1FFF).kind());
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        assert_eq!(Opcode::parse(0x5341), Opcode::Unknown(0x5341));
+        assert_eq!(Opcode::parse(0x8AB8), Opcode::Unknown(0x8AB8));
+        assert_eq!(Opcode::parse(0x9AB1), Opcode::Unknown(0x9AB1));
+        assert_eq!(Opcode::parse(0xE500), Opcode::Unknown(0xE500));
+        assert_eq!(Opcode::parse(0xFFFF), Opcode::Unknown(0xFFFF));
+    }
+}
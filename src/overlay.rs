@@ -0,0 +1,130 @@
+use crate::emulator::{FONTS, FONT_CHARACTER_SIZE};
+use std::time::Duration;
+
+/// Font characters are 4 pixels wide, plus a pixel of spacing
+const CHARACTER_ADVANCE: usize = 5;
+
+/// Averages the frame rate and instruction rate over roughly a second, so the numbers are
+/// readable rather than flickering every frame
+#[derive(Debug, Default)]
+pub struct SpeedCounter {
+    frames: u32,
+    instructions: u64,
+    elapsed: Duration,
+
+    frames_per_second: f64,
+    instructions_per_second: f64,
+}
+
+impl SpeedCounter {
+    /// Records a frame that took `frame_time` in total, including any sleep, and ran
+    /// `instructions` instructions. The averages update once a second's worth has been recorded.
+    pub fn record_frame(&mut self, frame_time: Duration, instructions: u64) {
+        self.frames += 1;
+        self.instructions += instructions;
+        self.elapsed += frame_time;
+
+        if self.elapsed >= Duration::from_secs(1) {
+            let seconds = self.elapsed.as_secs_f64();
+            self.frames_per_second = self.frames as f64 / seconds;
+            self.instructions_per_second = self.instructions as f64 / seconds;
+
+            self.frames = 0;
+            self.instructions = 0;
+            self.elapsed = Duration::ZERO;
+        }
+    }
+
+    pub fn frames_per_second(&self) -> f64 {
+        self.frames_per_second
+    }
+
+    pub fn instructions_per_second(&self) -> f64 {
+        self.instructions_per_second
+    }
+}
+
+/// Draws hex digits into a display buffer using the built in font, with a background box so the
+/// text stays readable over the game. Characters that aren't hex digits are left as gaps, and
+/// anything past the edge of the display is clipped.
+pub fn draw_text(
+    buffer: &mut [u32],
+    width: usize,
+    (x, y): (usize, usize),
+    text: &str,
+    (foreground_color, background_color): (u32, u32),
+) {
+    let height = buffer.len() / width;
+
+    for (index, character) in text.chars().enumerate() {
+        let glyph = character.to_digit(16).map(|digit| {
+            let start = digit as usize * FONT_CHARACTER_SIZE as usize;
+            &FONTS[start..start + FONT_CHARACTER_SIZE as usize]
+        });
+
+        for row in 0..FONT_CHARACTER_SIZE as usize {
+            for column in 0..CHARACTER_ADVANCE {
+                let (dest_x, dest_y) = (x + index * CHARACTER_ADVANCE + column, y + row);
+                if dest_x >= width || dest_y >= height {
+                    continue;
+                }
+
+                let is_on = glyph.is_some_and(|glyph| glyph[row] & (0x80 >> column) != 0);
+                buffer[dest_y * width + dest_x] =
+                    if is_on { foreground_color } else { background_color };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_speed_counter() {
+        let mut counter = SpeedCounter::default();
+
+        // Nothing is reported until a second has been recorded
+        for _ in 0..39 {
+            counter.record_frame(Duration::from_millis(25), 10);
+        }
+        assert_eq!(counter.frames_per_second(), 0.0);
+
+        counter.record_frame(Duration::from_millis(25), 10);
+
+        assert_eq!(counter.frames_per_second(), 40.0);
+        assert_eq!(counter.instructions_per_second(), 400.0);
+
+        // The next second is averaged from scratch
+        for _ in 0..4 {
+            counter.record_frame(Duration::from_millis(250), 100);
+        }
+        assert_eq!(counter.frames_per_second(), 4.0);
+        assert_eq!(counter.instructions_per_second(), 400.0);
+    }
+
+    #[test]
+    fn test_draw_text() {
+        let mut buffer = vec![0; 12 * 6];
+        draw_text(&mut buffer, 12, (1, 0), "1 F", (1, 2));
+
+        let rows: Vec<String> = buffer
+            .chunks(12)
+            .map(|row| row.iter().map(|pixel| match pixel { 1 => '#', 2 => '.', _ => ' ' }).collect())
+            .collect();
+
+        // The F is clipped at the right edge
+        assert_eq!(
+            rows,
+            vec![
+                " ..#.......#",
+                " .##.......#",
+                " ..#.......#",
+                " ..#.......#",
+                " .###......#",
+                "            ",
+            ]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::str::FromStr;
+
+/// Behaviors that differ between CHIP-8 interpreters. Different ROMs were written against different
+/// interpreters, so these need to be tuned per ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// 8XY6 and 8XYE copy VY into VX before shifting, rather than shifting VX in place
+    pub shift_uses_vy: bool,
+
+    /// BNNN is treated as BXNN, jumping to XNN + VX rather than NNN + V0
+    pub jump_uses_vx: bool,
+
+    /// FX55 and FX65 leave I incremented by X + 1 rather than unchanged
+    pub load_store_increments_i: bool,
+
+    /// FX0A completes when a key is released rather than when it is pressed
+    pub wait_for_key_release: bool,
+
+    /// FX1E sets VF to 1 when I overflows past 0x0FFF, and 0 otherwise
+    pub index_overflow_sets_vf: bool,
+
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0 as a side effect
+    pub vf_reset_on_logic: bool,
+
+    /// DXYN waits for the vertical blank, so at most one sprite is drawn per frame
+    pub display_wait: bool,
+
+    /// DXYN wraps sprites that run off the edge around to the opposite side, rather than clipping
+    pub wrap_sprites: bool,
+
+    /// DXYN also sets VF to 1 when rows of the sprite are clipped off the bottom of the screen,
+    /// like SUPER-CHIP 1.1, rather than only on collisions. Off by default. Has no effect with
+    /// `wrap_sprites`, since nothing is clipped.
+    pub clipping_sets_vf: bool,
+
+    /// 00CN, 00FB and 00FC scroll by half as far in low resolution, since SUPER-CHIP 1.1 scrolls
+    /// by high resolution pixels even when the display is in low resolution
+    pub half_scroll_in_lores: bool,
+
+    /// DXY0 draws a 16x16 sprite like SUPER-CHIP and XO-CHIP, rather than drawing nothing
+    pub large_sprites: bool,
+}
+
+impl Quirks {
+    /// The original COSMAC VIP interpreter
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            jump_uses_vx: false,
+            load_store_increments_i: true,
+            wait_for_key_release: true,
+            index_overflow_sets_vf: false,
+            vf_reset_on_logic: true,
+            display_wait: true,
+            wrap_sprites: false,
+            clipping_sets_vf: false,
+            half_scroll_in_lores: false,
+            large_sprites: false,
+        }
+    }
+
+    /// The CHIP-48 interpreter for the HP48 calculators
+    pub fn chip48() -> Self {
+        Self {
+            shift_uses_vy: false,
+            jump_uses_vx: true,
+            load_store_increments_i: false,
+            wait_for_key_release: true,
+            index_overflow_sets_vf: false,
+            vf_reset_on_logic: false,
+            display_wait: false,
+            wrap_sprites: false,
+            clipping_sets_vf: false,
+            half_scroll_in_lores: false,
+            large_sprites: false,
+        }
+    }
+
+    /// SUPER-CHIP 1.1, the successor to CHIP-48
+    pub fn super_chip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            jump_uses_vx: true,
+            load_store_increments_i: false,
+            wait_for_key_release: true,
+            index_overflow_sets_vf: false,
+            vf_reset_on_logic: false,
+            display_wait: false,
+            wrap_sprites: false,
+            clipping_sets_vf: true,
+            half_scroll_in_lores: true,
+            large_sprites: true,
+        }
+    }
+
+    /// XO-CHIP, as implemented by Octo
+    pub fn xo_chip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            jump_uses_vx: false,
+            load_store_increments_i: true,
+            wait_for_key_release: true,
+            index_overflow_sets_vf: false,
+            vf_reset_on_logic: false,
+            display_wait: false,
+            wrap_sprites: true,
+            clipping_sets_vf: false,
+            half_scroll_in_lores: false,
+            large_sprites: true,
+        }
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = String;
+
+    /// Looks up a preset by name: `chip8`, `chip48`, `superchip` or `xochip`
+    fn from_str(preset: &str) -> Result<Self, Self::Err> {
+        match preset {
+            "chip8" => Ok(Quirks::cosmac_vip()),
+            "chip48" => Ok(Quirks::chip48()),
+            "superchip" => Ok(Quirks::super_chip()),
+            "xochip" => Ok(Quirks::xo_chip()),
+            _ => Err(format!(
+                "Unknown quirks preset '{preset}', must be one of chip8, chip48, superchip or xochip"
+            )),
+        }
+    }
+}
+
+impl Default for Quirks {
+    /// The behavior most ROMs in the wild expect
+    fn default() -> Self {
+        Self {
+            shift_uses_vy: false,
+            jump_uses_vx: false,
+            load_store_increments_i: false,
+            wait_for_key_release: true,
+            index_overflow_sets_vf: false,
+            vf_reset_on_logic: false,
+            display_wait: false,
+            wrap_sprites: false,
+            clipping_sets_vf: false,
+            half_scroll_in_lores: false,
+            large_sprites: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_preset() {
+        let chip8: Quirks = "chip8".parse().unwrap();
+        assert!(chip8.shift_uses_vy);
+        assert!(!chip8.jump_uses_vx);
+        assert!(chip8.load_store_increments_i);
+        assert!(chip8.vf_reset_on_logic);
+        assert!(chip8.display_wait);
+        assert!(!chip8.wrap_sprites);
+        assert!(!chip8.large_sprites);
+
+        let chip48: Quirks = "chip48".parse().unwrap();
+        assert!(!chip48.shift_uses_vy);
+        assert!(chip48.jump_uses_vx);
+        assert!(!chip48.load_store_increments_i);
+        assert!(!chip48.display_wait);
+        assert!(!chip48.half_scroll_in_lores);
+        assert!(!chip48.large_sprites);
+
+        let superchip: Quirks = "superchip".parse().unwrap();
+        assert!(superchip.jump_uses_vx);
+        assert!(!superchip.vf_reset_on_logic);
+        assert!(superchip.half_scroll_in_lores);
+        assert!(superchip.large_sprites);
+        assert!(superchip.clipping_sets_vf);
+
+        let xochip: Quirks = "xochip".parse().unwrap();
+        assert!(xochip.shift_uses_vy);
+        assert!(!xochip.jump_uses_vx);
+        assert!(xochip.load_store_increments_i);
+        assert!(xochip.wrap_sprites);
+        assert!(!xochip.display_wait);
+        assert!(!xochip.half_scroll_in_lores);
+        assert!(xochip.large_sprites);
+        assert!(!xochip.clipping_sets_vf);
+    }
+
+    #[test]
+    fn test_parse_unknown_preset() {
+        assert!("schip".parse::<Quirks>().is_err());
+        assert!("".parse::<Quirks>().is_err());
+    }
+}
@@ -0,0 +1,110 @@
+use crate::emulator::{Chip8Emulator, KeyboardState};
+use minifb::Key;
+use std::error::Error;
+
+/// A backend the display is drawn to, which also supplies the keyboard input
+pub trait Renderer {
+    /// Draws a `width` x `height` buffer of ARGB pixels
+    fn update(&mut self, buffer: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>>;
+
+    /// Whether the main loop should keep going, e.g. false once the window is closed
+    fn should_run(&self) -> bool;
+
+    fn keyboard_state(&self) -> KeyboardState;
+
+    /// Processes input without redrawing, for frames where the display hasn't changed
+    fn refresh(&mut self) {}
+
+    /// Whether the renderer wants drawing again even though the display hasn't changed, e.g. to
+    /// finish an animation
+    fn needs_redraw(&self) -> bool {
+        false
+    }
+
+    /// Whether the key went down since the last update. Renderers without a keyboard never
+    /// report any keys.
+    fn was_key_pressed(&self, _key: Key) -> bool {
+        false
+    }
+
+    fn is_key_down(&self, _key: Key) -> bool {
+        false
+    }
+
+    /// Switches between fullscreen and a normal window. Renderers without a window ignore this.
+    fn toggle_fullscreen(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Draws the emulator's display if it's changed or the renderer asked for a redraw, otherwise just
+/// refreshes the renderer. Returns whether a frame was drawn.
+pub fn draw_if_dirty(emulator: &mut Chip8Emulator, renderer: &mut dyn Renderer) -> Result<bool, Box<dyn Error>> {
+    if emulator.take_display_dirty() || renderer.needs_redraw() {
+        let resolution = emulator.resolution();
+        renderer.update(emulator.display_buffer(), resolution.width(), resolution.height())?;
+        Ok(true)
+    } else {
+        renderer.refresh();
+        Ok(false)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::{Chip8Config, HEIGHT, WIDTH};
+
+    /// Keeps every frame it's given, so tests can check what would have been drawn
+    #[derive(Default)]
+    struct MockRenderer {
+        frames: Vec<(Vec<u32>, usize, usize)>,
+        refreshes: usize,
+    }
+
+    impl Renderer for MockRenderer {
+        fn update(&mut self, buffer: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>> {
+            self.frames.push((buffer.to_vec(), width, height));
+            Ok(())
+        }
+
+        fn should_run(&self) -> bool {
+            true
+        }
+
+        fn keyboard_state(&self) -> KeyboardState {
+            [false; 16]
+        }
+
+        fn refresh(&mut self) {
+            self.refreshes += 1;
+        }
+    }
+
+    #[test]
+    fn test_draw_if_dirty() {
+        let program = vec![
+            0x60, 0x00, // V0 = 0
+            0xF0, 0x29, // I = sprite for V0
+            0xD0, 0x05, // Draw the 0 at (0, 0)
+            0x12, 0x06, // Jump to 0x206, i.e. infinite loop
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        let mut renderer = MockRenderer::default();
+
+        emulator.run_60hz_frame([false; 16]);
+        assert!(draw_if_dirty(&mut emulator, &mut renderer).unwrap());
+
+        let (buffer, width, height) = &renderer.frames[0];
+        assert_eq!((*width, *height), (WIDTH, HEIGHT));
+        assert_eq!(buffer, emulator.display_buffer());
+        assert_ne!(buffer[0], buffer[4]);
+
+        // Nothing changed, so nothing more is drawn
+        emulator.run_60hz_frame([false; 16]);
+        assert!(!draw_if_dirty(&mut emulator, &mut renderer).unwrap());
+        assert_eq!(renderer.frames.len(), 1);
+        assert_eq!(renderer.refreshes, 1);
+    }
+}
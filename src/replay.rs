@@ -0,0 +1,223 @@
+use crate::emulator::KeyboardState;
+use std::error::Error;
+use std::fmt;
+use std::iter::{self, RepeatN};
+use std::vec;
+
+/// An input log that couldn't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InputLogError {
+    /// 1-based line number of the malformed run
+    pub line: usize,
+}
+
+impl fmt::Display for InputLogError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "malformed input log on line {}", self.line)
+    }
+}
+
+impl Error for InputLogError {}
+
+/// A parsed input log. Runs are kept as they are and only expanded into frames while replaying,
+/// so a damaged log with a huge frame count can't use up all the memory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputLog {
+    runs: Vec<(u32, KeyboardState)>,
+}
+
+impl InputLog {
+    /// The keyboard state of each frame, in order
+    pub fn frames(&self) -> impl Iterator<Item = KeyboardState> + '_ {
+        self.runs.iter().flat_map(|&(count, keys)| iter::repeat_n(keys, count as usize))
+    }
+
+    /// The keyboard state of frame `frame`, or `None` past the end of the log
+    pub fn get(&self, frame: usize) -> Option<KeyboardState> {
+        let mut run_end = 0;
+        for &(count, keys) in &self.runs {
+            run_end += count as usize;
+            if frame < run_end {
+                return Some(keys);
+            }
+        }
+        None
+    }
+}
+
+type Run = (u32, KeyboardState);
+
+impl IntoIterator for InputLog {
+    type Item = KeyboardState;
+    type IntoIter = iter::FlatMap<vec::IntoIter<Run>, RepeatN<KeyboardState>, fn(Run) -> RepeatN<KeyboardState>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.runs.into_iter().flat_map(|(count, keys)| iter::repeat_n(keys, count as usize))
+    }
+}
+
+/// The keyboard state fed to each frame of a session: a replayed log while it lasts, then the live
+/// keyboard. With recording on, every frame's state is kept for writing out as a log. Rewinding
+/// the emulator should rewind this too, so the replay and recording stay in line with it.
+#[derive(Debug, Clone)]
+pub struct InputTimeline {
+    replay: InputLog,
+    recorded: Option<Vec<KeyboardState>>,
+    frame: usize,
+}
+
+impl InputTimeline {
+    pub fn new(replay: InputLog, record: bool) -> Self {
+        Self { replay, recorded: record.then(Vec::new), frame: 0 }
+    }
+
+    /// Number of frames run so far
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// The keyboard state for the next frame, from the replay if it hasn't run out and `live`
+    /// otherwise
+    pub fn next_frame(&mut self, live: KeyboardState) -> KeyboardState {
+        let keys = self.replay.get(self.frame).unwrap_or(live);
+        if let Some(recorded) = &mut self.recorded {
+            recorded.push(keys);
+        }
+        self.frame += 1;
+        keys
+    }
+
+    /// Goes back to `frame`, dropping anything recorded after it and replaying from it again
+    pub fn rewind_to(&mut self, frame: usize) {
+        self.frame = self.frame.min(frame);
+        if let Some(recorded) = &mut self.recorded {
+            recorded.truncate(self.frame);
+        }
+    }
+
+    /// Every frame's keyboard state, or `None` if recording is off
+    pub fn into_recorded(self) -> Option<Vec<KeyboardState>> {
+        self.recorded
+    }
+}
+
+pub fn keys_to_mask(keys: &KeyboardState) -> u16 {
+    keys.iter().enumerate().filter(|(_, held)| **held).fold(0, |mask, (key, _)| mask | 1 << key)
+}
+
+pub fn mask_to_keys(mask: u16) -> KeyboardState {
+    std::array::from_fn(|key| mask & (1 << key) != 0)
+}
+
+/// Serializes the keyboard state of each frame into an input log. Logs are run-length encoded,
+/// one line per run of identical frames: `<frame count> <keys held as a 16 bit hex mask>`, with bit
+/// N set when key N is held.
+pub fn encode_inputs(frames: &[KeyboardState]) -> String {
+    frames
+        .chunk_by(|a, b| a == b)
+        .map(|run| format!("{} {:04X}\n", run.len(), keys_to_mask(&run[0])))
+        .collect()
+}
+
+/// Parses an input log written by [`encode_inputs`]. A run can be at most `u32::MAX` frames, a
+/// couple of years at 60Hz, anything longer means the log is damaged.
+pub fn decode_inputs(log: &str) -> Result<InputLog, InputLogError> {
+    let runs = log
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let error = InputLogError { line: index + 1 };
+
+            let (count, mask) = line.trim().split_once(' ').ok_or(error)?;
+            let count: u32 = count.parse().map_err(|_| error)?;
+            let mask = u16::from_str_radix(mask, 16).map_err(|_| error)?;
+            Ok((count, mask_to_keys(mask)))
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(InputLog { runs })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_keys_to_mask() {
+        let mut keys = [false; 16];
+        assert_eq!(keys_to_mask(&keys), 0);
+
+        keys[0x0] = true;
+        keys[0x5] = true;
+        keys[0xF] = true;
+        assert_eq!(keys_to_mask(&keys), 0x8021);
+        assert_eq!(mask_to_keys(0x8021), keys);
+    }
+
+    #[test]
+    fn test_encode_decode_inputs() {
+        let none = [false; 16];
+        let mut key_5 = [false; 16];
+        key_5[5] = true;
+
+        let frames = vec![none, none, none, key_5, key_5, none];
+        let log = encode_inputs(&frames);
+
+        assert_eq!(log, "3 0000\n2 0020\n1 0000\n");
+        assert_eq!(decode_inputs(&log).unwrap().frames().collect::<Vec<_>>(), frames);
+        assert_eq!(decode_inputs(&log).unwrap().into_iter().collect::<Vec<_>>(), frames);
+        assert_eq!(decode_inputs(""), Ok(InputLog::default()));
+    }
+
+    #[test]
+    fn test_decode_inputs_long_run() {
+        // Expanded while replaying rather than up front, so this doesn't allocate anything big
+        let log = decode_inputs(&format!("{} 0001\n", u32::MAX)).unwrap();
+        assert_eq!(log.frames().take(3).collect::<Vec<_>>(), [mask_to_keys(1); 3]);
+    }
+
+    #[test]
+    fn test_input_log_get() {
+        let log = decode_inputs("2 0001\n1 0002\n").unwrap();
+        assert_eq!(log.get(0), Some(mask_to_keys(1)));
+        assert_eq!(log.get(1), Some(mask_to_keys(1)));
+        assert_eq!(log.get(2), Some(mask_to_keys(2)));
+        assert_eq!(log.get(3), None);
+    }
+
+    #[test]
+    fn test_input_timeline() {
+        let live = mask_to_keys(0x8000);
+        let mut inputs = InputTimeline::new(decode_inputs("3 0001\n").unwrap(), true);
+
+        // The replay is used up first, then the live keyboard takes over
+        let frames: Vec<_> = (0..5).map(|_| inputs.next_frame(live)).collect();
+        assert_eq!(frames, [mask_to_keys(1), mask_to_keys(1), mask_to_keys(1), live, live]);
+        assert_eq!(inputs.frame(), 5);
+
+        // Rewinding into the replay drops the later frames and replays from there again
+        inputs.rewind_to(2);
+        assert_eq!(inputs.frame(), 2);
+        assert_eq!(inputs.next_frame(live), mask_to_keys(1));
+        assert_eq!(inputs.next_frame(live), live);
+
+        let recorded = inputs.into_recorded().unwrap();
+        assert_eq!(recorded, [mask_to_keys(1), mask_to_keys(1), mask_to_keys(1), live]);
+    }
+
+    #[test]
+    fn test_input_timeline_without_recording() {
+        let mut inputs = InputTimeline::new(InputLog::default(), false);
+        inputs.next_frame([false; 16]);
+        assert_eq!(inputs.into_recorded(), None);
+    }
+
+    #[test]
+    fn test_decode_inputs_invalid() {
+        assert_eq!(decode_inputs("3 0000\n2"), Err(InputLogError { line: 2 }));
+        assert_eq!(decode_inputs("x 0000"), Err(InputLogError { line: 1 }));
+        assert_eq!(decode_inputs("1 10000"), Err(InputLogError { line: 1 }));
+        assert_eq!(decode_inputs("18446744073709551615 0000"), Err(InputLogError { line: 1 }));
+        assert_eq!(decode_inputs("1 0000\n4294967296 0000"), Err(InputLogError { line: 2 }));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::emulator::Chip8Emulator;
+use std::collections::VecDeque;
+
+/// About 10 seconds at 60 frames per second
+pub const DEFAULT_REWIND_FRAMES: usize = 600;
+
+/// Holds a snapshot per frame for the last `capacity` frames, dropping the oldest as new ones come
+/// in. Snapshots include all of memory, so they're about 12KB with 4KB of memory and about 72KB
+/// with XO-CHIP's 64KB, putting the default capacity at about 7MB or 44MB.
+pub struct RewindBuffer {
+    snapshots: VecDeque<(usize, Vec<u8>)>,
+    capacity: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self { snapshots: VecDeque::with_capacity(capacity), capacity }
+    }
+
+    /// Records the emulator's current state. Call once per frame, before running it. `frame` is
+    /// handed back when this state is rewound to, for stepping back anything kept alongside the
+    /// emulator.
+    pub fn push(&mut self, emulator: &Chip8Emulator, frame: usize) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((frame, emulator.snapshot()));
+    }
+
+    /// Steps the emulator back one frame, returning the `frame` it was pushed with, or `None`
+    /// once there's no history left
+    pub fn rewind(&mut self, emulator: &mut Chip8Emulator) -> Option<usize> {
+        let (frame, snapshot) = self.snapshots.pop_back()?;
+        emulator.restore(&snapshot).expect("Rewind snapshots are always valid");
+        Some(frame)
+    }
+
+    /// Number of frames that can currently be rewound
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::Chip8Config;
+
+    fn counting_emulator() -> Chip8Emulator {
+        let program = vec![
+            0x70, 0x01, // V0 += 1
+            0x12, 0x00, // Jump to 0x200
+        ];
+
+        // Each frame runs one increment and one jump
+        Chip8Emulator::new(program, Chip8Config { instructions_per_frame: 2, ..Default::default() })
+    }
+
+    #[test]
+    fn test_rewind() {
+        let mut emulator = counting_emulator();
+        let mut rewind = RewindBuffer::new(DEFAULT_REWIND_FRAMES);
+
+        for frame in 0..10 {
+            rewind.push(&emulator, frame);
+            emulator.run_60hz_frame([false; 16]);
+        }
+
+        assert_eq!(emulator.registers()[0], 10);
+        assert_eq!(rewind.len(), 10);
+
+        // Rewinding 3 times restores the state from 3 frames ago
+        for frame in [9, 8, 7] {
+            assert_eq!(rewind.rewind(&mut emulator), Some(frame));
+        }
+
+        assert_eq!(emulator.registers()[0], 7);
+        assert_eq!(rewind.len(), 7);
+
+        // Running again continues on from there
+        rewind.push(&emulator, 7);
+        emulator.run_60hz_frame([false; 16]);
+
+        assert_eq!(emulator.registers()[0], 8);
+
+        while rewind.rewind(&mut emulator).is_some() {}
+
+        assert_eq!(emulator.registers()[0], 0);
+        assert!(rewind.is_empty());
+    }
+
+    #[test]
+    fn test_rewind_capacity() {
+        let mut emulator = counting_emulator();
+        let mut rewind = RewindBuffer::new(4);
+
+        for frame in 0..10 {
+            rewind.push(&emulator, frame);
+            emulator.run_60hz_frame([false; 16]);
+        }
+
+        // Only the last 4 frames are kept
+        assert_eq!(rewind.len(), 4);
+        while rewind.rewind(&mut emulator).is_some() {}
+
+        assert_eq!(emulator.registers()[0], 6);
+    }
+}
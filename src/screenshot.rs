@@ -0,0 +1,55 @@
+use image::{ImageResult, Rgba, RgbaImage};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Each display pixel becomes a SCREENSHOT_SCALE x SCREENSHOT_SCALE block, otherwise the image is
+/// tiny
+pub const SCREENSHOT_SCALE: u32 = 10;
+
+/// Converts an ARGB display buffer into a scaled up image. The display's alpha channel is ignored,
+/// like it is when drawn to the window.
+pub fn display_to_image(buffer: &[u32], width: usize, height: usize, scale: u32) -> RgbaImage {
+    assert_eq!(buffer.len(), width * height);
+
+    RgbaImage::from_fn(width as u32 * scale, height as u32 * scale, |x, y| {
+        let pixel = buffer[(y / scale) as usize * width + (x / scale) as usize];
+        let [_, r, g, b] = pixel.to_be_bytes();
+        Rgba([r, g, b, 0xFF])
+    })
+}
+
+/// Writes the display to a timestamped PNG in the current directory, returning its path
+pub fn save_screenshot(buffer: &[u32], width: usize, height: usize) -> ImageResult<PathBuf> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+    let path = PathBuf::from(format!("screenshot-{timestamp}.png"));
+
+    display_to_image(buffer, width, height, SCREENSHOT_SCALE).save(&path)?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display_to_image() {
+        let buffer = [
+            0x00000000, 0xFFFFFFFF, 0x0033FF66,
+            0xFF33FF66, 0x00000000, 0x00000000,
+        ];
+
+        let image = display_to_image(&buffer, 3, 2, 2);
+
+        assert_eq!(image.dimensions(), (6, 4));
+
+        // Each display pixel covers a 2x2 block
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            assert_eq!(*image.get_pixel(x, y), Rgba([0x00, 0x00, 0x00, 0xFF]));
+        }
+        assert_eq!(*image.get_pixel(2, 0), Rgba([0xFF, 0xFF, 0xFF, 0xFF]));
+        assert_eq!(*image.get_pixel(5, 1), Rgba([0x33, 0xFF, 0x66, 0xFF]));
+        assert_eq!(*image.get_pixel(0, 2), Rgba([0x33, 0xFF, 0x66, 0xFF]));
+        assert_eq!(*image.get_pixel(5, 3), Rgba([0x00, 0x00, 0x00, 0xFF]));
+    }
+}
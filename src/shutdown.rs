@@ -0,0 +1,35 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::process;
+
+/// What shells report for a process killed by SIGINT, 128 plus the signal number
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+/// A flag asking long running loops to stop, so they can finish up cleanly on Ctrl-C: flushing
+/// traces, writing recordings and restoring the terminal. Clones share the same flag.
+#[derive(Debug, Clone, Default)]
+pub struct Shutdown(Arc<AtomicBool>);
+
+impl Shutdown {
+    /// Requests a shutdown on Ctrl-C / SIGINT instead of the process being killed. A second
+    /// Ctrl-C exits straight away, in case shutting down gets stuck. Only one handler can be
+    /// installed per process.
+    pub fn on_ctrl_c() -> Result<Self, ctrlc::Error> {
+        let shutdown = Self::default();
+        let flag = shutdown.0.clone();
+        ctrlc::set_handler(move || {
+            if flag.swap(true, Ordering::Relaxed) {
+                process::exit(INTERRUPTED_EXIT_CODE);
+            }
+        })?;
+        Ok(shutdown)
+    }
+
+    pub fn request(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,193 @@
+use crate::emulator::KeyboardState;
+use crate::renderer::Renderer;
+use std::error::Error;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Roughly 30 frames per second
+const MIN_FRAME_INTERVAL: Duration = Duration::from_millis(33);
+
+/// Clears the screen and hides the cursor so redraws don't flicker
+const ENTER_SEQUENCE: &str = "\x1b[2J\x1b[?25l";
+
+/// Moves the cursor back to the top left, so each frame overwrites the last
+const HOME_SEQUENCE: &str = "\x1b[H";
+
+const SHOW_CURSOR_SEQUENCE: &str = "\x1b[?25h";
+
+/// Renders a display buffer as block characters, packing two rows of pixels into each line of text
+/// so pixels come out roughly square. Anything that isn't the background color counts as lit.
+pub fn display_to_blocks(buffer: &[u32], width: usize, background_color: u32) -> String {
+    let rows: Vec<&[u32]> = buffer.chunks(width).collect();
+
+    rows.chunks(2)
+        .map(|pair| {
+            (0..width)
+                .map(|x| {
+                    let top = pair[0][x] != background_color;
+                    let bottom = pair.get(1).is_some_and(|row| row[x] != background_color);
+                    match (top, bottom) {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    }
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// The Braille dot bit for each pixel in a 2x4 cell, indexed by `[y][x]`. Dots 1 - 6 run down the
+/// two columns of the top three rows, and dots 7 and 8 were tacked onto the bottom row later.
+const BRAILLE_DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+/// The Braille pattern with no dots raised, every other pattern is an offset from it
+const BRAILLE_BLANK: u32 = 0x2800;
+
+/// Renders a display buffer as Braille patterns, packing a 2x4 block of pixels into each
+/// character, so the 64x32 display fits in 32x8 characters. Anything that isn't the background
+/// color counts as lit.
+pub fn display_to_braille(buffer: &[u32], width: usize, background_color: u32) -> String {
+    let height = buffer.len() / width;
+    let is_lit = |x: usize, y: usize| y < height && buffer[y * width + x] != background_color;
+
+    (0..height)
+        .step_by(4)
+        .map(|top| {
+            (0..width)
+                .step_by(2)
+                .map(|left| {
+                    let mut pattern = BRAILLE_BLANK;
+                    for (dy, row) in BRAILLE_DOTS.iter().enumerate() {
+                        for (dx, dot) in row.iter().enumerate() {
+                            if left + dx < width && is_lit(left + dx, top + dy) {
+                                pattern |= dot;
+                            }
+                        }
+                    }
+                    // Every pattern is in the Braille block, so this can't fail
+                    char::from_u32(pattern).unwrap()
+                })
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Draws the display in the terminal instead of a window, for SSH sessions and the like. Each
+/// frame is drawn over the previous one in place. There's no keyboard input.
+pub struct TerminalRenderer {
+    background_color: u32,
+    braille: bool,
+    started: bool,
+    last_draw: Option<Instant>,
+
+    /// Set when a frame came in too soon after the last one, so it can be drawn later
+    frame_pending: bool,
+}
+
+impl TerminalRenderer {
+    pub fn new(background_color: u32) -> Self {
+        Self {
+            background_color,
+            braille: false,
+            started: false,
+            last_draw: None,
+            frame_pending: false,
+        }
+    }
+
+    /// Draws with Braille patterns instead of blocks, for a display a quarter of the size
+    pub fn with_braille(mut self) -> Self {
+        self.braille = true;
+        self
+    }
+}
+
+impl Renderer for TerminalRenderer {
+    /// Frames are dropped to redraw at most about 30 times a second, since terminals struggle to
+    /// keep up with redrawing the whole screen any faster
+    fn update(&mut self, buffer: &[u32], width: usize, _height: usize) -> Result<(), Box<dyn Error>> {
+        if self.last_draw.is_some_and(|last_draw| last_draw.elapsed() < MIN_FRAME_INTERVAL) {
+            self.frame_pending = true;
+            return Ok(());
+        }
+        self.last_draw = Some(Instant::now());
+        self.frame_pending = false;
+
+        let mut stdout = io::stdout().lock();
+
+        if !self.started {
+            stdout.write_all(ENTER_SEQUENCE.as_bytes())?;
+            self.started = true;
+        }
+
+        // Frames can change size with the resolution, so clear anything left below the new frame
+        let frame = if self.braille {
+            display_to_braille(buffer, width, self.background_color)
+        } else {
+            display_to_blocks(buffer, width, self.background_color)
+        };
+        write!(stdout, "{HOME_SEQUENCE}{frame}\x1b[J")?;
+        stdout.flush()?;
+        Ok(())
+    }
+
+    fn should_run(&self) -> bool {
+        true
+    }
+
+    fn keyboard_state(&self) -> KeyboardState {
+        [false; 16]
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.frame_pending
+    }
+}
+
+impl Drop for TerminalRenderer {
+    fn drop(&mut self) {
+        // Nothing useful can be done if stdout has gone away, so don't panic over it
+        if self.started {
+            let _ = writeln!(io::stdout(), "{SHOW_CURSOR_SEQUENCE}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display_to_blocks() {
+        let (on, off) = (0xFFFFFFFF, 0xFF000000);
+        let buffer = [
+            on, on, off, off,
+            on, off, on, off,
+            off, on, on, off,
+        ];
+
+        assert_eq!(display_to_blocks(&buffer, 4, off), "█▀▄ \n ▀▀ ");
+    }
+
+    #[test]
+    fn test_display_to_braille() {
+        let (on, off) = (0xFFFFFFFF, 0xFF000000);
+        let buffer = [
+            on, off, off, off,
+            off, on, off, off,
+            on, off, off, off,
+            on, on, off, on,
+            on, on, off, off,
+            off, off, off, off,
+            off, off, off, off,
+            off, off, off, off,
+        ];
+
+        // Dots 1, 5, 3, 7 and 8, then just dot 8, then dots 1 and 4 on their own
+        assert_eq!(display_to_braille(&buffer, 4, off), "\u{28D5}\u{2880}\n\u{2809}\u{2800}");
+    }
+}
@@ -0,0 +1,104 @@
+use crate::disasm;
+use crate::emulator::{Chip8Emulator, DecodedInstruction};
+use std::io::{self, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+
+/// Formats the state just before `instruction` runs as one trace line: the registers, then the raw
+/// opcode and its mnemonic, e.g. `PC=0200 I=0000 V0=00 ... VF=00 6005 LD V0, 0x05`
+pub fn trace_line(emulator: &Chip8Emulator, instruction: &DecodedInstruction) -> String {
+    let opcode = instruction.raw_instruction;
+    format!("{} {opcode:04X} {}", emulator.register_dump(), disasm::disassemble(opcode))
+}
+
+struct TraceOutput {
+    writer: BufWriter<Box<dyn Write + Send>>,
+
+    /// The first write that failed. Tracing stops there, and the error is reported on flush.
+    error: Option<io::Error>,
+}
+
+/// Writes a [`trace_line`] for every instruction the emulator runs, for diffing a run against a
+/// reference emulator. Writes are buffered, call [`Tracer::flush`] when done.
+pub struct Tracer {
+    output: Arc<Mutex<TraceOutput>>,
+}
+
+impl Tracer {
+    /// Starts tracing through the emulator's pre-hook, replacing any pre-hook already set
+    pub fn attach(emulator: &mut Chip8Emulator, writer: impl Write + Send + 'static) -> Self {
+        let writer: Box<dyn Write + Send> = Box::new(writer);
+        let output = Arc::new(Mutex::new(TraceOutput { writer: BufWriter::new(writer), error: None }));
+
+        let hook_output = output.clone();
+        emulator.set_pre_hook(move |emulator, instruction| {
+            let mut output = hook_output.lock().unwrap();
+            if output.error.is_none() {
+                if let Err(err) = writeln!(output.writer, "{}", trace_line(emulator, instruction)) {
+                    output.error = Some(err);
+                }
+            }
+        });
+
+        Self { output }
+    }
+
+    /// Flushes buffered lines, or returns the error that stopped tracing early
+    pub fn flush(&self) -> io::Result<()> {
+        let mut output = self.output.lock().unwrap();
+        if let Some(err) = output.error.take() {
+            return Err(err);
+        }
+        output.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::emulator::Chip8Config;
+
+    /// Collects the trace in memory so the test can read it back
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_tracer() {
+        let program = vec![
+            0x60, 0x05, // V0 = 5
+            0xA3, 0x00, // I = 0x300
+            0x12, 0x04, // Jump to 0x204
+        ];
+
+        let mut emulator = Chip8Emulator::new(program, Chip8Config::default());
+        let buffer = SharedBuffer::default();
+        let tracer = Tracer::attach(&mut emulator, buffer.clone());
+
+        for _ in 0..4 {
+            emulator.step();
+        }
+        tracer.flush().unwrap();
+
+        let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = trace.lines().collect();
+        assert_eq!(lines.len(), 4);
+
+        let program_counters: Vec<&str> = lines.iter().map(|line| &line[..7]).collect();
+        assert_eq!(program_counters, ["PC=0200", "PC=0202", "PC=0204", "PC=0204"]);
+
+        // Registers are captured before each instruction runs
+        assert!(lines[0].starts_with("PC=0200 I=0000 V0=00 "));
+        assert!(lines[0].ends_with(" 6005 LD V0, 0x05"));
+        assert!(lines[2].starts_with("PC=0204 I=0300 V0=05 "));
+        assert!(lines[2].ends_with(" 1204 JP 0x204"));
+    }
+}
@@ -1,67 +1,611 @@
-use minifb::{Key, Scale, Window, WindowOptions};
+use crate::emulator::{KeyboardState, HEIGHT, WIDTH};
+use crate::keymap::{default_keymap, keypad_state, Keymap};
+use crate::renderer::Renderer;
+use minifb::{Key, KeyRepeat, Scale, Window, WindowOptions};
+use std::error::Error;
 
-pub const WIDTH: usize = 64;
-pub const HEIGHT: usize = 32;
+/// Parses a hex color like `#33FF66` into an ARGB value. Colors without an alpha channel are
+/// fully opaque, and the leading `#` is optional.
+pub fn parse_hex_color(color: &str) -> Result<u32, String> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+
+    let value = u32::from_str_radix(hex, 16)
+        .map_err(|_| format!("Invalid hex color '{color}'"))?;
+
+    match hex.len() {
+        6 => Ok(0xFF000000 | value),
+        8 => Ok(value),
+        _ => Err(format!("Hex color '{color}' must be in the form #RRGGBB or #AARRGGBB")),
+    }
+}
+
+/// Maps an integer scale factor onto the scales minifb supports
+pub fn scale_from_factor(factor: u32) -> Option<Scale> {
+    match factor {
+        1 => Some(Scale::X1),
+        2 => Some(Scale::X2),
+        4 => Some(Scale::X4),
+        8 => Some(Scale::X8),
+        16 => Some(Scale::X16),
+        32 => Some(Scale::X32),
+        _ => None,
+    }
+}
+
+/// The integer factor for a minifb scale, the inverse of [`scale_from_factor`]
+pub fn factor_from_scale(scale: Scale) -> usize {
+    match scale {
+        Scale::X1 => 1,
+        Scale::X2 => 2,
+        Scale::X4 => 4,
+        Scale::X8 => 8,
+        Scale::X16 => 16,
+        Scale::X32 => 32,
+        Scale::FitScreen => 1,
+    }
+}
+
+/// Scales a buffer up by an integer factor, dimming every other row of the result to look like
+/// the scanlines of a CRT
+pub fn scale_with_scanlines(buffer: &[u32], width: usize, height: usize, factor: usize) -> Vec<u32> {
+    let scaled_width = width * factor;
+
+    (0..height * factor)
+        .flat_map(|y| {
+            let row = &buffer[(y / factor) * width..][..width];
+            (0..scaled_width).map(move |x| {
+                let pixel = row[x / factor];
+                if y % 2 == 1 {
+                    // Halve each color channel, leaving alpha alone
+                    (pixel & 0xFF000000) | ((pixel >> 1) & 0x007F7F7F)
+                } else {
+                    pixel
+                }
+            })
+        })
+        .collect()
+}
+
+/// Where a `width` x `height` image lands when scaled up as far as it fits in `target_width` x
+/// `target_height` without changing its aspect ratio, centered with bars on the sides that don't
+/// fit. Returns the x and y offset, then the scaled width and height.
+pub fn letterbox(
+    width: usize,
+    height: usize,
+    target_width: usize,
+    target_height: usize,
+) -> (usize, usize, usize, usize) {
+    // Whichever side runs out of room first limits the scale
+    let (scaled_width, scaled_height) = if target_width * height <= target_height * width {
+        (target_width, target_width * height / width)
+    } else {
+        (target_height * width / height, target_height)
+    };
+
+    ((target_width - scaled_width) / 2, (target_height - scaled_height) / 2, scaled_width, scaled_height)
+}
+
+/// Like [`letterbox`], but only scales by whole numbers so every pixel comes out the same size.
+/// A target smaller than the image leaves it unscaled, cropped to the top left.
+pub fn integer_letterbox(
+    width: usize,
+    height: usize,
+    target_width: usize,
+    target_height: usize,
+) -> (usize, usize, usize, usize) {
+    let factor = (target_width / width).min(target_height / height).max(1);
+    let (scaled_width, scaled_height) = (width * factor, height * factor);
+
+    (
+        target_width.saturating_sub(scaled_width) / 2,
+        target_height.saturating_sub(scaled_height) / 2,
+        scaled_width,
+        scaled_height,
+    )
+}
+
+/// Scales a buffer to fill `target_width` x `target_height` as far as its aspect ratio allows,
+/// filling the bars either side with `bar_color`. See [`letterbox`].
+pub fn scale_letterboxed(
+    buffer: &[u32],
+    size: (usize, usize),
+    target_size: (usize, usize),
+    bar_color: u32,
+) -> Vec<u32> {
+    let placement = letterbox(size.0, size.1, target_size.0, target_size.1);
+    scale_into(buffer, size, target_size, placement, bar_color)
+}
+
+/// Like [`scale_letterboxed`], but by a whole number so pixels stay square and evenly sized. See
+/// [`integer_letterbox`].
+pub fn scale_integer(buffer: &[u32], size: (usize, usize), target_size: (usize, usize), bar_color: u32) -> Vec<u32> {
+    let placement = integer_letterbox(size.0, size.1, target_size.0, target_size.1);
+    scale_into(buffer, size, target_size, placement, bar_color)
+}
+
+/// Draws a buffer scaled to `placement`, an x and y offset and a size, within a buffer of
+/// `target_width` x `target_height` filled with `bar_color`
+fn scale_into(
+    buffer: &[u32],
+    (width, height): (usize, usize),
+    (target_width, target_height): (usize, usize),
+    placement: (usize, usize, usize, usize),
+    bar_color: u32,
+) -> Vec<u32> {
+    let (x_offset, y_offset, scaled_width, scaled_height) = placement;
+
+    (0..target_height)
+        .flat_map(|y| {
+            (0..target_width).map(move |x| {
+                let inside = (x_offset..x_offset + scaled_width).contains(&x)
+                    && (y_offset..y_offset + scaled_height).contains(&y);
+                if inside {
+                    // Nearest neighbour, so pixels stay sharp
+                    let source_x = (x - x_offset) * width / scaled_width;
+                    let source_y = (y - y_offset) * height / scaled_height;
+                    buffer[source_y * width + source_x]
+                } else {
+                    bar_color
+                }
+            })
+        })
+        .collect()
+}
+
+/// Fullscreen windows are opened at this size. minifb can't ask how big the screen is, but the
+/// window is resizable so the window manager can fit it to the screen.
+pub const DEFAULT_FULLSCREEN_SIZE: (usize, usize) = (1920, 1080);
+
+/// Color of the bars either side of the display in fullscreen
+const LETTERBOX_COLOR: u32 = 0xFF000000;
+
+/// How much of its brightness an unlit pixel keeps each frame with phosphor fade on
+const FADE_DECAY: f32 = 0.6;
+
+/// Below this a fading pixel is treated as fully off, so fades finish rather than trailing off
+/// forever
+const FADE_CUTOFF: f32 = 0.05;
+
+/// The brightness of a pixel on the next frame, from 1.0 for fully lit to 0.0 for fully off. Lit
+/// pixels light up instantly, while unlit pixels fade out over a few frames like a CRT's phosphor.
+pub fn fade_intensity(intensity: f32, lit: bool) -> f32 {
+    if lit {
+        1.0
+    } else if intensity * FADE_DECAY < FADE_CUTOFF {
+        0.0
+    } else {
+        intensity * FADE_DECAY
+    }
+}
+
+/// Mixes two ARGB colors channel by channel, `intensity` of the way from `background` to
+/// `foreground`
+pub fn blend_colors(foreground: u32, background: u32, intensity: f32) -> u32 {
+    (0..4).fold(0, |color, channel| {
+        let shift = channel * 8;
+        let foreground = ((foreground >> shift) & 0xFF) as f32;
+        let background = ((background >> shift) & 0xFF) as f32;
+        let mixed = (background + (foreground - background) * intensity).round() as u32;
+        color | (mixed << shift)
+    })
+}
+
+/// Softens the flicker of sprites being erased and redrawn by fading pixels out rather than
+/// turning them off instantly. This only affects what's drawn, the emulator's display buffer is
+/// left alone so collisions work as normal.
+#[derive(Debug)]
+pub struct PhosphorFade {
+    foreground_color: u32,
+    background_color: u32,
+    intensities: Vec<f32>,
+}
+
+impl PhosphorFade {
+    pub fn new(foreground_color: u32, background_color: u32) -> Self {
+        Self { foreground_color, background_color, intensities: vec![] }
+    }
+
+    /// Advances the fade by a frame and returns the buffer to draw. Anything that isn't the
+    /// background color counts as lit, and is drawn as is.
+    pub fn apply(&mut self, buffer: &[u32]) -> Vec<u32> {
+        // The resolution changed, so there's nothing sensible to fade from
+        if self.intensities.len() != buffer.len() {
+            self.intensities = vec![0.0; buffer.len()];
+        }
+
+        buffer
+            .iter()
+            .zip(self.intensities.iter_mut())
+            .map(|(&pixel, intensity)| {
+                let lit = pixel != self.background_color;
+                *intensity = fade_intensity(*intensity, lit);
+
+                if lit {
+                    pixel
+                } else {
+                    blend_colors(self.foreground_color, self.background_color, *intensity)
+                }
+            })
+            .collect()
+    }
+
+    /// Whether any pixels are partway through fading out, and so need redrawing even if the
+    /// display hasn't changed
+    pub fn is_fading(&self) -> bool {
+        self.intensities.iter().any(|&intensity| intensity > 0.0 && intensity < 1.0)
+    }
+}
+
+/// Parses a window scale factor from the command line
+pub fn parse_scale(factor: &str) -> Result<Scale, String> {
+    factor
+        .parse()
+        .ok()
+        .and_then(scale_from_factor)
+        .ok_or_else(|| format!("Unsupported scale '{factor}', must be one of 1, 2, 4, 8, 16 or 32"))
+}
 
 pub struct Chip8Window {
     window: Window,
+
+    /// With scanlines the window is opened at its full size and buffers are scaled up by hand,
+    /// rather than letting minifb scale them
+    scanlines: bool,
+    width: usize,
+    height: usize,
+
+    /// The scale to go back to when leaving fullscreen
+    scale: Scale,
+
+    /// In fullscreen the window is borderless and buffers are letterboxed to whatever size the
+    /// window ends up, by hand
+    fullscreen: bool,
+    fullscreen_size: (usize, usize),
+
+    /// Buffers are scaled by hand to whole multiples of their size, in fullscreen and in a normal
+    /// window that can then be resized
+    integer_scaling: bool,
+
+    fade: Option<PhosphorFade>,
+
+    keymap: Keymap,
 }
 
 impl Chip8Window {
-    pub fn new() -> Self {
-        let mut window = Window::new(
-            "Iron Chip",
-            WIDTH,
-            HEIGHT,
-            WindowOptions { scale: Scale::X16, ..Default::default() },
-        )
-        .unwrap();
+    /// Fails if the display backend can't open a window, e.g. on a machine without a display
+    pub fn new(scale: Scale, scanlines: bool) -> Result<Self, minifb::Error> {
+        let (window, width, height) = Self::open(scale, scanlines, false, None)?;
+
+        Ok(Self {
+            window,
+            scanlines,
+            width,
+            height,
+            scale,
+            fullscreen: false,
+            fullscreen_size: DEFAULT_FULLSCREEN_SIZE,
+            integer_scaling: false,
+            fade: None,
+            keymap: default_keymap(),
+        })
+    }
+
+    /// Opens the window, returning it along with the size of buffer it expects. `fullscreen_size`
+    /// opens a borderless window of that size instead of a normal one, and `resizable` a normal one
+    /// the user can resize.
+    fn open(
+        scale: Scale,
+        scanlines: bool,
+        resizable: bool,
+        fullscreen_size: Option<(usize, usize)>,
+    ) -> Result<(Window, usize, usize), minifb::Error> {
+        let (width, height, options) = match fullscreen_size {
+            Some((width, height)) => {
+                let options = WindowOptions {
+                    borderless: true,
+                    title: false,
+                    resize: true,
+                    topmost: true,
+                    ..Default::default()
+                };
+                (width, height, options)
+            }
+            None if resizable => {
+                let factor = factor_from_scale(scale);
+                (WIDTH * factor, HEIGHT * factor, WindowOptions { resize: true, ..Default::default() })
+            }
+            None if scanlines => {
+                let factor = factor_from_scale(scale);
+                (WIDTH * factor, HEIGHT * factor, WindowOptions::default())
+            }
+            None => (WIDTH, HEIGHT, WindowOptions { scale, ..Default::default() }),
+        };
+
+        let mut window = Window::new("Iron Chip", width, height, options)?;
 
         // Unrestrict this so the main game loop can handle setting FPS
         window.set_target_fps(0);
 
-        Self { window }
+        Ok((window, width, height))
     }
 
-    pub fn should_run(&self) -> bool {
-        self.window.is_open()
+    /// Size of the borderless window opened for fullscreen, usually the screen's resolution
+    pub fn with_fullscreen_size(mut self, width: usize, height: usize) -> Self {
+        self.fullscreen_size = (width, height);
+        self
     }
 
-    pub fn update(&mut self, buffer: &[u32; WIDTH * HEIGHT]) {
-        self.window.update_with_buffer(buffer, WIDTH, HEIGHT).unwrap();
+    /// Switches between a borderless fullscreen window and a normal one, by reopening the window.
+    /// The old window is kept if the new one can't be opened.
+    pub fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), minifb::Error> {
+        if fullscreen == self.fullscreen {
+            return Ok(());
+        }
+
+        let fullscreen_size = fullscreen.then_some(self.fullscreen_size);
+        let (window, width, height) =
+            Self::open(self.scale, self.scanlines, self.integer_scaling, fullscreen_size)?;
+        self.window = window;
+        self.width = width;
+        self.height = height;
+        self.fullscreen = fullscreen;
+        Ok(())
     }
 
-    pub fn keyboard_state(&self) -> [bool; 16] {
-        let keys_down: Vec<u8> = self.window.get_keys().iter().filter_map(|key| {
-            match key {
-                Key::Key1 => Some(0x1),
-                Key::Key2 => Some(0x2),
-                Key::Key3 => Some(0x3),
-                Key::Key4 => Some(0xC),
+    /// Keeps pixels square and evenly sized by only scaling by whole numbers, centering the image
+    /// in whatever space is left over. The window becomes resizable, and scanlines aren't drawn.
+    pub fn with_integer_scaling(mut self) -> Result<Self, minifb::Error> {
+        let fullscreen_size = self.fullscreen.then_some(self.fullscreen_size);
+        let (window, width, height) = Self::open(self.scale, self.scanlines, true, fullscreen_size)?;
+        self.window = window;
+        self.width = width;
+        self.height = height;
+        self.integer_scaling = true;
+        Ok(self)
+    }
 
-                Key::Q => Some(0x4),
-                Key::W => Some(0x5),
-                Key::E => Some(0x6),
-                Key::R => Some(0xD),
+    /// Fades pixels out over a few frames rather than turning them off instantly
+    pub fn with_phosphor_fade(mut self, foreground_color: u32, background_color: u32) -> Self {
+        self.fade = Some(PhosphorFade::new(foreground_color, background_color));
+        self
+    }
 
-                Key::A => Some(0x7),
-                Key::S => Some(0x8),
-                Key::D => Some(0x9),
-                Key::F => Some(0xE),
+    /// Replaces the default QWERTY layout for the keypad
+    pub fn with_keymap(mut self, keymap: Keymap) -> Self {
+        self.keymap = keymap;
+        self
+    }
+}
 
-                Key::Z => Some(0xA),
-                Key::X => Some(0x0),
-                Key::C => Some(0xB),
-                Key::V => Some(0xF),
-                _ => None,
+impl Renderer for Chip8Window {
+    /// Draws a `width` x `height` buffer. The window stays the same size, so high resolution
+    /// buffers are drawn with smaller pixels.
+    ///
+    /// Fails if the display backend couldn't draw the frame, in which case the window is left
+    /// showing the previous frame.
+    fn update(&mut self, buffer: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>> {
+        let faded;
+        let buffer = match &mut self.fade {
+            Some(fade) => {
+                faded = fade.apply(buffer);
+                &faded
             }
-        }).collect();
+            None => buffer,
+        };
+
+        let factor = self.width / width;
+
+        if self.fullscreen || self.integer_scaling {
+            // The window manager or the user may have resized the window
+            let size = self.window.get_size();
+            let scaled = if self.integer_scaling {
+                scale_integer(buffer, (width, height), size, LETTERBOX_COLOR)
+            } else {
+                scale_letterboxed(buffer, (width, height), size, LETTERBOX_COLOR)
+            };
+            self.window.update_with_buffer(&scaled, size.0, size.1)?;
+        } else if self.scanlines && factor > 1 {
+            let scaled = scale_with_scanlines(buffer, width, height, factor);
+            self.window.update_with_buffer(&scaled, self.width, self.height)?;
+        } else {
+            self.window.update_with_buffer(buffer, width, height)?;
+        }
+        Ok(())
+    }
+
+    fn should_run(&self) -> bool {
+        self.window.is_open()
+    }
+
+    fn keyboard_state(&self) -> KeyboardState {
+        keypad_state(&self.keymap, &self.window.get_keys())
+    }
+
+    fn refresh(&mut self) {
+        self.window.update();
+    }
+
+    /// Keeps redrawing until any fading pixels have finished fading out
+    fn needs_redraw(&self) -> bool {
+        self.fade.as_ref().is_some_and(PhosphorFade::is_fading)
+    }
+
+    /// Holding the key down doesn't repeat
+    fn was_key_pressed(&self, key: Key) -> bool {
+        self.window.is_key_pressed(key, KeyRepeat::No)
+    }
+
+    fn is_key_down(&self, key: Key) -> bool {
+        self.window.is_key_down(key)
+    }
+
+    fn toggle_fullscreen(&mut self) -> Result<(), Box<dyn Error>> {
+        Ok(self.set_fullscreen(!self.fullscreen)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#33FF66"), Ok(0xFF33FF66));
+        assert_eq!(parse_hex_color("33ff66"), Ok(0xFF33FF66));
+        assert_eq!(parse_hex_color("#8033FF66"), Ok(0x8033FF66));
+
+        assert!(parse_hex_color("#33FF6").is_err());
+        assert!(parse_hex_color("#33FG66").is_err());
+        assert!(parse_hex_color("").is_err());
+    }
+
+    #[test]
+    fn test_scale_from_factor() {
+        assert!(matches!(scale_from_factor(1), Some(Scale::X1)));
+        assert!(matches!(scale_from_factor(2), Some(Scale::X2)));
+        assert!(matches!(scale_from_factor(4), Some(Scale::X4)));
+        assert!(matches!(scale_from_factor(8), Some(Scale::X8)));
+        assert!(matches!(scale_from_factor(16), Some(Scale::X16)));
+        assert!(matches!(scale_from_factor(32), Some(Scale::X32)));
+
+        assert!(scale_from_factor(0).is_none());
+        assert!(scale_from_factor(3).is_none());
+        assert!(scale_from_factor(64).is_none());
+    }
 
-        let mut ret = [false; 16];
-        for i in keys_down {
-            ret[i as usize] = true;
+    #[test]
+    fn test_factor_from_scale() {
+        for factor in [1, 2, 4, 8, 16, 32] {
+            assert_eq!(factor_from_scale(scale_from_factor(factor).unwrap()), factor as usize);
         }
+    }
+
+    #[test]
+    fn test_scale_with_scanlines() {
+        let buffer = [
+            0xFFFFFFFF, 0xFF000000,
+            0xFF204060, 0xFFFFFFFF,
+        ];
+
+        let scaled = scale_with_scanlines(&buffer, 2, 2, 2);
+
+        assert_eq!(
+            scaled,
+            vec![
+                0xFFFFFFFF, 0xFFFFFFFF, 0xFF000000, 0xFF000000,
+                0xFF7F7F7F, 0xFF7F7F7F, 0xFF000000, 0xFF000000,
+                0xFF204060, 0xFF204060, 0xFFFFFFFF, 0xFFFFFFFF,
+                0xFF102030, 0xFF102030, 0xFF7F7F7F, 0xFF7F7F7F,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_letterbox() {
+        // A 2:1 display on a 16:9 screen gets bars above and below
+        assert_eq!(letterbox(64, 32, 1920, 1080), (0, 60, 1920, 960));
+
+        // On a tall window the bars go on the sides
+        assert_eq!(letterbox(64, 32, 800, 1000), (0, 300, 800, 400));
+        assert_eq!(letterbox(64, 32, 1000, 400), (100, 0, 800, 400));
+
+        // An exact fit has no bars, and high resolution has the same aspect ratio
+        assert_eq!(letterbox(64, 32, 640, 320), (0, 0, 640, 320));
+        assert_eq!(letterbox(128, 64, 1920, 1080), (0, 60, 1920, 960));
+    }
+
+    #[test]
+    fn test_scale_letterboxed() {
+        let buffer = [1, 2];
+        let bar = 0xFF000000;
+
+        let scaled = scale_letterboxed(&buffer, (2, 1), (4, 4), bar);
+
+        assert_eq!(
+            scaled,
+            vec![
+                bar, bar, bar, bar,
+                1, 1, 2, 2,
+                1, 1, 2, 2,
+                bar, bar, bar, bar,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_integer_letterbox() {
+        // 1000x700 isn't a multiple of 64x32, so the largest whole scale that fits is 15
+        assert_eq!(integer_letterbox(64, 32, 1000, 700), (20, 110, 960, 480));
+        assert_eq!(integer_letterbox(64, 32, 1920, 1080), (0, 60, 1920, 960));
+        assert_eq!(integer_letterbox(128, 64, 1000, 700), (52, 126, 896, 448));
+
+        // Too small to fit even once, so drawn unscaled from the corner
+        assert_eq!(integer_letterbox(64, 32, 50, 20), (0, 0, 64, 32));
+    }
+
+    #[test]
+    fn test_scale_integer() {
+        let buffer = [1, 2];
+        let bar = 0xFF000000;
+
+        // 5x3 only fits a scale of 2, leaving a column and a row of bars
+        let scaled = scale_integer(&buffer, (2, 1), (5, 3), bar);
+
+        assert_eq!(
+            scaled,
+            vec![
+                1, 1, 2, 2, bar,
+                1, 1, 2, 2, bar,
+                bar, bar, bar, bar, bar,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fade_intensity() {
+        assert_eq!(fade_intensity(0.0, true), 1.0);
+        assert_eq!(fade_intensity(0.3, true), 1.0);
+
+        // A pixel turning off dims over a few frames, then goes out completely
+        let mut intensity = 1.0;
+        let mut frames = vec![];
+        while intensity > 0.0 {
+            intensity = fade_intensity(intensity, false);
+            frames.push(intensity);
+        }
+        assert_eq!(frames.len(), 6);
+        assert!(frames.windows(2).all(|pair| pair[1] < pair[0]));
+        assert_eq!(fade_intensity(0.0, false), 0.0);
+    }
+
+    #[test]
+    fn test_blend_colors() {
+        assert_eq!(blend_colors(0xFFFFFFFF, 0xFF000000, 1.0), 0xFFFFFFFF);
+        assert_eq!(blend_colors(0xFFFFFFFF, 0xFF000000, 0.0), 0xFF000000);
+        assert_eq!(blend_colors(0xFF33FF66, 0xFF111111, 0.5), 0xFF22883C);
+    }
+
+    #[test]
+    fn test_phosphor_fade() {
+        let (foreground, background) = (0xFFFFFFFF, 0xFF000000);
+        let mut fade = PhosphorFade::new(foreground, background);
+
+        assert_eq!(fade.apply(&[foreground, background]), vec![foreground, background]);
+        assert!(!fade.is_fading());
+
+        // The pixel that turned off is drawn part lit rather than going straight to background
+        let faded = fade.apply(&[background, background]);
+        assert_eq!(faded, vec![0xFF999999, background]);
+        assert!(fade.is_fading());
+
+        assert_eq!(fade.apply(&[foreground, background]), vec![foreground, background]);
+        assert!(!fade.is_fading());
+    }
 
-        ret
+    #[test]
+    fn test_parse_scale() {
+        assert!(matches!(parse_scale("8"), Ok(Scale::X8)));
+        assert!(parse_scale("5").is_err());
+        assert!(parse_scale("big").is_err());
     }
 }
@@ -0,0 +1,53 @@
+//! Golden display tests. Each ROM is run headless for a fixed number of frames and the final
+//! display is compared against a known good hash, to catch rendering regressions.
+//!
+//! To add a ROM, add a test calling `assert_golden` with a placeholder hash, check the display
+//! printed on failure is right, and commit the hash it reports.
+
+use iron_chip::headless::{display_hash, display_to_ascii, run_headless};
+use iron_chip::{Chip8Config, Chip8Emulator, Quirks};
+
+fn assert_golden(name: &str, rom: Vec<u8>, quirks: Quirks, frames: u32, expected_hash: u64) {
+    // Seeded so ROMs using CXNN draw the same thing every run
+    let config = Chip8Config { instructions_per_frame: 10, quirks, seed: Some(0), ..Default::default() };
+    let mut emulator = Chip8Emulator::new(rom, config);
+    run_headless(&mut emulator, frames, &[]);
+
+    let hash = display_hash(&emulator);
+    assert!(
+        hash == expected_hash,
+        "{name} display hash is {hash:#018X}, expected {expected_hash:#018X}. The display was:\n{}",
+        display_to_ascii(&emulator)
+    );
+}
+
+#[test]
+fn test_golden_font_digits() {
+    // Draws the 16 hex digits from the built-in font in two rows of eight
+    let rom = vec![
+        0x60, 0x00, // V0 = 0, the digit
+        0x61, 0x00, // V1 = 0, x
+        0x62, 0x00, // V2 = 0, y
+        0xF0, 0x29, // I = sprite for V0
+        0xD1, 0x25, // Draw the digit at (V1, V2)
+        0x71, 0x08, // x += 8
+        0x70, 0x01, // Next digit
+        0x40, 0x08, // Skip unless on the 9th digit
+        0x61, 0x00, // x = 0
+        0x40, 0x08, // Skip unless on the 9th digit
+        0x62, 0x08, // y = 8
+        0x40, 0x10, // Skip unless all 16 digits are drawn
+        0x12, 0x18, // Jump to 0x218, i.e. infinite loop
+        0x12, 0x06, // Jump to 0x206 for the next digit
+    ];
+
+    assert_golden("font digits", rom, Quirks::default(), 20, 0x2672811F9FB75B99);
+}
+
+#[test]
+fn test_golden_ibm_logo() {
+    // The classic IBM logo test ROM, which only uses 00E0, 1NNN, 6XNN, 7XNN, ANNN and DXYN
+    let rom = include_bytes!("roms/ibm_logo.ch8").to_vec();
+
+    assert_golden("IBM logo", rom, Quirks::default(), 10, 0x8412E0FAF7C00A65);
+}